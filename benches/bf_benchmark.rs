@@ -1,9 +1,16 @@
-use bf_rs::{interpreter::Interpreter, lexer::Lexer, parser::Parser};
+use bf_rs::{
+    bytecode::Bytecode,
+    interpreter::{Interpreter, OutputMode},
+    lexer::Lexer,
+    optimizer::Optimizer,
+    parser::Parser,
+};
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::fs::{File, OpenOptions};
 use std::io;
 
-#[cfg(feature = "optimizer")]
-use bf_rs::optimizer::Optimizer;
+#[cfg(feature = "jit")]
+use bf_rs::jit::JitProgram;
 
 const SAMPLE_SIZE: usize = 10;
 const EXAMPLES: &[(&str, &str)] = &[
@@ -28,7 +35,6 @@ fn bench_full_pipeline(c: &mut Criterion) {
                 let mut parser = Parser::new(tokens);
                 let program = parser.parse().expect("Parsing failed");
 
-                #[cfg(feature = "optimizer")]
                 let program = {
                     let optimizer = Optimizer::new();
                     optimizer.optimize(program)
@@ -76,8 +82,7 @@ fn bench_components(c: &mut Criterion) {
             });
         });
 
-        // Benchmark optimizer (only when the feature is enabled)
-        #[cfg(feature = "optimizer")]
+        // Benchmark optimizer
         {
             let mut parser = Parser::new(tokens.clone());
             let program = parser.parse().expect("Parsing failed");
@@ -98,8 +103,6 @@ fn bench_components(c: &mut Criterion) {
         let program = parser.parse().expect("Parsing failed");
 
         // For execution benchmarks, use both optimized and non-optimized versions
-        // when the optimizer feature is enabled
-        #[cfg(feature = "optimizer")]
         {
             let optimizer = Optimizer::new();
             let optimized_program = optimizer.optimize(program.clone());
@@ -122,6 +125,25 @@ fn bench_components(c: &mut Criterion) {
                     });
                 },
             );
+
+            group.bench_with_input(
+                BenchmarkId::new("execution_bytecode_optimized", name),
+                &Bytecode::compile(&optimized_program),
+                |b, bytecode| {
+                    b.iter(|| {
+                        let mut interpreter = Interpreter::new();
+                        let null_writer = io::sink();
+                        let null_reader = io::empty();
+                        interpreter
+                            .execute_bytecode(
+                                bytecode,
+                                &mut io::BufWriter::new(null_writer),
+                                &mut io::BufReader::new(null_reader),
+                            )
+                            .expect("Execution failed")
+                    });
+                },
+            );
         }
 
         group.bench_with_input(
@@ -143,10 +165,105 @@ fn bench_components(c: &mut Criterion) {
                 });
             },
         );
+
+        // Benchmark the bytecode VM on the same program, excluding the compilation
+        group.bench_with_input(
+            BenchmarkId::new("execution_bytecode", name),
+            &Bytecode::compile(&program),
+            |b, bytecode| {
+                b.iter(|| {
+                    let mut interpreter = Interpreter::new();
+                    let null_writer = io::sink();
+                    let null_reader = io::empty();
+                    interpreter
+                        .execute_bytecode(
+                            bytecode,
+                            &mut io::BufWriter::new(null_writer),
+                            &mut io::BufReader::new(null_reader),
+                        )
+                        .expect("Execution failed")
+                });
+            },
+        );
+
+        // Benchmark the native code on the same program, excluding the compilation
+        #[cfg(feature = "jit")]
+        group.bench_with_input(
+            BenchmarkId::new("execution_jit", name),
+            &JitProgram::compile(&program).expect("Compilation failed"),
+            |b, compiled| {
+                b.iter(|| {
+                    let mut interpreter = Interpreter::new();
+                    let null_writer = io::sink();
+                    let null_reader = io::empty();
+                    interpreter
+                        .execute_jit(
+                            compiled,
+                            &mut io::BufWriter::new(null_writer),
+                            &mut io::BufReader::new(null_reader),
+                        )
+                        .expect("Execution failed")
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/// Opens the platform's null device, so that every flush is a real write syscall
+fn null_device() -> File {
+    let path = if cfg!(windows) { "NUL" } else { "/dev/null" };
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .expect("Failed to open the null device")
+}
+
+/// Benchmark execution with each output mode
+fn bench_output_modes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Output");
+    group.sample_size(SAMPLE_SIZE);
+
+    let modes = [
+        OutputMode::Unbuffered,
+        OutputMode::LineBuffered,
+        OutputMode::FullyBuffered,
+        OutputMode::FlushBeforeInput,
+    ];
+
+    for (name, source) in EXAMPLES.iter() {
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(lexer.tokenize());
+        let program = parser.parse().expect("Parsing failed");
+
+        let program = Optimizer::new().optimize(program);
+
+        for mode in modes {
+            group.bench_with_input(
+                BenchmarkId::new(format!("output_{}", mode), name),
+                &program,
+                |b, program| {
+                    b.iter(|| {
+                        let mut interpreter = Interpreter::new().with_output_mode(mode);
+                        // Buffer like `Interpreter::run` does, flushing according to the mode
+                        let mut writer = io::BufWriter::new(null_device());
+                        interpreter
+                            .execute(program, &mut writer, &mut io::empty())
+                            .expect("Execution failed")
+                    });
+                },
+            );
+        }
     }
 
     group.finish();
 }
 
-criterion_group!(benches, bench_full_pipeline, bench_components);
+criterion_group!(
+    benches,
+    bench_full_pipeline,
+    bench_components,
+    bench_output_modes
+);
 criterion_main!(benches);
@@ -0,0 +1,100 @@
+use crate::bytecode::Instruction;
+use crate::lexer::Span;
+use crate::parser::OptimizedOp;
+use crate::parser::{BfOp, BfOpKind};
+
+/// A program lowered to a flat array of instructions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode {
+    /// The instructions, with absolute jump targets.
+    pub instructions: Vec<Instruction>,
+    /// The source location of each instruction, at the same index.
+    /// Both jumps of a loop carry the span of the whole loop.
+    pub spans: Vec<Span>,
+}
+
+impl Bytecode {
+    /// Lowers a program, optimized or not, to bytecode.
+    ///
+    /// # Details
+    /// Loops are lowered with an explicit stack instead of through recursion,
+    /// so the nesting depth is limited only by the available heap memory.
+    pub fn compile(ops: &[BfOp]) -> Self {
+        let mut bytecode = Bytecode {
+            instructions: Vec::with_capacity(ops.len()),
+            spans: Vec::with_capacity(ops.len()),
+        };
+
+        // The sequences being lowered, with the index of the next op in each,
+        // and the index of the `JumpIfZero` of each enclosing loop
+        let mut frames: Vec<(&[BfOp], usize, usize)> = Vec::new();
+        let mut current = ops;
+        let mut index = 0;
+
+        loop {
+            let Some(op) = current.get(index) else {
+                // End of a sequence: either the program is done, or a loop body is finished
+                let Some((enclosing, loop_index, loop_start)) = frames.pop() else {
+                    break;
+                };
+                let loop_end = bytecode.instructions.len();
+                bytecode.push(
+                    Instruction::JumpIfNonZero(loop_start + 1),
+                    enclosing[loop_index].span,
+                );
+                bytecode.instructions[loop_start] = Instruction::JumpIfZero(loop_end + 1);
+
+                current = enclosing;
+                index = loop_index + 1;
+                continue;
+            };
+
+            match &op.kind {
+                BfOpKind::PointerIncrement(offset) => {
+                    bytecode.push(Instruction::Move(*offset), op.span)
+                }
+                BfOpKind::Increment(count) => bytecode.push(Instruction::Add(count.0), op.span),
+                BfOpKind::OutputByte => bytecode.push(Instruction::Output, op.span),
+                BfOpKind::InputByte => bytecode.push(Instruction::Input, op.span),
+                BfOpKind::Loop(body) => {
+                    // The target is patched once the end of the loop is known
+                    let loop_start = bytecode.instructions.len();
+                    bytecode.push(Instruction::JumpIfZero(usize::MAX), op.span);
+
+                    frames.push((current, index, loop_start));
+                    current = body;
+                    index = 0;
+                    continue;
+                }
+                BfOpKind::Optimized(opt_op) => match opt_op {
+                    OptimizedOp::ClearCell => bytecode.push(Instruction::Clear, op.span),
+                    &OptimizedOp::SetCell(value) => bytecode.push(Instruction::Set(value), op.span),
+                    &OptimizedOp::MulAdd { offset, factor } => {
+                        bytecode.push(Instruction::MulAdd { offset, factor }, op.span)
+                    }
+                    &OptimizedOp::ScanRight(stride) => {
+                        bytecode.push(Instruction::Scan(stride as isize), op.span)
+                    }
+                    &OptimizedOp::ScanLeft(stride) => {
+                        bytecode.push(Instruction::Scan(-(stride as isize)), op.span)
+                    }
+                    &OptimizedOp::AddAt { offset, delta } => {
+                        bytecode.push(Instruction::AddAt { offset, delta }, op.span)
+                    }
+                    &OptimizedOp::OutputAt(offset) => {
+                        bytecode.push(Instruction::OutputAt(offset), op.span)
+                    }
+                },
+            }
+
+            index += 1;
+        }
+
+        bytecode
+    }
+
+    fn push(&mut self, instruction: Instruction, span: Span) {
+        self.instructions.push(instruction);
+        self.spans.push(span);
+    }
+}
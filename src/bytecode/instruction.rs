@@ -0,0 +1,86 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+/// A single bytecode instruction.
+///
+/// Jump targets are absolute indices into the instruction array.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    /// Move the pointer by the given number of cells.
+    Move(isize),
+    /// Add the given delta to the current cell, wrapping around.
+    Add(i64),
+    /// Write the current cell to output.
+    Output,
+    /// Read a byte from input into the current cell.
+    Input,
+    /// Start of a loop: jump to the target, just past the matching `JumpIfNonZero`,
+    /// when the current cell is zero.
+    JumpIfZero(usize),
+    /// End of a loop: jump to the target, just past the matching `JumpIfZero`,
+    /// when the current cell is not zero.
+    JumpIfNonZero(usize),
+
+    // Optimized operations
+    Clear,
+    /// Set the current cell to the given value, wrapping around.
+    Set(i64),
+    /// Add the current cell times `factor` to the cell `offset` cells away,
+    /// unless the current cell is zero.
+    MulAdd {
+        offset: isize,
+        factor: i64,
+    },
+    /// Move the pointer by the given stride until the current cell is zero.
+    Scan(isize),
+    /// Add the given delta to the cell `offset` cells away, wrapping around.
+    AddAt {
+        offset: isize,
+        delta: i64,
+    },
+    /// Write the cell `offset` cells away to output.
+    OutputAt(isize),
+}
+
+impl Instruction {
+    /// Whether the instruction moves the pointer, even if only for a moment, so that
+    /// it can fail or grow the tape.
+    pub(crate) fn moves_pointer(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Move(_)
+                | Instruction::MulAdd { .. }
+                | Instruction::Scan(_)
+                | Instruction::AddAt { .. }
+                | Instruction::OutputAt(_)
+        )
+    }
+
+    /// Whether the instruction works on a cell at an offset from the current one,
+    /// moving there only when that cell is outside the tape.
+    pub(crate) fn uses_offset(&self) -> bool {
+        matches!(
+            self,
+            Instruction::MulAdd { .. } | Instruction::AddAt { .. } | Instruction::OutputAt(_)
+        )
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Move(offset) => write!(f, "move {}", offset),
+            Instruction::Add(delta) => write!(f, "add {}", delta),
+            Instruction::Output => write!(f, "output"),
+            Instruction::Input => write!(f, "input"),
+            Instruction::JumpIfZero(target) => write!(f, "jz {}", target),
+            Instruction::JumpIfNonZero(target) => write!(f, "jnz {}", target),
+            Instruction::Clear => write!(f, "clear"),
+            Instruction::Set(value) => write!(f, "set {}", value),
+            Instruction::MulAdd { offset, factor } => write!(f, "muladd {} {}", offset, factor),
+            Instruction::Scan(stride) => write!(f, "scan {}", stride),
+            Instruction::AddAt { offset, delta } => write!(f, "addat {} {}", offset, delta),
+            Instruction::OutputAt(offset) => write!(f, "outputat {}", offset),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+//! Brainfuck bytecode.
+//!
+//! This module lowers a tree of `BfOp` into a flat array of instructions
+//! with precomputed jump targets, which `Interpreter::execute_bytecode` runs
+//! in a single dispatch loop.
+
+mod compiler;
+mod instruction;
+
+pub use compiler::Bytecode;
+pub use instruction::Instruction;
@@ -0,0 +1,281 @@
+//! Command-line argument parsing for the `bf-rs` binary.
+
+use bf_rs::codegen::Emit;
+use bf_rs::interpreter::{CellWidth, EofPolicy, OutputMode, TapeConfig};
+use bf_rs::optimizer::{OptimizationLevel, Optimizer};
+use std::fmt;
+use std::fmt::Formatter;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How the program is executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Walk the tree of operations directly, as a reference.
+    #[default]
+    Tree,
+    /// Compile to flat bytecode first, then run it in a dispatch loop.
+    Bytecode,
+    /// Compile to native code, available with the `jit` feature.
+    #[cfg(feature = "jit")]
+    Jit,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::Tree => write!(f, "tree"),
+            Backend::Bytecode => write!(f, "bytecode"),
+            #[cfg(feature = "jit")]
+            Backend::Jit => write!(f, "jit"),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tree" => Ok(Backend::Tree),
+            "bytecode" => Ok(Backend::Bytecode),
+            #[cfg(feature = "jit")]
+            "jit" => Ok(Backend::Jit),
+            _ => Err(format!(
+                "invalid backend `{}`, expected {}",
+                s,
+                if cfg!(feature = "jit") {
+                    "tree, bytecode or jit"
+                } else {
+                    "tree or bytecode"
+                }
+            )),
+        }
+    }
+}
+
+/// What the binary does with the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Run the program.
+    Run,
+    /// Compile the program ahead of time.
+    Build {
+        /// The kind of output to produce.
+        emit: Emit,
+        /// Where to write the output, next to the program by default.
+        output: Option<String>,
+    },
+}
+
+/// Options selected on the command line.
+#[derive(Debug)]
+pub struct Options {
+    /// What to do with the program.
+    pub command: Command,
+    /// Path of the Brainfuck program to run.
+    pub path: String,
+    /// Width of the interpreter's memory cells.
+    pub cell_width: CellWidth,
+    /// What `,` does once the input is exhausted.
+    pub eof_policy: EofPolicy,
+    /// Layout of the interpreter's tape.
+    pub tape: TapeConfig,
+    /// Maximum number of operations to execute.
+    pub fuel: Option<u64>,
+    /// Maximum wall-clock time of the execution.
+    pub timeout: Option<Duration>,
+    /// When the output is flushed.
+    pub output_mode: OutputMode,
+    /// How the program is executed.
+    pub backend: Backend,
+    /// The optimizer for the selected level and passes.
+    pub optimizer: Optimizer,
+    /// Whether to print what the optimizer did.
+    pub optimization_report: bool,
+    /// Problems with the options that are not worth stopping for.
+    pub warnings: Vec<String>,
+}
+
+/// Returns the usage text for the binary invoked as `program`.
+pub fn usage(program: &str) -> String {
+    let mut usage = format!(
+        "Usage: {0} [options] <brainfuck_file>
+       {0} build [options] [--emit <kind>] [-o <path>] <brainfuck_file>
+
+Options:
+  --cell-width <bits>    Width of each memory cell: 8, 16, 32 or 64 (default: 8)
+  --eof <policy>         What `,` does at end of input: unchanged, zero, minus-one
+                         or error (default: unchanged)
+  --tape <layout>        Layout of the tape: fixed:<cells>, wrap:<cells>,
+                         growing[:<max cells>] or bidirectional[:<max cells>]
+                         (default: growing)
+  --fuel <operations>    Stop after executing this many operations
+  --timeout <seconds>    Stop after running for this many seconds
+  --output <mode>        When output is flushed: unbuffered, line, full or
+                         before-input (default: unbuffered)
+  --backend <backend>    How the program is executed: tree, bytecode, or jit
+                         with the `jit` feature (default: tree)
+  -O<level>              How much to optimize, from -O0 to -O3 (default: -O3)
+  --opt <passes>         Comma-separated passes to enable, or to disable when
+                         prefixed with `-`, like `--opt=-scan-loops`
+  --opt-report           Print what the optimizer did to stderr
+
+Build options:
+  --emit <kind>          What to produce: c, rust, llvm-ir, wat, wasm, asm,
+                         nasm or elf (default: from the extension of `-o`,
+                         else c)
+  -o <path>              Where to write the output (default: next to the
+                         program, with the usual extension)
+
+When building, the cell width, EOF policy and tape layout are compiled into
+the output. Assembly comes with a linker script for `ld`.",
+        program
+    );
+
+    usage.push_str("\n\nOptimization passes:");
+    for (level, rule) in Optimizer::default_rules() {
+        let description = format!("{} ({})", rule.description(), level);
+        usage.push_str(&format!("\n  {:<22} {}", rule.name(), description));
+    }
+
+    usage
+}
+
+/// Parses the arguments following the program name.
+///
+/// Options take their value either as `--option value` or `--option=value`.
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let (building, args) = match args.split_first() {
+        Some((command, rest)) if command == "build" => (true, rest),
+        _ => (false, args),
+    };
+
+    let mut path = None;
+    let mut emit = None;
+    let mut output = None;
+    let mut cell_width = CellWidth::default();
+    let mut eof_policy = EofPolicy::default();
+    let mut tape = TapeConfig::default();
+    let mut fuel = None;
+    let mut timeout = None;
+    let mut output_mode = OutputMode::default();
+    let mut backend = Backend::default();
+    let mut optimization_level = OptimizationLevel::default();
+    let mut passes = Vec::new();
+    let mut optimization_report = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-o" {
+            let value = args.next().ok_or("missing value for `-o`")?;
+            output = Some(value.clone());
+            continue;
+        }
+
+        if let Some(level) = arg.strip_prefix("-O") {
+            optimization_level = level.parse()?;
+            continue;
+        }
+
+        let Some(option) = arg.strip_prefix("--") else {
+            if path.replace(arg.clone()).is_some() {
+                return Err(format!("unexpected argument `{}`", arg));
+            }
+            continue;
+        };
+
+        let (name, inline_value) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (option, None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next().cloned())
+                .ok_or_else(|| format!("missing value for `--{}`", name))
+        };
+
+        match name {
+            "cell-width" => cell_width = value()?.parse()?,
+            "eof" => eof_policy = value()?.parse()?,
+            "tape" => tape = value()?.parse()?,
+            "output" => output_mode = value()?.parse()?,
+            "backend" => backend = value()?.parse()?,
+            "emit" => emit = Some(value()?.parse()?),
+            "opt" => {
+                for pass in value()?.split(',') {
+                    // Passes are applied once the level is known, wherever it was given
+                    match pass.strip_prefix('-') {
+                        Some(name) => passes.push((name.to_string(), false)),
+                        None => passes.push((pass.trim_start_matches('+').to_string(), true)),
+                    }
+                }
+            }
+            "opt-report" => match inline_value {
+                None => optimization_report = true,
+                Some(_) => return Err("`--opt-report` does not take a value".to_string()),
+            },
+            "fuel" => {
+                let value = value()?;
+                let parsed = value
+                    .parse()
+                    .map_err(|_| format!("invalid fuel `{}`", value))?;
+                fuel = Some(parsed);
+            }
+            "timeout" => {
+                let value = value()?;
+                let parsed = value
+                    .parse()
+                    .ok()
+                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                    .ok_or_else(|| format!("invalid timeout `{}`", value))?;
+                timeout = Some(parsed);
+            }
+            _ => return Err(format!("unknown option `--{}`", name)),
+        }
+    }
+
+    let command = if building {
+        // Without `--emit`, the kind of output follows the extension of `-o`
+        let inferred = output.as_deref().and_then(|output| {
+            let extension = Path::new(output).extension().unwrap_or_default();
+            Emit::from_extension(extension.to_str()?)
+        });
+        Command::Build {
+            emit: emit.or(inferred).unwrap_or_default(),
+            output,
+        }
+    } else if emit.is_some() || output.is_some() {
+        return Err("`--emit` and `-o` are only valid with `build`".to_string());
+    } else {
+        Command::Run
+    };
+
+    let mut warnings = Vec::new();
+    let mut optimizer = Optimizer::with_level(optimization_level);
+    for (name, enabled) in &passes {
+        if !optimizer.set_rule_enabled(name, *enabled)? {
+            warnings.push(format!(
+                "ignoring `--opt` for the `{}` pass, which needs the `optimizer` feature",
+                name
+            ));
+        }
+    }
+
+    Ok(Options {
+        command,
+        path: path.ok_or("missing Brainfuck file")?,
+        cell_width,
+        eof_policy,
+        tape,
+        fuel,
+        timeout,
+        output_mode,
+        backend,
+        optimizer,
+        optimization_report,
+        warnings,
+    })
+}
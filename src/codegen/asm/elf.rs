@@ -0,0 +1,97 @@
+//! A minimal ELF writer, producing static x86-64 Linux executables directly from the
+//! assembled code.
+
+use super::instruction::assemble;
+use super::lower::lower;
+use crate::codegen::CodegenOptions;
+use crate::parser::BfOp;
+
+/// The address the executable is loaded at.
+const LOAD_ADDRESS: u64 = 0x400000;
+const PAGE: u64 = 0x1000;
+const HEADER_SIZE: u64 = 64;
+const PROGRAM_HEADER_SIZE: u64 = 56;
+const PROGRAM_HEADERS: u64 = 3;
+/// The code follows the headers in the file, and in memory.
+const CODE_OFFSET: u64 = HEADER_SIZE + PROGRAM_HEADERS * PROGRAM_HEADER_SIZE;
+
+// Segment types and permissions
+const PT_LOAD: u32 = 1;
+const PT_GNU_STACK: u32 = 0x6474_E551;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+/// Compiles `program` to a static x86-64 Linux executable, without any toolchain.
+///
+/// The executable behaves like the assembly output of [`emit_gas`](super::emit_gas),
+/// linked with its linker script: the code and read-only data are loaded in one
+/// segment, and the zeroed buffers in a writable segment on the next page.
+pub fn emit_elf(program: &[BfOp], options: &CodegenOptions) -> Vec<u8> {
+    let width = options.cell_width.bits() as u8 / 8;
+    let code_address = LOAD_ADDRESS + CODE_OFFSET;
+    let machine = assemble(&lower(program, options), width, |size| {
+        (bss_address(size as u64) - code_address) as usize
+    });
+    let entry = code_address + machine.labels["_start"] as u64;
+    let bss = bss_address(machine.code.len() as u64);
+
+    let file_size = CODE_OFFSET + machine.code.len() as u64;
+    let mut out = Vec::with_capacity(file_size as usize);
+
+    // ELF header: 64-bit, little-endian, System V, executable, x86-64
+    out.extend(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+    out.extend(2u16.to_le_bytes());
+    out.extend(0x3Eu16.to_le_bytes());
+    out.extend(1u32.to_le_bytes());
+    out.extend(entry.to_le_bytes());
+    out.extend(HEADER_SIZE.to_le_bytes()); // Program headers
+    out.extend(0u64.to_le_bytes()); // No section headers
+    out.extend(0u32.to_le_bytes()); // Flags
+    out.extend((HEADER_SIZE as u16).to_le_bytes());
+    out.extend((PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    out.extend((PROGRAM_HEADERS as u16).to_le_bytes());
+    out.extend([0; 6]); // Section header size, count and name index
+
+    // The headers, code and read-only data
+    program_header(
+        &mut out,
+        PT_LOAD,
+        PF_R | PF_X,
+        LOAD_ADDRESS,
+        file_size,
+        file_size,
+    );
+    // The buffers, which take no space in the file
+    program_header(
+        &mut out,
+        PT_LOAD,
+        PF_R | PF_W,
+        bss,
+        0,
+        machine.bss_size as u64,
+    );
+    // A non-executable stack
+    program_header(&mut out, PT_GNU_STACK, PF_R | PF_W, 0, 0, 0);
+    debug_assert_eq!(out.len() as u64, CODE_OFFSET);
+
+    out.extend(machine.code);
+    out
+}
+
+/// The address of the buffers, on the page after the code of `code_size` bytes.
+fn bss_address(code_size: u64) -> u64 {
+    (LOAD_ADDRESS + CODE_OFFSET + code_size).div_ceil(PAGE) * PAGE
+}
+
+/// Appends a program header for a segment at the start of the file.
+fn program_header(out: &mut Vec<u8>, kind: u32, flags: u32, address: u64, size: u64, memory: u64) {
+    out.extend(kind.to_le_bytes());
+    out.extend(flags.to_le_bytes());
+    out.extend(0u64.to_le_bytes()); // Offset in the file
+    out.extend(address.to_le_bytes());
+    out.extend(address.to_le_bytes()); // Physical address
+    out.extend(size.to_le_bytes());
+    out.extend(memory.to_le_bytes());
+    out.extend(if kind == PT_LOAD { PAGE } else { 0 }.to_le_bytes());
+}
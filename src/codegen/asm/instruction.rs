@@ -0,0 +1,456 @@
+//! The lines of the assembly output, which render to GNU as or NASM syntax, or encode
+//! directly to machine code.
+
+use crate::x86::mul_cell_factor;
+use crate::x86::{Assembler, Condition, Label, Reg, CELLS, POINTER};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// The dialect of the assembly output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Syntax {
+    /// GNU as, in Intel syntax.
+    Gas,
+    Nasm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Section {
+    Text,
+    Rodata,
+    Bss,
+}
+
+/// A line of the assembly output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Line {
+    Section(Section),
+    Label(String),
+    Inst(Inst),
+    /// A NUL-terminated string, in the read-only data section.
+    String(String),
+    /// Zeroed bytes, in the bss section.
+    Reserve(usize),
+}
+
+/// An instruction. Registers are 64-bit unless stated otherwise, and the cell
+/// instructions address the cell `offset` cells away from the current one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Inst {
+    MovImm(Reg, i64),
+    Mov(Reg, Reg),
+    /// `mov dst, qword [base]`
+    Load(Reg, Reg),
+    /// `lea dst, [base + disp]`
+    Lea(Reg, Reg, i32),
+    /// `lea dst, [rip + symbol]`
+    LeaSymbol(Reg, String),
+    Add(Reg, Reg),
+    Sub(Reg, Reg),
+    Cmp(Reg, Reg),
+    Test(Reg, Reg),
+    Xor(Reg, Reg),
+    AddImm(Reg, i32),
+    SubImm(Reg, i32),
+    CmpImm(Reg, i32),
+    Shl(Reg, u8),
+    Shr(Reg, u8),
+    Neg(Reg),
+    Cqo,
+    Div(Reg),
+    Idiv(Reg),
+    Push(Reg),
+    Pop(Reg),
+    Call(String),
+    Jmp(String),
+    Jcc(Condition, String),
+    Ret,
+    Syscall,
+    Std,
+    Cld,
+    RepMovsb,
+    RepStosb,
+    /// `movzx dst32, byte [base]`
+    LoadByte(Reg, Reg),
+    /// `mov byte [base], src8`
+    StoreByte(Reg, Reg),
+    /// `mov byte [base], imm8`
+    StoreByteImm(Reg, u8),
+    AddCell {
+        offset: i32,
+        delta: i64,
+    },
+    SetCell {
+        offset: i32,
+        value: i64,
+    },
+    CmpCellZero {
+        offset: i32,
+    },
+    /// Stores the low bytes of a register into a cell.
+    StoreCell {
+        offset: i32,
+        src: Reg,
+    },
+    /// `movzx dst32, byte cell`
+    LoadCellByte {
+        dst: Reg,
+        offset: i32,
+    },
+    /// Loads the cell times `factor` into `rax`, clobbering `rcx`.
+    MulCell {
+        offset: i32,
+        factor: i64,
+    },
+    /// Adds the low bytes of a register to a cell.
+    AddCellReg {
+        offset: i32,
+        src: Reg,
+    },
+}
+
+impl From<Inst> for Line {
+    fn from(inst: Inst) -> Self {
+        Line::Inst(inst)
+    }
+}
+
+const NAMES: [[&str; 4]; 16] = [
+    ["al", "ax", "eax", "rax"],
+    ["cl", "cx", "ecx", "rcx"],
+    ["dl", "dx", "edx", "rdx"],
+    ["bl", "bx", "ebx", "rbx"],
+    ["spl", "sp", "esp", "rsp"],
+    ["bpl", "bp", "ebp", "rbp"],
+    ["sil", "si", "esi", "rsi"],
+    ["dil", "di", "edi", "rdi"],
+    ["r8b", "r8w", "r8d", "r8"],
+    ["r9b", "r9w", "r9d", "r9"],
+    ["r10b", "r10w", "r10d", "r10"],
+    ["r11b", "r11w", "r11d", "r11"],
+    ["r12b", "r12w", "r12d", "r12"],
+    ["r13b", "r13w", "r13d", "r13"],
+    ["r14b", "r14w", "r14d", "r14"],
+    ["r15b", "r15w", "r15d", "r15"],
+];
+
+/// The name of the low `bytes` bytes of `reg`.
+fn name(reg: Reg, bytes: u8) -> &'static str {
+    NAMES[reg as usize][bytes.trailing_zeros() as usize]
+}
+
+fn condition(condition: Condition) -> &'static str {
+    match condition {
+        Condition::AboveOrEqual => "jae",
+        Condition::Equal => "je",
+        Condition::NotEqual => "jne",
+        Condition::BelowOrEqual => "jbe",
+        Condition::Above => "ja",
+        Condition::Sign => "js",
+        Condition::NotSign => "jns",
+        Condition::LessOrEqual => "jle",
+    }
+}
+
+impl Syntax {
+    /// A memory operand of `bytes` bytes at `address`.
+    fn memory(self, bytes: u8, address: &str) -> String {
+        let size = match bytes {
+            1 => "byte",
+            2 => "word",
+            4 => "dword",
+            _ => "qword",
+        };
+        match self {
+            Syntax::Gas => format!("{} ptr [{}]", size, address),
+            Syntax::Nasm => format!("{} [{}]", size, address),
+        }
+    }
+
+    /// The address of a symbol, relative to the instruction pointer.
+    fn symbol(self, symbol: &str) -> String {
+        match self {
+            Syntax::Gas => format!("[rip + {}]", symbol),
+            Syntax::Nasm => format!("[rel {}]", symbol),
+        }
+    }
+
+    /// The comment marker.
+    pub(crate) fn comment(self) -> &'static str {
+        match self {
+            Syntax::Gas => "#",
+            Syntax::Nasm => ";",
+        }
+    }
+}
+
+/// The address of a cell, such as `r12 + r13*2 + 4`.
+fn cell(width: u8, offset: i32) -> String {
+    let base = format!("{} + {}*{}", name(CELLS, 8), name(POINTER, 8), width);
+    match offset as i64 * width as i64 {
+        0 => base,
+        disp if disp < 0 => format!("{} - {}", base, -disp),
+        disp => format!("{} + {}", base, disp),
+    }
+}
+
+impl Line {
+    /// Renders the line, for cells of `width` bytes.
+    pub(crate) fn render(&self, syntax: Syntax, width: u8) -> String {
+        match self {
+            Line::Section(section) => {
+                let name = match section {
+                    Section::Text => ".text",
+                    Section::Rodata => ".rodata",
+                    Section::Bss => ".bss",
+                };
+                match syntax {
+                    Syntax::Gas => format!("    .section {}", name),
+                    Syntax::Nasm => format!("    section {}", name),
+                }
+            }
+            Line::Label(label) => format!("{}:", label),
+            Line::Inst(inst) => format!("    {}", inst.render(syntax, width)),
+            Line::String(string) => match syntax {
+                Syntax::Gas => format!("    .asciz \"{}\"", string.replace('\n', "\\n")),
+                Syntax::Nasm => {
+                    let mut out = String::from("    db ");
+                    for (i, part) in string.split('\n').enumerate() {
+                        if i > 0 {
+                            out.push_str("10, ");
+                        }
+                        if !part.is_empty() {
+                            let _ = write!(out, "\"{}\", ", part);
+                        }
+                    }
+                    out.push('0');
+                    out
+                }
+            },
+            Line::Reserve(bytes) => match syntax {
+                Syntax::Gas => format!("    .skip {}", bytes),
+                Syntax::Nasm => format!("    resb {}", bytes),
+            },
+        }
+    }
+}
+
+impl Inst {
+    fn render(&self, syntax: Syntax, width: u8) -> String {
+        let q = |reg: Reg| name(reg, 8);
+        match self {
+            Inst::MovImm(dst, imm) => format!("mov {}, {}", q(*dst), imm),
+            Inst::Mov(dst, src) => format!("mov {}, {}", q(*dst), q(*src)),
+            Inst::Load(dst, base) => format!("mov {}, {}", q(*dst), syntax.memory(8, q(*base))),
+            Inst::Lea(dst, base, 0) => format!("lea {}, [{}]", q(*dst), q(*base)),
+            Inst::Lea(dst, base, disp) if *disp < 0 => {
+                format!("lea {}, [{} - {}]", q(*dst), q(*base), -(*disp as i64))
+            }
+            Inst::Lea(dst, base, disp) => format!("lea {}, [{} + {}]", q(*dst), q(*base), disp),
+            Inst::LeaSymbol(dst, symbol) => format!("lea {}, {}", q(*dst), syntax.symbol(symbol)),
+            Inst::Add(dst, src) => format!("add {}, {}", q(*dst), q(*src)),
+            Inst::Sub(dst, src) => format!("sub {}, {}", q(*dst), q(*src)),
+            Inst::Cmp(left, right) => format!("cmp {}, {}", q(*left), q(*right)),
+            Inst::Test(left, right) => format!("test {}, {}", q(*left), q(*right)),
+            Inst::Xor(dst, src) => format!("xor {}, {}", q(*dst), q(*src)),
+            Inst::AddImm(dst, imm) => format!("add {}, {}", q(*dst), imm),
+            Inst::SubImm(dst, imm) => format!("sub {}, {}", q(*dst), imm),
+            Inst::CmpImm(left, imm) => format!("cmp {}, {}", q(*left), imm),
+            Inst::Shl(reg, count) => format!("shl {}, {}", q(*reg), count),
+            Inst::Shr(reg, count) => format!("shr {}, {}", q(*reg), count),
+            Inst::Neg(reg) => format!("neg {}", q(*reg)),
+            Inst::Cqo => "cqo".to_string(),
+            Inst::Div(reg) => format!("div {}", q(*reg)),
+            Inst::Idiv(reg) => format!("idiv {}", q(*reg)),
+            Inst::Push(reg) => format!("push {}", q(*reg)),
+            Inst::Pop(reg) => format!("pop {}", q(*reg)),
+            Inst::Call(symbol) => format!("call {}", symbol),
+            Inst::Jmp(symbol) => format!("jmp {}", symbol),
+            Inst::Jcc(cond, symbol) => format!("{} {}", condition(*cond), symbol),
+            Inst::Ret => "ret".to_string(),
+            Inst::Syscall => "syscall".to_string(),
+            Inst::Std => "std".to_string(),
+            Inst::Cld => "cld".to_string(),
+            Inst::RepMovsb => "rep movsb".to_string(),
+            Inst::RepStosb => "rep stosb".to_string(),
+            Inst::LoadByte(dst, base) => {
+                format!("movzx {}, {}", name(*dst, 4), syntax.memory(1, q(*base)))
+            }
+            Inst::StoreByte(base, src) => {
+                format!("mov {}, {}", syntax.memory(1, q(*base)), name(*src, 1))
+            }
+            Inst::StoreByteImm(base, imm) => format!("mov {}, {}", syntax.memory(1, q(*base)), imm),
+            Inst::AddCell { offset, delta } => {
+                let operand = syntax.memory(width, &cell(width, *offset));
+                if width == 8 && i32::try_from(*delta).is_err() {
+                    format!("mov rax, {}\n    add {}, rax", delta, operand)
+                } else {
+                    format!("add {}, {}", operand, delta)
+                }
+            }
+            Inst::SetCell { offset, value } => {
+                let operand = syntax.memory(width, &cell(width, *offset));
+                if width == 8 && i32::try_from(*value).is_err() {
+                    format!("mov rax, {}\n    mov {}, rax", value, operand)
+                } else {
+                    format!("mov {}, {}", operand, value)
+                }
+            }
+            Inst::CmpCellZero { offset } => {
+                format!("cmp {}, 0", syntax.memory(width, &cell(width, *offset)))
+            }
+            Inst::StoreCell { offset, src } => format!(
+                "mov {}, {}",
+                syntax.memory(width, &cell(width, *offset)),
+                name(*src, width)
+            ),
+            Inst::LoadCellByte { dst, offset } => format!(
+                "movzx {}, {}",
+                name(*dst, 4),
+                syntax.memory(1, &cell(width, *offset))
+            ),
+            Inst::MulCell { offset, factor } => {
+                let operand = syntax.memory(width, &cell(width, *offset));
+                let load = match width {
+                    1 | 2 => format!("movzx eax, {}", operand),
+                    _ => format!("mov {}, {}", name(Reg::Rax, width), operand),
+                };
+                match mul_cell_factor(width, *factor) {
+                    1 => load,
+                    factor if i32::try_from(factor).is_ok() => {
+                        format!("{}\n    imul rax, rax, {}", load, factor)
+                    }
+                    factor => format!("{}\n    mov rcx, {}\n    imul rax, rcx", load, factor),
+                }
+            }
+            Inst::AddCellReg { offset, src } => format!(
+                "add {}, {}",
+                syntax.memory(width, &cell(width, *offset)),
+                name(*src, width)
+            ),
+        }
+    }
+
+    /// The symbol the instruction refers to, if any.
+    fn symbol(&self) -> Option<&str> {
+        match self {
+            Inst::LeaSymbol(_, symbol)
+            | Inst::Call(symbol)
+            | Inst::Jmp(symbol)
+            | Inst::Jcc(_, symbol) => Some(symbol),
+            _ => None,
+        }
+    }
+
+    /// Encodes the instruction into `asm`, for cells of `width` bytes, looking up
+    /// symbols with `label`.
+    fn encode(&self, asm: &mut Assembler, width: u8, label: &impl Fn(&str) -> Label) {
+        match self {
+            Inst::MovImm(dst, imm) => asm.mov_imm(*dst, *imm),
+            Inst::Mov(dst, src) => asm.mov(*dst, *src),
+            Inst::Load(dst, base) => asm.load(*dst, *base, 0),
+            Inst::Lea(dst, base, disp) => asm.lea(*dst, *base, *disp),
+            Inst::LeaSymbol(dst, symbol) => asm.lea_label(*dst, label(symbol)),
+            Inst::Add(dst, src) => asm.add(*dst, *src),
+            Inst::Sub(dst, src) => asm.sub(*dst, *src),
+            Inst::Cmp(left, right) => asm.cmp(*left, *right),
+            Inst::Test(left, right) => asm.test(*left, *right),
+            Inst::Xor(dst, src) => asm.xor(*dst, *src),
+            Inst::AddImm(dst, imm) => asm.add_imm(*dst, *imm),
+            Inst::SubImm(dst, imm) => asm.sub_imm(*dst, *imm),
+            Inst::CmpImm(left, imm) => asm.cmp_imm(*left, *imm),
+            Inst::Shl(reg, count) => asm.shl(*reg, *count),
+            Inst::Shr(reg, count) => asm.shr(*reg, *count),
+            Inst::Neg(reg) => asm.neg(*reg),
+            Inst::Cqo => asm.cqo(),
+            Inst::Div(reg) => asm.div(*reg),
+            Inst::Idiv(reg) => asm.idiv(*reg),
+            Inst::Push(reg) => asm.push(*reg),
+            Inst::Pop(reg) => asm.pop(*reg),
+            Inst::Call(symbol) => asm.call(label(symbol)),
+            Inst::Jmp(symbol) => asm.jmp(label(symbol)),
+            Inst::Jcc(cond, symbol) => asm.jcc(*cond, label(symbol)),
+            Inst::Ret => asm.ret(),
+            Inst::Syscall => asm.syscall(),
+            Inst::Std => asm.std(),
+            Inst::Cld => asm.cld(),
+            Inst::RepMovsb => asm.rep_movsb(),
+            Inst::RepStosb => asm.rep_stosb(),
+            Inst::LoadByte(dst, base) => asm.load_byte(*dst, *base),
+            Inst::StoreByte(base, src) => asm.store_byte(*base, *src),
+            Inst::StoreByteImm(base, imm) => asm.store_byte_imm(*base, *imm),
+            Inst::AddCell { offset, delta } => asm.add_cell(width, *offset, *delta),
+            Inst::SetCell { offset, value } => asm.set_cell(width, *offset, *value),
+            Inst::CmpCellZero { offset } => asm.cmp_cell_zero(width, *offset),
+            Inst::StoreCell { offset, src } => asm.store_cell(width, *offset, *src),
+            Inst::LoadCellByte { dst, offset } => asm.load_cell_byte(*dst, width, *offset),
+            Inst::MulCell { offset, factor } => asm.mul_cell(width, *offset, *factor),
+            Inst::AddCellReg { offset, src } => asm.add_cell_reg(width, *offset, *src),
+        }
+    }
+}
+
+/// Machine code assembled from lines, with the layout of its bss section.
+pub(crate) struct MachineCode {
+    /// The code, followed by the read-only data.
+    pub(crate) code: Vec<u8>,
+    /// The position of each label relative to the start of the code.
+    pub(crate) labels: HashMap<String, usize>,
+    pub(crate) bss_size: usize,
+}
+
+/// Assembles `lines` for cells of `width` bytes. `bss_start` maps the size of the code
+/// and data to the position of the bss section relative to the start of the code.
+pub(crate) fn assemble(
+    lines: &[Line],
+    width: u8,
+    bss_start: impl FnOnce(usize) -> usize,
+) -> MachineCode {
+    let mut asm = Assembler::new();
+    // Create every label up front, so instructions can refer to them while encoding
+    let mut labels: HashMap<&str, Label> = HashMap::new();
+    for line in lines {
+        let symbol = match line {
+            Line::Label(symbol) => symbol,
+            Line::Inst(inst) => match inst.symbol() {
+                Some(symbol) => symbol,
+                None => continue,
+            },
+            _ => continue,
+        };
+        labels.entry(symbol).or_insert_with(|| asm.new_label());
+    }
+
+    let mut positions = HashMap::new();
+    let mut bss = Vec::new();
+    let mut bss_size = 0;
+    let mut section = Section::Text;
+    for line in lines {
+        match line {
+            Line::Section(new) => section = *new,
+            Line::Label(symbol) if section == Section::Bss => bss.push((symbol, bss_size)),
+            Line::Label(symbol) => {
+                asm.bind(labels[symbol.as_str()]);
+                positions.insert(symbol.clone(), asm.position());
+            }
+            Line::Inst(inst) => inst.encode(&mut asm, width, &|symbol| labels[symbol]),
+            Line::String(string) => {
+                asm.data(string.as_bytes());
+                asm.data(&[0]);
+            }
+            Line::Reserve(bytes) => bss_size += bytes,
+        }
+    }
+
+    let start = bss_start(asm.position());
+    for (symbol, offset) in bss {
+        asm.bind_to(labels[symbol.as_str()], start + offset);
+        positions.insert(symbol.clone(), start + offset);
+    }
+
+    MachineCode {
+        code: asm.finish(),
+        labels: positions,
+        bss_size,
+    }
+}
@@ -0,0 +1,642 @@
+//! Lowering of programs to x86-64 assembly for Linux, with a runtime using raw system
+//! calls instead of a C library.
+
+use super::instruction::{Inst, Line, Section};
+use crate::bytecode::{Bytecode, Instruction};
+use crate::codegen::CodegenOptions;
+use crate::interpreter::{EofPolicy, TapeConfig};
+use crate::parser::BfOp;
+use crate::x86::{Condition, Reg, CELLS, POINTER};
+
+use Condition::*;
+use Inst::*;
+use Reg::*;
+
+/// The register holding the number of cells.
+const LEN: Reg = R14;
+/// The register holding the index of cell 0, which moves as the tape grows to the left.
+const ORIGIN: Reg = R15;
+/// The register holding the number of bytes waiting in the output buffer.
+const PENDING: Reg = Rbx;
+
+const OUTPUT_BUFFER: usize = 4096;
+const ERROR_BUFFER: usize = 256;
+
+// System call numbers
+const READ: i64 = 0;
+const WRITE: i64 = 1;
+const MMAP: i64 = 9;
+const MREMAP: i64 = 25;
+const EXIT: i64 = 60;
+const EINTR: i32 = 4;
+
+/// Accumulates the lines of the output.
+#[derive(Default)]
+struct Code(Vec<Line>);
+
+impl Code {
+    fn label(&mut self, label: &str) {
+        self.0.push(Line::Label(label.to_string()));
+    }
+
+    fn emit(&mut self, insts: impl IntoIterator<Item = Inst>) {
+        self.0.extend(insts.into_iter().map(Line::Inst));
+    }
+
+    /// Reports an error from the message at `message`, with the values pushed so far.
+    fn fail(&mut self, message: &str) {
+        self.emit([
+            LeaSymbol(Rdi, message.to_string()),
+            Mov(Rsi, Rsp),
+            Jmp("bf_fail".to_string()),
+        ]);
+    }
+}
+
+fn symbol(name: &str) -> String {
+    name.to_string()
+}
+
+/// Lowers `program` to the lines of a freestanding Linux executable.
+///
+/// # Details
+/// The current cell is `[r12 + r13 * width]`, like in the JIT, with the number of cells
+/// in `r14`. Moves that stay within the tape are inline, and the others call `bf_move`,
+/// which follows the tape layout. The tape is mapped with `mmap` and grown with `mremap`.
+/// Output is buffered, and flushed before reading, on exit and on errors, which are
+/// reported on the standard error with the same messages as the interpreter.
+pub(crate) fn lower(program: &[BfOp], options: &CodegenOptions) -> Vec<Line> {
+    let bytecode = Bytecode::compile(program);
+    let width = options.cell_width.bits() as u8 / 8;
+    let shift = width.trailing_zeros() as u8;
+    let initial = options.initial_cells();
+    let uses = |predicate: fn(&Instruction) -> bool| bytecode.instructions.iter().any(predicate);
+
+    let mut code = Code::default();
+    code.0.push(Line::Section(Section::Text));
+    code.label("_start");
+    // Map the tape, zeroed
+    code.emit([
+        Xor(Rdi, Rdi),
+        MovImm(Rsi, (initial << shift) as i64),
+        MovImm(Rdx, 3),    // PROT_READ | PROT_WRITE
+        MovImm(R10, 0x22), // MAP_PRIVATE | MAP_ANONYMOUS
+        MovImm(R8, -1),
+        Xor(R9, R9),
+        MovImm(Rax, MMAP),
+        Syscall,
+        CmpImm(Rax, -4096),
+        Jcc(Above, symbol("bf_out_of_memory")),
+        Mov(CELLS, Rax),
+        Xor(POINTER, POINTER),
+        MovImm(LEN, initial as i64),
+        Xor(ORIGIN, ORIGIN),
+        Xor(PENDING, PENDING),
+    ]);
+
+    let mut slow_moves = Code::default();
+    for (pc, (instruction, span)) in bytecode
+        .instructions
+        .iter()
+        .zip(&bytecode.spans)
+        .enumerate()
+    {
+        let (line, column) = (span.line as i64, span.column as i64);
+        match *instruction {
+            Instruction::Move(offset) => move_by(
+                &mut code,
+                &mut slow_moves,
+                offset,
+                (line, column),
+                &format!("move_{}", pc),
+            ),
+            Instruction::Add(delta) => code.emit([AddCell {
+                offset: 0,
+                delta: options.wrap_delta(delta) as i64,
+            }]),
+            Instruction::Output => code.emit([
+                LoadCellByte {
+                    dst: Rdi,
+                    offset: 0,
+                },
+                Call(symbol("bf_output")),
+            ]),
+            Instruction::Input => code.emit([
+                MovImm(Rdx, line),
+                MovImm(Rcx, column),
+                Call(symbol("bf_input")),
+            ]),
+            Instruction::JumpIfZero(_) => {
+                code.emit([
+                    CmpCellZero { offset: 0 },
+                    Jcc(Equal, format!("loop_{}_end", pc)),
+                ]);
+                code.label(&format!("loop_{}", pc));
+            }
+            Instruction::JumpIfNonZero(target) => {
+                code.emit([
+                    CmpCellZero { offset: 0 },
+                    Jcc(NotEqual, format!("loop_{}", target - 1)),
+                ]);
+                code.label(&format!("loop_{}_end", target - 1));
+            }
+            Instruction::Clear => code.emit([SetCell {
+                offset: 0,
+                value: 0,
+            }]),
+            Instruction::Set(value) => code.emit([SetCell {
+                offset: 0,
+                value: options.wrap_delta(value) as i64,
+            }]),
+            Instruction::MulAdd { offset, factor } => {
+                // Keep the product on the stack while moving, as `bf_move` may call out
+                let done = format!("mul_add_{}_done", pc);
+                code.emit([
+                    CmpCellZero { offset: 0 },
+                    Jcc(Equal, done.clone()),
+                    MulCell { offset: 0, factor },
+                    Push(Rax),
+                ]);
+                let position = (line, column);
+                move_by(
+                    &mut code,
+                    &mut slow_moves,
+                    offset,
+                    position,
+                    &format!("move_{}", pc),
+                );
+                code.emit([
+                    Pop(Rcx),
+                    AddCellReg {
+                        offset: 0,
+                        src: Rcx,
+                    },
+                ]);
+                let back = format!("move_{}_back", pc);
+                move_by(&mut code, &mut slow_moves, -offset, position, &back);
+                code.label(&done);
+            }
+            Instruction::Scan(stride) => {
+                let scan = format!("scan_{}", pc);
+                let done = format!("scan_{}_done", pc);
+                code.emit([CmpCellZero { offset: 0 }, Jcc(Equal, done.clone())]);
+                code.label(&scan);
+                let position = (line, column);
+                move_by(
+                    &mut code,
+                    &mut slow_moves,
+                    stride,
+                    position,
+                    &format!("move_{}", pc),
+                );
+                code.emit([CmpCellZero { offset: 0 }, Jcc(NotEqual, scan)]);
+                code.label(&done);
+            }
+            Instruction::AddAt { offset, delta } => {
+                let position = (line, column);
+                let there = format!("move_{}", pc);
+                move_by(&mut code, &mut slow_moves, offset, position, &there);
+                code.emit([AddCell {
+                    offset: 0,
+                    delta: options.wrap_delta(delta) as i64,
+                }]);
+                let back = format!("move_{}_back", pc);
+                move_by(&mut code, &mut slow_moves, -offset, position, &back);
+            }
+            Instruction::OutputAt(offset) => {
+                let position = (line, column);
+                let there = format!("move_{}", pc);
+                move_by(&mut code, &mut slow_moves, offset, position, &there);
+                code.emit([
+                    LoadCellByte {
+                        dst: Rdi,
+                        offset: 0,
+                    },
+                    Call(symbol("bf_output")),
+                ]);
+                let back = format!("move_{}_back", pc);
+                move_by(&mut code, &mut slow_moves, -offset, position, &back);
+            }
+        }
+    }
+
+    code.emit([
+        Call(symbol("bf_flush")),
+        Xor(Rdi, Rdi),
+        MovImm(Rax, EXIT),
+        Syscall,
+    ]);
+    code.0.append(&mut slow_moves.0);
+
+    if uses(Instruction::moves_pointer) {
+        move_function(&mut code, options.tape, shift);
+    }
+    output_functions(&mut code);
+    if uses(|instruction| matches!(instruction, Instruction::Input)) {
+        input_function(&mut code, options.eof_policy);
+    }
+    fail_function(&mut code);
+
+    code.0.push(Line::Section(Section::Rodata));
+    for (label, message) in MESSAGES {
+        code.label(label);
+        code.0
+            .push(Line::String(format!("Runtime error: {}\n", message)));
+    }
+
+    code.0.push(Line::Section(Section::Bss));
+    code.label("output_buffer");
+    code.0.push(Line::Reserve(OUTPUT_BUFFER));
+    code.label("error_buffer");
+    code.0.push(Line::Reserve(ERROR_BUFFER));
+    code.label("input_byte");
+    code.0.push(Line::Reserve(1));
+
+    code.0
+}
+
+/// Moves the pointer by `offset` for the command at `position`. Moves that stay within
+/// the tape are inline, after the label `done`, and the others go through `bf_move` in
+/// `slow_moves`.
+fn move_by(
+    code: &mut Code,
+    slow_moves: &mut Code,
+    offset: isize,
+    (line, column): (i64, i64),
+    done: &str,
+) {
+    let call = [
+        MovImm(Rsi, offset as i64),
+        MovImm(Rdx, line),
+        MovImm(Rcx, column),
+        Call(symbol("bf_move")),
+    ];
+    match i32::try_from(offset) {
+        Ok(offset) => {
+            let slow = format!("{}_slow", done);
+            code.emit([
+                Lea(Rax, POINTER, offset),
+                Cmp(Rax, LEN),
+                Jcc(AboveOrEqual, slow.clone()),
+            ]);
+            code.label(done);
+            slow_moves.label(&slow);
+            slow_moves.emit(call);
+            slow_moves.emit([Jmp(done.to_string())]);
+        }
+        Err(_) => code.emit(call),
+    }
+    code.emit([Mov(POINTER, Rax)]);
+}
+
+/// The error messages, where `%u` and `%d` stand for unsigned and signed numbers.
+const MESSAGES: [(&str, &str); 6] = [
+    (
+        "underflow_message",
+        "Pointer underflow at line %u, column %u: attempted to move left %u steps when pointer was at position %u",
+    ),
+    (
+        "overflow_message",
+        "Pointer overflow at line %u, column %u: attempted to move right %u steps when pointer was at position %u",
+    ),
+    (
+        "limit_message",
+        "Tape limit exceeded at line %u, column %u: the tape cannot grow beyond %u cells, pointer was at position %d",
+    ),
+    (
+        "eof_message",
+        "Unexpected end of input at line %u, column %u",
+    ),
+    ("input_error_message", "Input error: os error %u"),
+    ("out_of_memory_message", "Out of memory"),
+];
+
+/// Appends `bf_move`, which takes the offset in `rsi` and the position of the command
+/// in `rdx` and `rcx`, and returns the index of the new cell in `rax`.
+fn move_function(code: &mut Code, tape: TapeConfig, shift: u8) {
+    // Keep the size of the tape in bytes within 64 bits
+    let limit = |max: usize| max.min(isize::MAX as usize >> shift) as i64;
+
+    code.label("bf_move");
+    match tape {
+        TapeConfig::Fixed { .. } => {
+            code.emit([Test(Rsi, Rsi), Jcc(Sign, symbol("bf_move_underflow"))]);
+            code.emit([Push(POINTER), Push(Rsi), Push(Rcx), Push(Rdx)]);
+            code.fail("overflow_message");
+            underflow(code);
+        }
+        TapeConfig::Wrapping { .. } => {
+            code.emit([
+                Mov(Rax, POINTER),
+                Add(Rax, Rsi),
+                Cqo,
+                Idiv(LEN),
+                Test(Rdx, Rdx),
+                Jcc(NotSign, symbol("bf_move_wrapped")),
+                Add(Rdx, LEN),
+            ]);
+            code.label("bf_move_wrapped");
+            code.emit([Mov(Rax, Rdx), Ret]);
+        }
+        TapeConfig::Growing { max, .. } => {
+            code.emit([Test(Rsi, Rsi), Jcc(Sign, symbol("bf_move_underflow"))]);
+            grow_right(code, limit(max), false);
+            underflow(code);
+            resize_function(code, shift);
+        }
+        TapeConfig::Bidirectional { max, .. } => {
+            let max = limit(max);
+            code.emit([Test(Rsi, Rsi), Jcc(NotSign, symbol("bf_move_right"))]);
+            // Grow to the left, by at least the current length
+            code.emit([
+                Mov(Rax, Rsi),
+                Neg(Rax),
+                Sub(Rax, POINTER),
+                MovImm(R8, max),
+                Sub(R8, LEN),
+                Cmp(Rax, R8),
+                Jcc(Above, symbol("bf_move_limit")),
+                Cmp(Rax, LEN),
+                Jcc(AboveOrEqual, symbol("bf_move_at_least_len")),
+                Mov(Rax, LEN),
+            ]);
+            code.label("bf_move_at_least_len");
+            code.emit([
+                Cmp(Rax, R8),
+                Jcc(BelowOrEqual, symbol("bf_move_left")),
+                Mov(Rax, R8),
+            ]);
+            code.label("bf_move_left");
+            code.emit([
+                Push(Rsi),
+                Push(Rax),
+                Mov(Rdi, LEN),
+                Add(Rdi, Rax),
+                Call(symbol("bf_resize")),
+                Pop(Rax),
+                // Shift the cells up, starting from the last byte
+                Mov(Rcx, LEN),
+                Sub(Rcx, Rax),
+                Shl(Rcx, shift),
+                Mov(R8, Rax),
+                Shl(Rax, shift),
+                Mov(Rsi, CELLS),
+                Add(Rsi, Rcx),
+                SubImm(Rsi, 1),
+                Mov(Rdi, Rsi),
+                Add(Rdi, Rax),
+                Std,
+                RepMovsb,
+                Cld,
+                // Clear the cells in front
+                Mov(Rdi, CELLS),
+                Mov(Rcx, Rax),
+                Xor(Rax, Rax),
+                RepStosb,
+                Add(ORIGIN, R8),
+                Add(POINTER, R8),
+                Pop(Rsi),
+                Mov(Rax, POINTER),
+                Add(Rax, Rsi),
+                Ret,
+            ]);
+            code.label("bf_move_right");
+            grow_right(code, max, true);
+            resize_function(code, shift);
+        }
+    }
+}
+
+/// Appends the code growing the tape to the right, geometrically and up to `max` cells,
+/// followed by `bf_move_limit`, which reports that the tape cannot grow any further.
+fn grow_right(code: &mut Code, max: i64, bidirectional: bool) {
+    code.emit([
+        Mov(Rax, POINTER),
+        Add(Rax, Rsi),
+        MovImm(R8, max),
+        Cmp(Rax, R8),
+        Jcc(AboveOrEqual, symbol("bf_move_limit")),
+        Lea(R9, Rax, 1),
+        Mov(R10, LEN),
+        Add(R10, R10),
+        Cmp(R10, R9),
+        Jcc(BelowOrEqual, symbol("bf_move_at_least_target")),
+        Mov(R9, R10),
+    ]);
+    code.label("bf_move_at_least_target");
+    code.emit([
+        Cmp(R9, R8),
+        Jcc(BelowOrEqual, symbol("bf_move_grow")),
+        Mov(R9, R8),
+    ]);
+    code.label("bf_move_grow");
+    code.emit([
+        Push(Rax),
+        Mov(Rdi, R9),
+        Call(symbol("bf_resize")),
+        Pop(Rax),
+        Ret,
+    ]);
+
+    code.label("bf_move_limit");
+    code.emit([Mov(Rax, POINTER)]);
+    if bidirectional {
+        code.emit([Sub(Rax, ORIGIN)]);
+    }
+    code.emit([Push(Rax), MovImm(R8, max), Push(R8), Push(Rcx), Push(Rdx)]);
+    code.fail("limit_message");
+}
+
+fn underflow(code: &mut Code) {
+    code.label("bf_move_underflow");
+    code.emit([Neg(Rsi), Push(POINTER), Push(Rsi), Push(Rcx), Push(Rdx)]);
+    code.fail("underflow_message");
+}
+
+/// Appends `bf_resize`, which resizes the tape to the number of cells in `rdi`.
+/// New cells are zero, as the tape is an anonymous mapping.
+fn resize_function(code: &mut Code, shift: u8) {
+    code.label("bf_resize");
+    code.emit([
+        Mov(Rdx, Rdi),
+        Shl(Rdx, shift),
+        Mov(Rsi, LEN),
+        Shl(Rsi, shift),
+        Mov(Rdi, CELLS),
+        MovImm(R10, 1), // MREMAP_MAYMOVE
+        MovImm(Rax, MREMAP),
+        Syscall,
+        CmpImm(Rax, -4096),
+        Jcc(Above, symbol("bf_out_of_memory")),
+        Mov(CELLS, Rax),
+        Mov(LEN, Rdx),
+        Shr(LEN, shift),
+        Ret,
+    ]);
+}
+
+/// Appends `bf_output`, which buffers the byte in `rdi`, and `bf_flush`, which writes
+/// the buffer out. Write errors are ignored.
+fn output_functions(code: &mut Code) {
+    code.label("bf_output");
+    code.emit([
+        LeaSymbol(Rax, symbol("output_buffer")),
+        Add(Rax, PENDING),
+        StoreByte(Rax, Rdi),
+        AddImm(PENDING, 1),
+        CmpImm(PENDING, OUTPUT_BUFFER as i32),
+        Jcc(NotEqual, symbol("bf_output_done")),
+        Call(symbol("bf_flush")),
+    ]);
+    code.label("bf_output_done");
+    code.emit([Ret]);
+
+    code.label("bf_flush");
+    code.emit([LeaSymbol(Rsi, symbol("output_buffer")), Mov(Rdx, PENDING)]);
+    code.label("bf_flush_loop");
+    code.emit([
+        Test(Rdx, Rdx),
+        Jcc(Equal, symbol("bf_flush_done")),
+        MovImm(Rdi, 1),
+        MovImm(Rax, WRITE),
+        Syscall,
+        Test(Rax, Rax),
+        Jcc(LessOrEqual, symbol("bf_flush_done")),
+        Add(Rsi, Rax),
+        Sub(Rdx, Rax),
+        Jmp(symbol("bf_flush_loop")),
+    ]);
+    code.label("bf_flush_done");
+    code.emit([Xor(PENDING, PENDING), Ret]);
+}
+
+/// Appends `bf_input`, which reads a byte into the current cell for the command at the
+/// line in `rdx` and the column in `rcx`, following the EOF policy.
+fn input_function(code: &mut Code, eof_policy: EofPolicy) {
+    code.label("bf_input");
+    // Keep the position on the stack, as the values of an error message
+    code.emit([Push(Rcx), Push(Rdx), Call(symbol("bf_flush"))]);
+    code.label("bf_input_retry");
+    code.emit([
+        Xor(Rdi, Rdi),
+        LeaSymbol(Rsi, symbol("input_byte")),
+        MovImm(Rdx, 1),
+        MovImm(Rax, READ),
+        Syscall,
+        CmpImm(Rax, 1),
+        Jcc(Equal, symbol("bf_input_read")),
+        CmpImm(Rax, -EINTR),
+        Jcc(Equal, symbol("bf_input_retry")),
+        Test(Rax, Rax),
+        Jcc(Equal, symbol("bf_input_eof")),
+        Neg(Rax),
+        Push(Rax),
+    ]);
+    code.fail("input_error_message");
+
+    code.label("bf_input_read");
+    code.emit([
+        LoadByte(Rax, Rsi),
+        StoreCell {
+            offset: 0,
+            src: Rax,
+        },
+        AddImm(Rsp, 16),
+        Ret,
+    ]);
+
+    code.label("bf_input_eof");
+    match eof_policy {
+        EofPolicy::Unchanged => {}
+        EofPolicy::Zero => code.emit([SetCell {
+            offset: 0,
+            value: 0,
+        }]),
+        EofPolicy::MinusOne => code.emit([SetCell {
+            offset: 0,
+            value: -1,
+        }]),
+        EofPolicy::Error => code.fail("eof_message"),
+    }
+    if eof_policy != EofPolicy::Error {
+        code.emit([AddImm(Rsp, 16), Ret]);
+    }
+}
+
+/// Appends `bf_fail`, which flushes the output, prints the message at `rdi` with the
+/// values at `rsi` on the standard error, and exits with status 1, and
+/// `bf_out_of_memory`.
+fn fail_function(code: &mut Code) {
+    code.label("bf_out_of_memory");
+    code.fail("out_of_memory_message");
+
+    code.label("bf_fail");
+    code.emit([
+        Mov(R8, Rdi),
+        Mov(R9, Rsi),
+        Call(symbol("bf_flush")),
+        LeaSymbol(Rdi, symbol("error_buffer")),
+    ]);
+    code.label("bf_fail_next");
+    code.emit([
+        LoadByte(Rax, R8),
+        AddImm(R8, 1),
+        Test(Rax, Rax),
+        Jcc(Equal, symbol("bf_fail_write")),
+        CmpImm(Rax, b'%' as i32),
+        Jcc(Equal, symbol("bf_fail_number")),
+        StoreByte(Rdi, Rax),
+        AddImm(Rdi, 1),
+        Jmp(symbol("bf_fail_next")),
+    ]);
+
+    code.label("bf_fail_number");
+    code.emit([
+        LoadByte(Rcx, R8),
+        AddImm(R8, 1),
+        Load(Rax, R9),
+        AddImm(R9, 8),
+        CmpImm(Rcx, b'd' as i32),
+        Jcc(NotEqual, symbol("bf_fail_unsigned")),
+        Test(Rax, Rax),
+        Jcc(NotSign, symbol("bf_fail_unsigned")),
+        StoreByteImm(Rdi, b'-'),
+        AddImm(Rdi, 1),
+        Neg(Rax),
+    ]);
+    // Push the digits, least significant first, then pop them into the buffer
+    code.label("bf_fail_unsigned");
+    code.emit([Xor(Rcx, Rcx), MovImm(R10, 10)]);
+    code.label("bf_fail_divide");
+    code.emit([
+        Xor(Rdx, Rdx),
+        Div(R10),
+        AddImm(Rdx, b'0' as i32),
+        Push(Rdx),
+        AddImm(Rcx, 1),
+        Test(Rax, Rax),
+        Jcc(NotEqual, symbol("bf_fail_divide")),
+    ]);
+    code.label("bf_fail_digit");
+    code.emit([
+        Pop(Rax),
+        StoreByte(Rdi, Rax),
+        AddImm(Rdi, 1),
+        SubImm(Rcx, 1),
+        Jcc(NotEqual, symbol("bf_fail_digit")),
+        Jmp(symbol("bf_fail_next")),
+    ]);
+
+    code.label("bf_fail_write");
+    code.emit([
+        LeaSymbol(Rsi, symbol("error_buffer")),
+        Mov(Rdx, Rdi),
+        Sub(Rdx, Rsi),
+        MovImm(Rdi, 2),
+        MovImm(Rax, WRITE),
+        Syscall,
+        MovImm(Rdi, 1),
+        MovImm(Rax, EXIT),
+        Syscall,
+    ]);
+}
@@ -0,0 +1,10 @@
+//! The x86-64 assembly backend, for Linux, which emits GNU as or NASM source, or a
+//! static executable through a minimal built-in ELF writer.
+
+mod elf;
+mod instruction;
+mod lower;
+mod text;
+
+pub use elf::emit_elf;
+pub use text::{emit_gas, emit_nasm, LINKER_SCRIPT};
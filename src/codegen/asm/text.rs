@@ -0,0 +1,83 @@
+//! The assembly output, in GNU as or NASM syntax.
+
+use super::instruction::Syntax;
+use super::lower::lower;
+use crate::codegen::CodegenOptions;
+use crate::parser::BfOp;
+use std::fmt::Write;
+
+/// A linker script for the assembly output, producing a static executable with the code
+/// and read-only data in one segment, and the buffers in another.
+pub const LINKER_SCRIPT: &str = "/* Generated by bf-rs. */
+ENTRY(_start)
+
+SECTIONS
+{
+    . = 0x400000 + SIZEOF_HEADERS;
+    .text : { *(.text) *(.rodata) }
+    . = ALIGN(0x1000);
+    .bss : { *(.bss) }
+    /DISCARD/ : { *(.comment) *(.note.gnu.property) }
+}
+";
+
+/// Compiles `program` to x86-64 assembly for GNU as, in Intel syntax.
+///
+/// # Details
+/// The output is a freestanding Linux program using raw `read` and `write` system
+/// calls, which reports errors with the same messages as the interpreter. Link it
+/// with [`LINKER_SCRIPT`]:
+///
+/// ```text
+/// as -o program.o program.s && ld -T program.ld -o program program.o
+/// ```
+pub fn emit_gas(program: &[BfOp], options: &CodegenOptions) -> String {
+    render(
+        program,
+        options,
+        Syntax::Gas,
+        "as -o program.o program.s && ld -T program.ld -o program program.o",
+        "    .intel_syntax noprefix\n    .globl _start\n",
+        "    .section .note.GNU-stack,\"\",@progbits\n",
+    )
+}
+
+/// Compiles `program` to x86-64 assembly for NASM.
+///
+/// See [`emit_gas`] for the details, and link it the same way:
+///
+/// ```text
+/// nasm -f elf64 -o program.o program.asm && ld -T program.ld -o program program.o
+/// ```
+pub fn emit_nasm(program: &[BfOp], options: &CodegenOptions) -> String {
+    render(
+        program,
+        options,
+        Syntax::Nasm,
+        "nasm -f elf64 -o program.o program.asm && ld -T program.ld -o program program.o",
+        "    bits 64\n    default rel\n    global _start\n",
+        "    section .note.GNU-stack noalloc noexec nowrite progbits\n",
+    )
+}
+
+fn render(
+    program: &[BfOp],
+    options: &CodegenOptions,
+    syntax: Syntax,
+    build: &str,
+    prologue: &str,
+    epilogue: &str,
+) -> String {
+    let width = options.cell_width.bits() as u8 / 8;
+    let comment = syntax.comment();
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{} Generated by bf-rs.", comment);
+    let _ = writeln!(out, "{} Build with: {}\n", comment, build);
+    out.push_str(prologue);
+    for line in lower(program, options) {
+        let _ = writeln!(out, "{}", line.render(syntax, width));
+    }
+    out.push_str(epilogue);
+    out
+}
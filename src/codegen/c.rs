@@ -0,0 +1,333 @@
+use crate::bytecode::{Bytecode, Instruction};
+use crate::codegen::CodegenOptions;
+use crate::interpreter::{EofPolicy, TapeConfig};
+use crate::parser::BfOp;
+use std::fmt::Write;
+
+/// Compiles `program` to a standalone C99 program using `getchar` and `putchar`.
+///
+/// # Details
+/// The current cell is always `p[0]`, and loops become `while (p[0])` blocks.
+/// Moves and reads go through small helper functions that implement the tape layout
+/// and the EOF policy, and report errors with the same messages as the interpreter.
+/// Cells at an offset from the current one are reached through `at`, which only takes
+/// the slow path of moving there and back when they are outside the tape.
+pub fn emit_c(program: &[BfOp], options: &CodegenOptions) -> String {
+    let bytecode = Bytecode::compile(program);
+    let mut out = String::new();
+
+    let _ = writeln!(out, "/* Generated by bf-rs. */");
+    out.push_str(INCLUDES);
+    let _ = writeln!(out, "\ntypedef uint{}_t cell;\n", options.cell_width.bits());
+    out.push_str(STATE);
+    if let TapeConfig::Bidirectional { .. } = options.tape {
+        out.push_str("/* The index of cell 0, which moves as the tape grows to the left. */\n");
+        out.push_str("static size_t origin;\n");
+    }
+    out.push_str(FAIL);
+    // Only define the helpers that are used, so the output compiles without warnings
+    let uses = |predicate: fn(&Instruction) -> bool| bytecode.instructions.iter().any(predicate);
+    if uses(Instruction::moves_pointer) {
+        out.push_str(&move_function(options.tape));
+    }
+    if uses(Instruction::uses_offset) {
+        out.push_str(AT);
+    }
+    if uses(|instruction| matches!(instruction, Instruction::Input)) {
+        out.push_str(&input_function(options.eof_policy));
+    }
+
+    let _ = writeln!(out, "\nint main(void) {{");
+    let _ = writeln!(out, "    len = {};", options.initial_cells());
+    let _ = writeln!(out, "    tape = calloc(len, sizeof(cell));");
+    let _ = writeln!(out, "    if (!tape) {{");
+    let _ = writeln!(out, "        fail(\"Out of memory\");");
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out, "    p = tape;");
+
+    let mut depth = 1;
+    for (instruction, span) in bytecode.instructions.iter().zip(&bytecode.spans) {
+        if let Instruction::JumpIfNonZero(_) = instruction {
+            depth -= 1;
+        }
+        let indent = "    ".repeat(depth);
+
+        let _ = match *instruction {
+            Instruction::Move(offset) => writeln!(
+                out,
+                "{}move({}, {}, {});",
+                indent, offset, span.line, span.column
+            ),
+            Instruction::Add(delta) => {
+                writeln!(out, "{}p[0] {};", indent, compound_add(delta, options))
+            }
+            Instruction::Output => writeln!(out, "{}putchar((unsigned char)p[0]);", indent),
+            Instruction::Input => {
+                writeln!(out, "{}input({}, {});", indent, span.line, span.column)
+            }
+            Instruction::JumpIfZero(_) => writeln!(out, "{}while (p[0]) {{", indent),
+            Instruction::JumpIfNonZero(_) => writeln!(out, "{}}}", indent),
+            Instruction::Clear => writeln!(out, "{}p[0] = 0;", indent),
+            Instruction::Set(value) => {
+                // Negating an unsigned literal wraps it to the cell width
+                let value = options.wrap_delta(value);
+                let suffix = if value.unsigned_abs() > i32::MAX as u128 {
+                    "u"
+                } else {
+                    ""
+                };
+                writeln!(out, "{}p[0] = (cell){}{};", indent, value, suffix)
+            }
+            Instruction::MulAdd { offset, factor } => writeln!(
+                out,
+                "{0}if (p[0]) {{\n\
+                 {0}    cell value = p[0];\n\
+                 {0}    at({1}, {3}, {4})[0] {2};\n\
+                 {0}}}",
+                indent,
+                offset,
+                compound_mul_add(factor, options),
+                span.line,
+                span.column
+            ),
+            Instruction::Scan(stride) => writeln!(
+                out,
+                "{0}while (p[0]) {{\n{0}    move({1}, {2}, {3});\n{0}}}",
+                indent, stride, span.line, span.column
+            ),
+            Instruction::AddAt { offset, delta } => writeln!(
+                out,
+                "{}at({}, {}, {})[0] {};",
+                indent,
+                offset,
+                span.line,
+                span.column,
+                compound_add(delta, options)
+            ),
+            Instruction::OutputAt(offset) => writeln!(
+                out,
+                "{}putchar((unsigned char)at({}, {}, {})[0]);",
+                indent, offset, span.line, span.column
+            ),
+        };
+
+        if let Instruction::JumpIfZero(_) = instruction {
+            depth += 1;
+        }
+    }
+
+    let _ = writeln!(out, "    return 0;");
+    let _ = writeln!(out, "}}");
+    out
+}
+
+const INCLUDES: &str = "#include <stdarg.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+";
+
+const STATE: &str = "/* The tape, the current cell, and the number of cells. */
+static cell *tape;
+static cell *p;
+static size_t len;
+";
+
+const FAIL: &str = "
+/* Reports a runtime error like the interpreter does, then exits. */
+static void fail(const char *format, ...) {
+    va_list args;
+    fflush(stdout);
+    fputs(\"Runtime error: \", stderr);
+    va_start(args, format);
+    vfprintf(stderr, format, args);
+    va_end(args);
+    fputc('\\n', stderr);
+    exit(1);
+}
+";
+
+const AT: &str = "
+/* Returns the cell `offset` cells away from the current one, without moving the pointer.
+   Outside the tape, the pointer moves there and back, so that the tape grows, wraps or
+   fails like the move would. */
+static cell *at(ptrdiff_t offset, int line, int column) {
+    size_t target = (size_t)(p - tape) + (size_t)offset;
+    if (target < len) {
+        return tape + target;
+    }
+    move(offset, line, column);
+    cell *moved = p;
+    move(-offset, line, column);
+    return moved;
+}
+";
+
+/// A C expression for a maximum number of cells.
+fn c_max(max: usize) -> String {
+    if max == usize::MAX {
+        "SIZE_MAX".to_string()
+    } else {
+        format!("(size_t){}u", max)
+    }
+}
+
+/// Builds the `move` helper, which moves the pointer by `offset` cells for the
+/// command at `line` and `column`, following the tape layout.
+fn move_function(tape: TapeConfig) -> String {
+    let mut out = String::from(
+        "
+/* Moves the pointer, handling the ends of the tape. */
+static void move(ptrdiff_t offset, int line, int column) {
+    size_t pointer = (size_t)(p - tape);
+    size_t target = pointer + (size_t)offset;
+    size_t magnitude = offset < 0 ? (size_t)0 - (size_t)offset : (size_t)offset;
+    if (target < len) {
+        p = tape + target;
+        return;
+    }
+",
+    );
+
+    let underflow = "        fail(\"Pointer underflow at line %d, column %d: attempted to move left %zu steps when pointer was at position %zu\",
+             line, column, magnitude, pointer);
+";
+
+    match tape {
+        TapeConfig::Fixed { .. } => {
+            out.push_str("    if (offset < 0) {\n");
+            out.push_str(underflow);
+            out.push_str("    }\n");
+            out.push_str("    fail(\"Pointer overflow at line %d, column %d: attempted to move right %zu steps when pointer was at position %zu\",\n");
+            out.push_str("         line, column, magnitude, pointer);\n");
+        }
+        TapeConfig::Wrapping { .. } => {
+            out.push_str("    (void)line;\n    (void)column;\n    (void)magnitude;\n");
+            out.push_str(
+                "    ptrdiff_t wrapped = ((ptrdiff_t)pointer + offset) % (ptrdiff_t)len;\n",
+            );
+            out.push_str("    p = tape + (wrapped < 0 ? wrapped + (ptrdiff_t)len : wrapped);\n");
+        }
+        TapeConfig::Growing { max, .. } => {
+            let _ = writeln!(out, "    size_t max = {};", c_max(max));
+            out.push_str("    if (offset < 0) {\n");
+            out.push_str(underflow);
+            out.push_str("    }\n");
+            push_grow_right(&mut out, "pointer");
+        }
+        TapeConfig::Bidirectional { max, .. } => {
+            let _ = writeln!(out, "    size_t max = {};", c_max(max));
+            out.push_str("    if (offset < 0) {\n");
+            out.push_str("        /* Grow to the left, by at least the current length */\n");
+            out.push_str("        size_t missing = magnitude - pointer;\n");
+            out.push_str("        if (missing > max - len) {\n");
+            out.push_str(&limit_exceeded("            ", "pointer - origin"));
+            out.push_str("        }\n");
+            out.push_str("        size_t added = missing > len ? missing : len;\n");
+            out.push_str("        if (added > max - len) {\n");
+            out.push_str("            added = max - len;\n");
+            out.push_str("        }\n");
+            out.push_str("        cell *grown = calloc(len + added, sizeof(cell));\n");
+            out.push_str("        if (!grown) {\n");
+            out.push_str("            fail(\"Out of memory\");\n");
+            out.push_str("        }\n");
+            out.push_str("        memcpy(grown + added, tape, len * sizeof(cell));\n");
+            out.push_str("        free(tape);\n");
+            out.push_str("        tape = grown;\n");
+            out.push_str("        len += added;\n");
+            out.push_str("        origin += added;\n");
+            out.push_str("        p = tape + (pointer + added - magnitude);\n");
+            out.push_str("        return;\n");
+            out.push_str("    }\n");
+            push_grow_right(&mut out, "pointer - origin");
+        }
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// Appends the code growing the tape to the right, geometrically and up to `max` cells.
+fn push_grow_right(out: &mut String, position: &str) {
+    out.push_str("    if (target >= max) {\n");
+    out.push_str(&limit_exceeded("        ", position));
+    out.push_str("    }\n");
+    out.push_str("    size_t new_len = len * 2 > target + 1 ? len * 2 : target + 1;\n");
+    out.push_str("    if (new_len > max || new_len < len) {\n");
+    out.push_str("        new_len = max;\n");
+    out.push_str("    }\n");
+    out.push_str("    tape = realloc(tape, new_len * sizeof(cell));\n");
+    out.push_str("    if (!tape) {\n");
+    out.push_str("        fail(\"Out of memory\");\n");
+    out.push_str("    }\n");
+    out.push_str("    memset(tape + len, 0, (new_len - len) * sizeof(cell));\n");
+    out.push_str("    len = new_len;\n");
+    out.push_str("    p = tape + target;\n");
+}
+
+/// Builds the call reporting that the tape cannot grow to `max` cells, indented by `indent`.
+/// `position` is the C expression of the pointer position relative to cell 0.
+fn limit_exceeded(indent: &str, position: &str) -> String {
+    format!(
+        "{0}fail(\"Tape limit exceeded at line %d, column %d: the tape cannot grow beyond %zu cells, pointer was at position %td\",\n{0}     line, column, max, (ptrdiff_t)({1}));\n",
+        indent, position
+    )
+}
+
+/// Builds the `input` helper, which reads a byte into the current cell for the command
+/// at `line` and `column`, following the EOF policy.
+fn input_function(eof_policy: EofPolicy) -> String {
+    let at_eof = match eof_policy {
+        EofPolicy::Unchanged => "    (void)line;\n    (void)column;\n",
+        EofPolicy::Zero => "    (void)line;\n    (void)column;\n    p[0] = 0;\n",
+        EofPolicy::MinusOne => "    (void)line;\n    (void)column;\n    p[0] = (cell)-1;\n",
+        EofPolicy::Error => {
+            "    fail(\"Unexpected end of input at line %d, column %d\", line, column);\n"
+        }
+    };
+
+    format!(
+        "
+/* Reads a byte into the current cell, showing any pending output first. */
+static void input(int line, int column) {{
+    fflush(stdout);
+    int c = getchar();
+    if (c != EOF) {{
+        p[0] = (cell)c;
+        return;
+    }}
+{}}}
+",
+        at_eof
+    )
+}
+
+/// Builds a compound assignment adding `delta` to a cell, such as `+= 3` or `-= 1`.
+fn compound_add(delta: i64, options: &CodegenOptions) -> String {
+    let delta = options.wrap_delta(delta);
+    let (operator, magnitude) = if delta < 0 {
+        ("-=", delta.unsigned_abs())
+    } else {
+        ("+=", delta as u128)
+    };
+
+    if magnitude > i32::MAX as u128 {
+        format!("{} {}u", operator, magnitude)
+    } else {
+        format!("{} {}", operator, magnitude)
+    }
+}
+
+/// Builds a compound assignment adding `value` times `factor` to a cell, such as
+/// `+= (cell)(value * 3u)`. Unsigned arithmetic keeps the product from overflowing.
+fn compound_mul_add(factor: i64, options: &CodegenOptions) -> String {
+    let factor = options.wrap_delta(factor);
+    let (operator, magnitude) = if factor < 0 {
+        ("-=", factor.unsigned_abs())
+    } else {
+        ("+=", factor as u128)
+    };
+    format!("{} (cell)(value * {}u)", operator, magnitude)
+}
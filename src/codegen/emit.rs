@@ -0,0 +1,124 @@
+use crate::codegen::{
+    emit_c, emit_elf, emit_gas, emit_llvm_ir, emit_nasm, emit_rust, emit_wasm, emit_wat,
+    CodegenOptions, LINKER_SCRIPT,
+};
+use crate::parser::BfOp;
+use std::fmt;
+use std::str::FromStr;
+
+/// The kind of output produced when building a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Emit {
+    /// A standalone C program.
+    #[default]
+    C,
+    /// A self-contained Rust source file defining a `run` function.
+    Rust,
+    /// A textual LLVM IR module.
+    LlvmIr,
+    /// A WebAssembly module in the text format.
+    Wat,
+    /// A WebAssembly module in the binary format.
+    Wasm,
+    /// x86-64 assembly for GNU as, along with a linker script.
+    Asm,
+    /// x86-64 assembly for NASM, along with a linker script.
+    Nasm,
+    /// A static x86-64 Linux executable.
+    Elf,
+}
+
+impl Emit {
+    /// The usual file extension of the output, empty for executables.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Emit::C => "c",
+            Emit::Rust => "rs",
+            Emit::LlvmIr => "ll",
+            Emit::Wat => "wat",
+            Emit::Wasm => "wasm",
+            Emit::Asm => "s",
+            Emit::Nasm => "asm",
+            Emit::Elf => "",
+        }
+    }
+
+    /// The kind of output usually written to a file with `extension`, if any.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        [
+            Emit::C,
+            Emit::Rust,
+            Emit::LlvmIr,
+            Emit::Wat,
+            Emit::Wasm,
+            Emit::Asm,
+            Emit::Nasm,
+            Emit::Elf,
+        ]
+        .into_iter()
+        .find(|emit| emit.extension() == extension)
+    }
+
+    /// Whether the output is an executable.
+    pub fn is_executable(self) -> bool {
+        self == Emit::Elf
+    }
+
+    /// The extension and contents of a file to write next to the output, if any.
+    pub fn companion(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Emit::Asm | Emit::Nasm => Some(("ld", LINKER_SCRIPT)),
+            _ => None,
+        }
+    }
+
+    /// Compiles `program` to this kind of output.
+    pub fn compile(self, program: &[BfOp], options: &CodegenOptions) -> Vec<u8> {
+        match self {
+            Emit::C => emit_c(program, options).into_bytes(),
+            Emit::Rust => emit_rust(program, options).into_bytes(),
+            Emit::LlvmIr => emit_llvm_ir(program, options).into_bytes(),
+            Emit::Wat => emit_wat(program, options).into_bytes(),
+            Emit::Wasm => emit_wasm(program, options),
+            Emit::Asm => emit_gas(program, options).into_bytes(),
+            Emit::Nasm => emit_nasm(program, options).into_bytes(),
+            Emit::Elf => emit_elf(program, options),
+        }
+    }
+}
+
+impl fmt::Display for Emit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Emit::C => write!(f, "c"),
+            Emit::Rust => write!(f, "rust"),
+            Emit::LlvmIr => write!(f, "llvm-ir"),
+            Emit::Wat => write!(f, "wat"),
+            Emit::Wasm => write!(f, "wasm"),
+            Emit::Asm => write!(f, "asm"),
+            Emit::Nasm => write!(f, "nasm"),
+            Emit::Elf => write!(f, "elf"),
+        }
+    }
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "c" => Ok(Emit::C),
+            "rust" => Ok(Emit::Rust),
+            "llvm-ir" => Ok(Emit::LlvmIr),
+            "wat" => Ok(Emit::Wat),
+            "wasm" => Ok(Emit::Wasm),
+            "asm" => Ok(Emit::Asm),
+            "nasm" => Ok(Emit::Nasm),
+            "elf" => Ok(Emit::Elf),
+            _ => Err(format!(
+                "invalid output kind `{}`, expected c, rust, llvm-ir, wat, wasm, asm, nasm or elf",
+                s
+            )),
+        }
+    }
+}
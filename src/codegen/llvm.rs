@@ -0,0 +1,565 @@
+use crate::bytecode::{Bytecode, Instruction};
+use crate::codegen::CodegenOptions;
+use crate::interpreter::{CellWidth, EofPolicy, TapeConfig};
+use crate::parser::BfOp;
+use std::fmt::Write;
+
+/// Compiles `program` to a textual LLVM IR module using `getchar` and `putchar`.
+///
+/// # Details
+/// The tape starts out as the global array `@tape`, and the pointer is an index kept in a
+/// stack slot of `@main`, which `mem2reg` turns into a register. Each loop becomes a header
+/// block testing the current cell and a body block branching back to the header.
+/// Moves and reads call small internal functions that implement the tape layout and the
+/// EOF policy, and report errors with the same messages as the interpreter.
+///
+/// The module uses opaque pointers, so it needs LLVM 15 or later, and a 64-bit target.
+pub fn emit_llvm_ir(program: &[BfOp], options: &CodegenOptions) -> String {
+    let bytecode = Bytecode::compile(program);
+    let cell = format!("i{}", options.cell_width.bits());
+    let growable = matches!(
+        options.tape,
+        TapeConfig::Growing { .. } | TapeConfig::Bidirectional { .. }
+    );
+
+    let mut out = String::new();
+    let _ = writeln!(out, "; Generated by bf-rs.\n");
+    let _ = writeln!(
+        out,
+        "@tape = internal global [{} x {}] zeroinitializer",
+        options.initial_cells(),
+        cell
+    );
+    if growable {
+        out.push_str("; The cells, which move to the heap once the tape grows, and their number\n");
+        out.push_str("@cells = internal global ptr @tape\n");
+        let _ = writeln!(
+            out,
+            "@len = internal global i64 {}",
+            options.initial_cells()
+        );
+    }
+    if let TapeConfig::Bidirectional { .. } = options.tape {
+        out.push_str("; The index of cell 0, which moves as the tape grows to the left\n");
+        out.push_str("@origin = internal global i64 0\n");
+    }
+    out.push('\n');
+    for (name, message) in MESSAGES {
+        let _ = writeln!(
+            out,
+            "@{} = private unnamed_addr constant [{} x i8] c\"Runtime error: {}\\0A\\00\"",
+            name,
+            "Runtime error: ".len() + message.len() + 2,
+            message
+        );
+    }
+    out.push_str(DECLARATIONS);
+
+    // Only define the helpers that are used, like the other backends
+    let uses = |predicate: fn(&Instruction) -> bool| bytecode.instructions.iter().any(predicate);
+    if uses(Instruction::moves_pointer) {
+        out.push_str(&move_functions(options));
+    }
+    if uses(|instruction| matches!(instruction, Instruction::Input)) {
+        out.push_str(&input_function(options.eof_policy, options.cell_width));
+    }
+
+    let mut main = Main {
+        out: String::new(),
+        cell: &cell,
+        base: if growable { None } else { Some("@tape") },
+        next: 0,
+    };
+    for (index, (instruction, span)) in bytecode
+        .instructions
+        .iter()
+        .zip(&bytecode.spans)
+        .enumerate()
+    {
+        main.lower(index, instruction, span.line, span.column, options);
+    }
+
+    out.push_str("\ndefine i32 @main() {\nentry:\n");
+    out.push_str("  %pointer = alloca i64\n");
+    out.push_str("  store i64 0, ptr %pointer\n");
+    out.push_str(&main.out);
+    out.push_str("  ret i32 0\n}\n");
+    out
+}
+
+/// The body of `@main`, built one instruction at a time.
+struct Main<'a> {
+    out: String,
+    /// The IR type of a cell.
+    cell: &'a str,
+    /// The constant address of the cells, unless the tape can grow and move them.
+    base: Option<&'a str>,
+    /// The number of the next temporary value.
+    next: usize,
+}
+
+impl Main<'_> {
+    /// Returns the name of a fresh temporary value.
+    fn value(&mut self) -> String {
+        self.next += 1;
+        format!("%v{}", self.next)
+    }
+
+    /// Emits the computation of the address of the current cell, and returns its name.
+    fn current(&mut self) -> String {
+        let base = match self.base {
+            Some(base) => base.to_string(),
+            None => {
+                let base = self.value();
+                let _ = writeln!(self.out, "  {} = load ptr, ptr @cells", base);
+                base
+            }
+        };
+        let pointer = self.value();
+        let address = self.value();
+        let _ = writeln!(self.out, "  {} = load i64, ptr %pointer", pointer);
+        let _ = writeln!(
+            self.out,
+            "  {} = getelementptr {}, ptr {}, i64 {}",
+            address, self.cell, base, pointer
+        );
+        address
+    }
+
+    /// Emits the load of the current cell, and returns its name along with its address.
+    fn load(&mut self) -> (String, String) {
+        let address = self.current();
+        let value = self.value();
+        let _ = writeln!(
+            self.out,
+            "  {} = load {}, ptr {}",
+            value, self.cell, address
+        );
+        (value, address)
+    }
+
+    /// Emits a call to `@move` for the command at `line` and `column`.
+    fn move_by(&mut self, offset: isize, line: usize, column: usize) {
+        let pointer = self.value();
+        let moved = self.value();
+        let _ = writeln!(self.out, "  {} = load i64, ptr %pointer", pointer);
+        let _ = writeln!(
+            self.out,
+            "  {} = call i64 @move(i64 {}, i64 {}, i32 {}, i32 {})",
+            moved, pointer, offset, line, column
+        );
+        let _ = writeln!(self.out, "  store i64 {}, ptr %pointer", moved);
+    }
+
+    fn lower(
+        &mut self,
+        index: usize,
+        instruction: &Instruction,
+        line: usize,
+        column: usize,
+        options: &CodegenOptions,
+    ) {
+        let cell = self.cell;
+        match *instruction {
+            Instruction::Move(offset) => self.move_by(offset, line, column),
+            Instruction::Add(delta) => {
+                let (value, address) = self.load();
+                let sum = self.value();
+                let _ = writeln!(
+                    self.out,
+                    "  {} = add {} {}, {}",
+                    sum,
+                    cell,
+                    value,
+                    constant(options.wrap_delta(delta), options.cell_width)
+                );
+                let _ = writeln!(self.out, "  store {} {}, ptr {}", cell, sum, address);
+            }
+            Instruction::Output => {
+                // `putchar` converts its argument to `unsigned char` itself
+                let (value, _) = self.load();
+                let argument = match options.cell_width {
+                    CellWidth::U32 => value,
+                    width => {
+                        let converted = self.value();
+                        let conversion = if width == CellWidth::U64 {
+                            "trunc"
+                        } else {
+                            "zext"
+                        };
+                        let _ = writeln!(
+                            self.out,
+                            "  {} = {} {} {} to i32",
+                            converted, conversion, cell, value
+                        );
+                        converted
+                    }
+                };
+                let _ = writeln!(self.out, "  call i32 @putchar(i32 {})", argument);
+            }
+            Instruction::Input => {
+                let address = self.current();
+                let _ = writeln!(
+                    self.out,
+                    "  call void @input(ptr {}, i32 {}, i32 {})",
+                    address, line, column
+                );
+            }
+            Instruction::JumpIfZero(_) => {
+                let _ = writeln!(self.out, "  br label %loop{}", index);
+                let _ = writeln!(self.out, "loop{}:", index);
+                let (value, _) = self.load();
+                let nonzero = self.value();
+                let _ = writeln!(self.out, "  {} = icmp ne {} {}, 0", nonzero, cell, value);
+                let _ = writeln!(
+                    self.out,
+                    "  br i1 {}, label %body{}, label %end{}",
+                    nonzero, index, index
+                );
+                let _ = writeln!(self.out, "body{}:", index);
+            }
+            Instruction::JumpIfNonZero(target) => {
+                let _ = writeln!(self.out, "  br label %loop{}", target - 1);
+                let _ = writeln!(self.out, "end{}:", target - 1);
+            }
+            Instruction::Clear => {
+                let address = self.current();
+                let _ = writeln!(self.out, "  store {} 0, ptr {}", cell, address);
+            }
+            Instruction::Set(value) => {
+                let address = self.current();
+                let _ = writeln!(
+                    self.out,
+                    "  store {} {}, ptr {}",
+                    cell,
+                    constant(options.wrap_delta(value), options.cell_width),
+                    address
+                );
+            }
+            Instruction::MulAdd { offset, factor } => {
+                let (value, _) = self.load();
+                let nonzero = self.value();
+                let _ = writeln!(self.out, "  {} = icmp ne {} {}, 0", nonzero, cell, value);
+                let _ = writeln!(
+                    self.out,
+                    "  br i1 {}, label %muladd{}, label %muladd_done{}",
+                    nonzero, index, index
+                );
+                let _ = writeln!(self.out, "muladd{}:", index);
+                let product = self.value();
+                let _ = writeln!(
+                    self.out,
+                    "  {} = mul {} {}, {}",
+                    product,
+                    cell,
+                    value,
+                    constant(options.wrap_delta(factor), options.cell_width)
+                );
+                self.move_by(offset, line, column);
+                let (target, address) = self.load();
+                let sum = self.value();
+                let _ = writeln!(self.out, "  {} = add {} {}, {}", sum, cell, target, product);
+                let _ = writeln!(self.out, "  store {} {}, ptr {}", cell, sum, address);
+                self.move_by(-offset, line, column);
+                let _ = writeln!(self.out, "  br label %muladd_done{}", index);
+                let _ = writeln!(self.out, "muladd_done{}:", index);
+            }
+            Instruction::Scan(stride) => {
+                let _ = writeln!(self.out, "  br label %scan{}", index);
+                let _ = writeln!(self.out, "scan{}:", index);
+                let (value, _) = self.load();
+                let nonzero = self.value();
+                let _ = writeln!(self.out, "  {} = icmp ne {} {}, 0", nonzero, cell, value);
+                let _ = writeln!(
+                    self.out,
+                    "  br i1 {}, label %scan_move{}, label %scan_done{}",
+                    nonzero, index, index
+                );
+                let _ = writeln!(self.out, "scan_move{}:", index);
+                self.move_by(stride, line, column);
+                let _ = writeln!(self.out, "  br label %scan{}", index);
+                let _ = writeln!(self.out, "scan_done{}:", index);
+            }
+            Instruction::AddAt { offset, delta } => {
+                self.move_by(offset, line, column);
+                self.lower(index, &Instruction::Add(delta), line, column, options);
+                self.move_by(-offset, line, column);
+            }
+            Instruction::OutputAt(offset) => {
+                self.move_by(offset, line, column);
+                self.lower(index, &Instruction::Output, line, column, options);
+                self.move_by(-offset, line, column);
+            }
+        }
+    }
+}
+
+/// An IR constant for a cell value wrapped to `width`, which must be in the signed range.
+fn constant(value: i128, width: CellWidth) -> i128 {
+    let modulus = 1i128 << width.bits();
+    if value >= modulus / 2 {
+        value - modulus
+    } else {
+        value
+    }
+}
+
+/// The error messages, without the `Runtime error: ` prefix, by global name.
+const MESSAGES: [(&str, &str); 5] = [
+    (
+        "underflow",
+        "Pointer underflow at line %d, column %d: attempted to move left %zu steps when pointer was at position %zu",
+    ),
+    (
+        "overflow",
+        "Pointer overflow at line %d, column %d: attempted to move right %zu steps when pointer was at position %zu",
+    ),
+    (
+        "limit",
+        "Tape limit exceeded at line %d, column %d: the tape cannot grow beyond %zu cells, pointer was at position %td",
+    ),
+    ("eof", "Unexpected end of input at line %d, column %d"),
+    ("out_of_memory", "Out of memory"),
+];
+
+const DECLARATIONS: &str = "
+declare i32 @getchar()
+declare i32 @putchar(i32)
+declare i32 @fflush(ptr)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @exit(i32) noreturn
+declare ptr @calloc(i64, i64)
+declare ptr @memcpy(ptr, ptr, i64)
+declare void @free(ptr)
+";
+
+/// Builds the instructions reporting a runtime error like the interpreter does, then
+/// exiting. `arguments` are the typed values formatted into the message.
+fn fail(message: &str, arguments: &str) -> String {
+    format!(
+        "  call i32 @fflush(ptr null)
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @{}{})
+  call void @exit(i32 1)
+  unreachable
+",
+        message, arguments
+    )
+}
+
+/// Builds `@move`, which moves the pointer by `offset` cells for the command at `line`
+/// and `column` and returns the new pointer, along with the functions it calls out of
+/// line when the target is past either end of the tape.
+fn move_functions(options: &CodegenOptions) -> String {
+    let tape = options.tape;
+    let len = match tape {
+        TapeConfig::Fixed { .. } | TapeConfig::Wrapping { .. } => {
+            options.initial_cells().to_string()
+        }
+        TapeConfig::Growing { .. } | TapeConfig::Bidirectional { .. } => "%len".to_string(),
+    };
+
+    let mut out = String::from(
+        "
+; Moves the pointer, handling the ends of the tape out of line
+define internal i64 @move(i64 %pointer, i64 %offset, i32 %line, i32 %column) {
+entry:
+  %target = add i64 %pointer, %offset
+",
+    );
+    if len == "%len" {
+        out.push_str("  %len = load i64, ptr @len\n");
+    }
+    let _ = write!(
+        out,
+        "  %inside = icmp ult i64 %target, {len}
+  br i1 %inside, label %inside_tape, label %outside_tape
+inside_tape:
+  ret i64 %target
+outside_tape:
+  %moved = call i64 @move_outside(i64 %pointer, i64 %offset, i32 %line, i32 %column)
+  ret i64 %moved
+}}
+
+define internal i64 @move_outside(i64 %pointer, i64 %offset, i32 %line, i32 %column) noinline cold {{
+entry:
+  %target = add i64 %pointer, %offset
+  %negative = icmp slt i64 %offset, 0
+  %negated = sub i64 0, %offset
+  %magnitude = select i1 %negative, i64 %negated, i64 %offset
+"
+    );
+    if len == "%len" {
+        out.push_str("  %len = load i64, ptr @len\n");
+    }
+
+    let underflow = fail(
+        "underflow",
+        ", i32 %line, i32 %column, i64 %magnitude, i64 %pointer",
+    );
+    match tape {
+        TapeConfig::Fixed { .. } => {
+            out.push_str("  br i1 %negative, label %underflow, label %overflow\n");
+            out.push_str("underflow:\n");
+            out.push_str(&underflow);
+            out.push_str("overflow:\n");
+            out.push_str(&fail(
+                "overflow",
+                ", i32 %line, i32 %column, i64 %magnitude, i64 %pointer",
+            ));
+        }
+        TapeConfig::Wrapping { .. } => {
+            let _ = write!(
+                out,
+                "  %wrapped = srem i64 %target, {len}
+  %below = icmp slt i64 %wrapped, 0
+  %raised = add i64 %wrapped, {len}
+  %result = select i1 %below, i64 %raised, i64 %wrapped
+  ret i64 %result
+"
+            );
+        }
+        TapeConfig::Growing { max, .. } => {
+            out.push_str("  br i1 %negative, label %underflow, label %right\n");
+            out.push_str("underflow:\n");
+            out.push_str(&underflow);
+            push_grow_right(&mut out, max, "%pointer");
+        }
+        TapeConfig::Bidirectional { max, .. } => {
+            let _ = write!(
+                out,
+                "  %origin = load i64, ptr @origin
+  %position = sub i64 %pointer, %origin
+  %room = sub i64 {max}, %len
+  br i1 %negative, label %left, label %right
+left:
+  ; Grow to the left, by at least the current length
+  %missing = sub i64 %magnitude, %pointer
+  %too_far = icmp ugt i64 %missing, %room
+  br i1 %too_far, label %limit, label %grow_left
+grow_left:
+  %longer = icmp ugt i64 %missing, %len
+  %wanted_left = select i1 %longer, i64 %missing, i64 %len
+  %clamped_left = icmp ugt i64 %wanted_left, %room
+  %added = select i1 %clamped_left, i64 %room, i64 %wanted_left
+  %total = add i64 %len, %added
+  %old_left = load ptr, ptr @cells
+  %grown_left = call ptr @resize(ptr %old_left, i64 %len, i64 %total, i64 %added)
+  store ptr %grown_left, ptr @cells
+  store i64 %total, ptr @len
+  %moved_origin = add i64 %origin, %added
+  store i64 %moved_origin, ptr @origin
+  %shifted = add i64 %pointer, %added
+  %result_left = sub i64 %shifted, %magnitude
+  ret i64 %result_left
+",
+                max = max as i64
+            );
+            push_grow_right(&mut out, max, "%position");
+        }
+    }
+    out.push_str("}\n");
+
+    if let TapeConfig::Growing { .. } | TapeConfig::Bidirectional { .. } = tape {
+        let _ = write!(
+            out,
+            "
+; Moves the cells to a new zeroed allocation of `new_len` cells, `shift` cells to the right
+define internal ptr @resize(ptr %old, i64 %len, i64 %new_len, i64 %shift) {{
+entry:
+  %new = call ptr @calloc(i64 %new_len, i64 {size})
+  %failed = icmp eq ptr %new, null
+  br i1 %failed, label %out_of_memory, label %copy
+out_of_memory:
+{out_of_memory}copy:
+  %destination = getelementptr i{bits}, ptr %new, i64 %shift
+  %bytes = mul i64 %len, {size}
+  call ptr @memcpy(ptr %destination, ptr %old, i64 %bytes)
+  %global = icmp eq ptr %old, @tape
+  br i1 %global, label %done, label %release
+release:
+  call void @free(ptr %old)
+  br label %done
+done:
+  ret ptr %new
+}}
+",
+            size = options.cell_width.bits() / 8,
+            bits = options.cell_width.bits(),
+            out_of_memory = fail("out_of_memory", ""),
+        );
+    }
+    out
+}
+
+/// Appends the blocks growing the tape to the right, geometrically and up to `max` cells,
+/// along with the `limit` block reporting the pointer at `position`.
+fn push_grow_right(out: &mut String, max: usize, position: &str) {
+    let arguments = format!(
+        ", i32 %line, i32 %column, i64 {}, i64 {}",
+        max as i64, position
+    );
+    let limit = fail("limit", &arguments);
+    let _ = write!(
+        out,
+        "right:
+  %beyond = icmp uge i64 %target, {max}
+  br i1 %beyond, label %limit, label %grow_right
+limit:
+{limit}grow_right:
+  %doubled = shl i64 %len, 1
+  %needed = add i64 %target, 1
+  %larger = icmp ugt i64 %doubled, %needed
+  %wanted = select i1 %larger, i64 %doubled, i64 %needed
+  %too_long = icmp ugt i64 %wanted, {max}
+  %wrapped = icmp ult i64 %wanted, %len
+  %clamped = or i1 %too_long, %wrapped
+  %new_len = select i1 %clamped, i64 {max}, i64 %wanted
+  %old = load ptr, ptr @cells
+  %grown = call ptr @resize(ptr %old, i64 %len, i64 %new_len, i64 0)
+  store ptr %grown, ptr @cells
+  store i64 %new_len, ptr @len
+  ret i64 %target
+",
+        max = max as i64,
+        limit = limit,
+    );
+}
+
+/// Builds `@input`, which reads a byte into the cell at `%cell` for the command at `line`
+/// and `column`, following the EOF policy.
+fn input_function(eof_policy: EofPolicy, width: CellWidth) -> String {
+    let cell = format!("i{}", width.bits());
+    let at_eof = match eof_policy {
+        EofPolicy::Unchanged => "  ret void\n".to_string(),
+        EofPolicy::Zero => format!("  store {} 0, ptr %cell\n  ret void\n", cell),
+        EofPolicy::MinusOne => format!("  store {} -1, ptr %cell\n  ret void\n", cell),
+        EofPolicy::Error => fail("eof", ", i32 %line, i32 %column"),
+    };
+    let store = match width {
+        CellWidth::U32 => "  store i32 %byte, ptr %cell\n".to_string(),
+        CellWidth::U64 => {
+            "  %value = zext i32 %byte to i64\n  store i64 %value, ptr %cell\n".to_string()
+        }
+        _ => format!(
+            "  %value = trunc i32 %byte to {0}\n  store {0} %value, ptr %cell\n",
+            cell
+        ),
+    };
+
+    format!(
+        "
+; Reads a byte into a cell, showing any pending output first
+define internal void @input(ptr %cell, i32 %line, i32 %column) {{
+entry:
+  call i32 @fflush(ptr null)
+  %byte = call i32 @getchar()
+  %eof = icmp eq i32 %byte, -1
+  br i1 %eof, label %end_of_input, label %read
+read:
+{}  ret void
+end_of_input:
+{}}}
+",
+        store, at_eof
+    )
+}
@@ -0,0 +1,23 @@
+//! Ahead-of-time compilation of Brainfuck programs.
+//!
+//! This module lowers a program, optimized or not, to source code for other toolchains,
+//! or directly to an executable.
+//! The generated programs behave like the interpreter configured with the same
+//! `CodegenOptions`, including the errors they report.
+
+mod asm;
+mod c;
+mod emit;
+mod llvm;
+mod options;
+mod rust;
+mod wasm;
+
+pub use asm::{emit_elf, emit_gas, emit_nasm, LINKER_SCRIPT};
+pub use c::emit_c;
+pub use emit::Emit;
+pub use llvm::emit_llvm_ir;
+pub use options::CodegenOptions;
+pub use rust::emit_rust;
+pub use rust::rust_from_source;
+pub use wasm::{emit_wasm, emit_wat};
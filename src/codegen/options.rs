@@ -0,0 +1,36 @@
+use crate::interpreter::{CellWidth, EofPolicy, TapeConfig};
+
+/// The runtime behavior compiled into a generated program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CodegenOptions {
+    /// Width of the memory cells.
+    pub cell_width: CellWidth,
+    /// What `,` does once the input is exhausted.
+    pub eof_policy: EofPolicy,
+    /// Layout of the tape.
+    pub tape: TapeConfig,
+}
+
+impl CodegenOptions {
+    /// The number of cells the tape starts with.
+    pub(crate) fn initial_cells(&self) -> usize {
+        match self.tape {
+            TapeConfig::Fixed { cells } | TapeConfig::Wrapping { cells } => cells.max(1),
+            TapeConfig::Growing { initial, .. } | TapeConfig::Bidirectional { initial, .. } => {
+                initial.max(1)
+            }
+        }
+    }
+
+    /// Wraps `delta` to the cell width, choosing the representative closest to zero,
+    /// so that generated code can add or subtract a small constant.
+    pub(crate) fn wrap_delta(&self, delta: i64) -> i128 {
+        let modulus = 1i128 << self.cell_width.bits();
+        let value = (delta as i128).rem_euclid(modulus);
+        if value > modulus / 2 {
+            value - modulus
+        } else {
+            value
+        }
+    }
+}
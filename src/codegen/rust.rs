@@ -0,0 +1,379 @@
+use crate::bytecode::{Bytecode, Instruction};
+use crate::codegen::CodegenOptions;
+use crate::interpreter::{CellWidth, EofPolicy, TapeConfig};
+use crate::lexer::Lexer;
+use crate::optimizer::Optimizer;
+use crate::parser::{BfOp, ParseError, Parser};
+use std::fmt::Write;
+
+/// Compiles `program` to a self-contained Rust source file, without any dependencies.
+///
+/// # Details
+/// The file defines `pub fn run(input: &mut impl Read, output: &mut impl Write) -> io::Result<()>`,
+/// which runs the program like the interpreter configured with `options` would.
+/// Runtime errors, such as moving the pointer left of the first cell, are returned as
+/// `io::Error`s carrying the same messages as the interpreter's errors.
+///
+/// The file is meant to be included in a module of its own:
+///
+/// ```ignore
+/// mod hello {
+///     include!(concat!(env!("OUT_DIR"), "/hello.rs"));
+/// }
+/// ```
+pub fn emit_rust(program: &[BfOp], options: &CodegenOptions) -> String {
+    let bytecode = Bytecode::compile(program);
+    let uses = |predicate: fn(&Instruction) -> bool| bytecode.instructions.iter().any(predicate);
+    let moves = uses(Instruction::moves_pointer);
+    let reads = uses(|instruction| matches!(instruction, Instruction::Input));
+    let writes_cells = uses(|instruction| {
+        !matches!(
+            instruction,
+            Instruction::Output | Instruction::JumpIfZero(_) | Instruction::JumpIfNonZero(_)
+        )
+    });
+    let bidirectional = matches!(options.tape, TapeConfig::Bidirectional { .. });
+
+    let mut out = String::new();
+    out.push_str("// Generated by bf-rs, do not edit.\n\n");
+    out.push_str("use std::io::{self, Read, Write};\n\n");
+    let _ = writeln!(out, "/// The type of the memory cells.");
+    let _ = writeln!(out, "type Cell = u{};\n", options.cell_width.bits());
+
+    out.push_str("/// The memory of the program, along with the pointer into it.\n");
+    out.push_str("struct Tape {\n    cells: Vec<Cell>,\n    pointer: usize,\n");
+    if bidirectional {
+        out.push_str("    /// The index of cell 0, which moves as the tape grows to the left.\n");
+        out.push_str("    origin: usize,\n");
+    }
+    out.push_str("}\n\n");
+
+    out.push_str(TAPE_ACCESS);
+    if moves {
+        out.push_str(&move_method(options.tape));
+    }
+    if uses(Instruction::uses_offset) {
+        out.push_str(AT_METHOD);
+    }
+    if reads {
+        out.push_str(&read_method(options.eof_policy));
+    }
+    out.push_str("}\n\n");
+
+    out.push_str("/// Runs the program, reading from `input` and writing to `output`.\n");
+    if !reads {
+        out.push_str("#[allow(unused_variables)]\n");
+    }
+    out.push_str(
+        "pub fn run(input: &mut impl Read, output: &mut impl Write) -> io::Result<()> {\n",
+    );
+    let _ = writeln!(
+        out,
+        "    let {}tape = Tape {{\n        cells: vec![0; {}],\n        pointer: 0,",
+        if writes_cells { "mut " } else { "" },
+        options.initial_cells()
+    );
+    if bidirectional {
+        out.push_str("        origin: 0,\n");
+    }
+    out.push_str("    };\n");
+
+    let mut depth = 1;
+    for (instruction, span) in bytecode.instructions.iter().zip(&bytecode.spans) {
+        if let Instruction::JumpIfNonZero(_) = instruction {
+            depth -= 1;
+        }
+        let indent = "    ".repeat(depth);
+
+        let _ = match *instruction {
+            Instruction::Move(offset) => writeln!(
+                out,
+                "{}tape.move_by({}, {}, {})?;",
+                indent, offset, span.line, span.column
+            ),
+            Instruction::Add(delta) => match options.wrap_delta(delta) {
+                delta if delta < 0 => writeln!(out, "{}tape.sub({});", indent, -delta),
+                delta => writeln!(out, "{}tape.add({});", indent, delta),
+            },
+            Instruction::Output => match options.cell_width {
+                CellWidth::U8 => writeln!(out, "{}output.write_all(&[tape.get()])?;", indent),
+                _ => writeln!(out, "{}output.write_all(&[tape.get() as u8])?;", indent),
+            },
+            Instruction::Input => writeln!(
+                out,
+                "{}tape.read(input, output, {}, {})?;",
+                indent, span.line, span.column
+            ),
+            Instruction::JumpIfZero(_) => writeln!(out, "{}while tape.get() != 0 {{", indent),
+            Instruction::JumpIfNonZero(_) => writeln!(out, "{}}}", indent),
+            Instruction::Clear => writeln!(out, "{}tape.set(0);", indent),
+            Instruction::Set(value) => {
+                let modulus = 1i128 << options.cell_width.bits();
+                let value = options.wrap_delta(value).rem_euclid(modulus);
+                writeln!(out, "{}tape.set({});", indent, value)
+            }
+            Instruction::MulAdd { offset, factor } => {
+                let (method, magnitude) = match options.wrap_delta(factor) {
+                    factor if factor < 0 => ("wrapping_sub", -factor),
+                    factor => ("wrapping_add", factor),
+                };
+                writeln!(
+                    out,
+                    "{0}if tape.get() != 0 {{\n\
+                     {0}    let value = tape.get();\n\
+                     {0}    tape.at({1}, {4}, {5})\n\
+                     {0}        .map(|cell| *cell = cell.{2}(value.wrapping_mul({3})))?;\n\
+                     {0}}}",
+                    indent, offset, method, magnitude, span.line, span.column
+                )
+            }
+            Instruction::Scan(stride) => writeln!(
+                out,
+                "{0}while tape.get() != 0 {{\n{0}    tape.move_by({1}, {2}, {3})?;\n{0}}}",
+                indent, stride, span.line, span.column
+            ),
+            Instruction::AddAt { offset, delta } => {
+                let (method, magnitude) = match options.wrap_delta(delta) {
+                    delta if delta < 0 => ("wrapping_sub", -delta),
+                    delta => ("wrapping_add", delta),
+                };
+                writeln!(
+                    out,
+                    "{}tape.at({}, {}, {}).map(|cell| *cell = cell.{}({}))?;",
+                    indent, offset, span.line, span.column, method, magnitude
+                )
+            }
+            Instruction::OutputAt(offset) => {
+                let cast = match options.cell_width {
+                    CellWidth::U8 => "",
+                    _ => " as u8",
+                };
+                writeln!(
+                    out,
+                    "{}output.write_all(&[*tape.at({}, {}, {})?{}])?;",
+                    indent, offset, span.line, span.column, cast
+                )
+            }
+        };
+
+        if let Instruction::JumpIfZero(_) = instruction {
+            depth += 1;
+        }
+    }
+
+    out.push_str("    output.flush()\n}\n");
+    out
+}
+
+/// Lexes, parses and compiles Brainfuck `source` to Rust, for use in build scripts.
+/// The program is optimized first, at the default level.
+///
+/// ```no_run
+/// // build.rs
+/// use bf_rs::codegen::{rust_from_source, CodegenOptions};
+/// use std::{env, fs, path::Path};
+///
+/// let source = fs::read_to_string("src/hello.bf").unwrap();
+/// let code = rust_from_source(&source, &CodegenOptions::default()).unwrap();
+/// let out_dir = env::var("OUT_DIR").unwrap();
+/// fs::write(Path::new(&out_dir).join("hello.rs"), code).unwrap();
+/// println!("cargo::rerun-if-changed=src/hello.bf");
+/// ```
+pub fn rust_from_source(source: &str, options: &CodegenOptions) -> Result<String, ParseError> {
+    let tokens = Lexer::new(source).tokenize();
+    let program = Parser::new(tokens).parse()?;
+
+    let program = Optimizer::new().optimize(program);
+
+    Ok(emit_rust(&program, options))
+}
+
+const TAPE_ACCESS: &str = "#[allow(dead_code)]
+impl Tape {
+    fn get(&self) -> Cell {
+        self.cells[self.pointer]
+    }
+
+    fn set(&mut self, value: Cell) {
+        self.cells[self.pointer] = value;
+    }
+
+    fn add(&mut self, delta: Cell) {
+        self.set(self.get().wrapping_add(delta));
+    }
+
+    fn sub(&mut self, delta: Cell) {
+        self.set(self.get().wrapping_sub(delta));
+    }
+";
+
+/// The `at` method, which returns the cell `offset` cells away from the current one.
+/// Outside the tape, the pointer moves there and back, so that the tape grows, wraps or
+/// fails like the move would.
+const AT_METHOD: &str = "
+    fn at(&mut self, offset: isize, line: usize, column: usize) -> io::Result<&mut Cell> {
+        let target = self.pointer.wrapping_add_signed(offset);
+        if target < self.cells.len() {
+            return Ok(&mut self.cells[target]);
+        }
+        self.move_by(offset, line, column)?;
+        let target = self.pointer;
+        self.move_by(-offset, line, column)?;
+        Ok(&mut self.cells[target])
+    }
+";
+
+/// Builds the `move_by` method, which moves the pointer by `offset` cells for the
+/// command at `line` and `column`, following the tape layout.
+fn move_method(tape: TapeConfig) -> String {
+    let mut out = String::from(
+        "
+    fn move_by(&mut self, offset: isize, line: usize, column: usize) -> io::Result<()> {
+        let target = self.pointer.wrapping_add_signed(offset);
+        if target < self.cells.len() {
+            self.pointer = target;
+            return Ok(());
+        }
+",
+    );
+
+    let underflow = "        let magnitude = offset.unsigned_abs();
+        if offset < 0 {
+            return Err(io::Error::other(format!(
+                \"Pointer underflow at line {}, column {}: attempted to move left {} steps when pointer was at position {}\",
+                line, column, magnitude, self.pointer
+            )));
+        }
+";
+
+    match tape {
+        TapeConfig::Fixed { .. } => {
+            out.push_str(underflow);
+            out.push_str("        Err(io::Error::other(format!(\n");
+            out.push_str("            \"Pointer overflow at line {}, column {}: attempted to move right {} steps when pointer was at position {}\",\n");
+            out.push_str("            line, column, magnitude, self.pointer\n");
+            out.push_str("        )))\n");
+        }
+        TapeConfig::Wrapping { .. } => {
+            out.push_str("        let _ = (line, column);\n");
+            out.push_str("        let len = self.cells.len() as i128;\n");
+            out.push_str(
+                "        self.pointer = (self.pointer as i128 + offset as i128).rem_euclid(len) as usize;\n",
+            );
+            out.push_str("        Ok(())\n");
+        }
+        TapeConfig::Growing { max, .. } => {
+            let _ = writeln!(out, "        let max: usize = {};", rust_max(max));
+            out.push_str(underflow);
+            push_grow_right(&mut out, "self.pointer as isize");
+        }
+        TapeConfig::Bidirectional { max, .. } => {
+            let _ = writeln!(out, "        let max: usize = {};", rust_max(max));
+            out.push_str("        let magnitude = offset.unsigned_abs();\n");
+            out.push_str("        if offset < 0 {\n");
+            out.push_str("            // Grow to the left, by at least the current length\n");
+            out.push_str("            let len = self.cells.len();\n");
+            out.push_str("            let missing = magnitude - self.pointer;\n");
+            out.push_str("            if len.saturating_add(missing) > max {\n");
+            out.push_str(&limit_exceeded(
+                "                ",
+                "self.pointer as isize - self.origin as isize",
+            ));
+            out.push_str("            }\n");
+            out.push_str("            let added = missing.max(len).min(max - len);\n");
+            out.push_str("            let mut cells = vec![0; added];\n");
+            out.push_str("            cells.append(&mut self.cells);\n");
+            out.push_str("            self.cells = cells;\n");
+            out.push_str("            self.origin += added;\n");
+            out.push_str("            self.pointer = self.pointer + added - magnitude;\n");
+            out.push_str("            return Ok(());\n");
+            out.push_str("        }\n");
+            push_grow_right(&mut out, "self.pointer as isize - self.origin as isize");
+        }
+    }
+
+    out.push_str("    }\n");
+    out
+}
+
+/// Appends the code growing the tape to the right, geometrically and up to `max` cells.
+/// `position` is the expression of the pointer position relative to cell 0.
+fn push_grow_right(out: &mut String, position: &str) {
+    out.push_str("        let target = self.pointer.saturating_add(magnitude);\n");
+    out.push_str("        if target >= max {\n");
+    out.push_str(&limit_exceeded("            ", position));
+    out.push_str("        }\n");
+    out.push_str("        let len = self.cells.len();\n");
+    out.push_str(
+        "        let new_len = target.saturating_add(1).max(len.saturating_mul(2)).min(max);\n",
+    );
+    out.push_str("        self.cells.resize(new_len, 0);\n");
+    out.push_str("        self.pointer = target;\n");
+    out.push_str("        Ok(())\n");
+}
+
+/// Builds the statement reporting that the tape cannot grow to `max` cells, indented by `indent`.
+fn limit_exceeded(indent: &str, position: &str) -> String {
+    format!(
+        "{0}return Err(io::Error::other(format!(
+{0}    \"Tape limit exceeded at line {{}}, column {{}}: the tape cannot grow beyond {{}} cells, pointer was at position {{}}\",
+{0}    line, column, max, {1}
+{0})));
+",
+        indent, position
+    )
+}
+
+/// A Rust expression for a maximum number of cells.
+fn rust_max(max: usize) -> String {
+    if max == usize::MAX {
+        "usize::MAX".to_string()
+    } else {
+        max.to_string()
+    }
+}
+
+/// Builds the `read` method, which reads a byte into the current cell for the command
+/// at `line` and `column`, following the EOF policy.
+fn read_method(eof_policy: EofPolicy) -> String {
+    let at_eof = match eof_policy {
+        EofPolicy::Unchanged => "                let _ = (line, column);\n",
+        EofPolicy::Zero => {
+            "                let _ = (line, column);\n                self.set(0);\n"
+        }
+        EofPolicy::MinusOne => {
+            "                let _ = (line, column);\n                self.set(Cell::MAX);\n"
+        }
+        EofPolicy::Error => {
+            "                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(\"Unexpected end of input at line {}, column {}\", line, column),
+                ));
+"
+        }
+    };
+
+    format!(
+        "
+    /// Reads a byte into the current cell, showing any pending output first.
+    fn read(
+        &mut self,
+        input: &mut impl Read,
+        output: &mut impl Write,
+        line: usize,
+        column: usize,
+    ) -> io::Result<()> {{
+        output.flush()?;
+        let mut buffer = [0];
+        match input.read_exact(&mut buffer) {{
+            Ok(()) => self.set(Cell::from(buffer[0])),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {{
+{}            }}
+            Err(e) => return Err(e),
+        }}
+        Ok(())
+    }}
+",
+        at_eof
+    )
+}
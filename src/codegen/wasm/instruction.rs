@@ -0,0 +1,271 @@
+//! The subset of WebAssembly instructions used by the backend, with their text and
+//! binary encodings.
+
+/// A WebAssembly value type. Only the local holding a cell value can be `i64`, so
+/// parameters and globals are all `i32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValType {
+    I32,
+    I64,
+}
+
+impl ValType {
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            ValType::I32 => 0x7F,
+            ValType::I64 => 0x7E,
+        }
+    }
+
+    pub(crate) fn text(self) -> &'static str {
+        match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+        }
+    }
+}
+
+/// A WebAssembly instruction.
+///
+/// Indices are absolute: function indices count the imports first. Blocks never take
+/// parameters or produce results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Instr {
+    Unreachable,
+    Block,
+    Loop,
+    If,
+    Else,
+    End,
+    Br(u32),
+    BrIf(u32),
+    Return,
+    Call(u32),
+    Select,
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    I32Load,
+    I64Load,
+    I32Load8U,
+    I32Load16U,
+    I32Store,
+    I64Store,
+    I32Store8,
+    I32Store16,
+    MemorySize,
+    MemoryGrow,
+    I32Const(i32),
+    I64Const(i64),
+    I32Eqz,
+    I32Eq,
+    I32LtS,
+    I32LtU,
+    I32GtS,
+    I32GeS,
+    I32GtU,
+    I32GeU,
+    I64Eqz,
+    I32Add,
+    I32Sub,
+    I32Mul,
+    I32DivS,
+    I32DivU,
+    I32RemS,
+    I64Add,
+    I64Mul,
+    I64ExtendI32U,
+    MemoryCopy,
+    MemoryFill,
+}
+
+/// Names used when rendering indices as text.
+pub(crate) struct Names<'a> {
+    pub(crate) functions: &'a [String],
+    pub(crate) globals: &'a [String],
+    pub(crate) locals: &'a [String],
+}
+
+impl Instr {
+    /// Whether the instruction opens a block, which is closed by `End`.
+    pub(crate) fn opens_block(self) -> bool {
+        matches!(self, Instr::Block | Instr::Loop | Instr::If)
+    }
+
+    /// Renders the instruction in the text format, naming indices with `names`.
+    pub(crate) fn text(self, names: &Names) -> String {
+        let name = |names: &[String], index: u32| format!("${}", names[index as usize]);
+        match self {
+            Instr::Br(depth) => format!("br {}", depth),
+            Instr::BrIf(depth) => format!("br_if {}", depth),
+            Instr::Call(index) => format!("call {}", name(names.functions, index)),
+            Instr::LocalGet(index) => format!("local.get {}", name(names.locals, index)),
+            Instr::LocalSet(index) => format!("local.set {}", name(names.locals, index)),
+            Instr::LocalTee(index) => format!("local.tee {}", name(names.locals, index)),
+            Instr::GlobalGet(index) => format!("global.get {}", name(names.globals, index)),
+            Instr::GlobalSet(index) => format!("global.set {}", name(names.globals, index)),
+            Instr::I32Const(value) => format!("i32.const {}", value),
+            Instr::I64Const(value) => format!("i64.const {}", value),
+            _ => self.mnemonic().to_string(),
+        }
+    }
+
+    fn mnemonic(self) -> &'static str {
+        match self {
+            Instr::Unreachable => "unreachable",
+            Instr::Block => "block",
+            Instr::Loop => "loop",
+            Instr::If => "if",
+            Instr::Else => "else",
+            Instr::End => "end",
+            Instr::Return => "return",
+            Instr::Select => "select",
+            Instr::I32Load => "i32.load",
+            Instr::I64Load => "i64.load",
+            Instr::I32Load8U => "i32.load8_u",
+            Instr::I32Load16U => "i32.load16_u",
+            Instr::I32Store => "i32.store",
+            Instr::I64Store => "i64.store",
+            Instr::I32Store8 => "i32.store8",
+            Instr::I32Store16 => "i32.store16",
+            Instr::MemorySize => "memory.size",
+            Instr::MemoryGrow => "memory.grow",
+            Instr::I32Eqz => "i32.eqz",
+            Instr::I32Eq => "i32.eq",
+            Instr::I32LtS => "i32.lt_s",
+            Instr::I32LtU => "i32.lt_u",
+            Instr::I32GtS => "i32.gt_s",
+            Instr::I32GeS => "i32.ge_s",
+            Instr::I32GtU => "i32.gt_u",
+            Instr::I32GeU => "i32.ge_u",
+            Instr::I64Eqz => "i64.eqz",
+            Instr::I32Add => "i32.add",
+            Instr::I32Sub => "i32.sub",
+            Instr::I32Mul => "i32.mul",
+            Instr::I32DivS => "i32.div_s",
+            Instr::I32DivU => "i32.div_u",
+            Instr::I32RemS => "i32.rem_s",
+            Instr::I64Add => "i64.add",
+            Instr::I64Mul => "i64.mul",
+            Instr::I64ExtendI32U => "i64.extend_i32_u",
+            Instr::MemoryCopy => "memory.copy",
+            Instr::MemoryFill => "memory.fill",
+            _ => unreachable!("Instruction with immediates"),
+        }
+    }
+
+    /// Appends the binary encoding of the instruction to `out`.
+    pub(crate) fn encode(self, out: &mut Vec<u8>) {
+        // Loads and stores take the alignment as a power of two, and an offset of 0
+        let memory = |out: &mut Vec<u8>, opcode: u8, align: u8| out.extend([opcode, align, 0]);
+        match self {
+            Instr::Unreachable => out.push(0x00),
+            Instr::Block => out.extend([0x02, 0x40]),
+            Instr::Loop => out.extend([0x03, 0x40]),
+            Instr::If => out.extend([0x04, 0x40]),
+            Instr::Else => out.push(0x05),
+            Instr::End => out.push(0x0B),
+            Instr::Br(depth) => {
+                out.push(0x0C);
+                write_u32(out, depth);
+            }
+            Instr::BrIf(depth) => {
+                out.push(0x0D);
+                write_u32(out, depth);
+            }
+            Instr::Return => out.push(0x0F),
+            Instr::Call(index) => {
+                out.push(0x10);
+                write_u32(out, index);
+            }
+            Instr::Select => out.push(0x1B),
+            Instr::LocalGet(index) => {
+                out.push(0x20);
+                write_u32(out, index);
+            }
+            Instr::LocalSet(index) => {
+                out.push(0x21);
+                write_u32(out, index);
+            }
+            Instr::LocalTee(index) => {
+                out.push(0x22);
+                write_u32(out, index);
+            }
+            Instr::GlobalGet(index) => {
+                out.push(0x23);
+                write_u32(out, index);
+            }
+            Instr::GlobalSet(index) => {
+                out.push(0x24);
+                write_u32(out, index);
+            }
+            Instr::I32Load => memory(out, 0x28, 2),
+            Instr::I64Load => memory(out, 0x29, 3),
+            Instr::I32Load8U => memory(out, 0x2D, 0),
+            Instr::I32Load16U => memory(out, 0x2F, 1),
+            Instr::I32Store => memory(out, 0x36, 2),
+            Instr::I64Store => memory(out, 0x37, 3),
+            Instr::I32Store8 => memory(out, 0x3A, 0),
+            Instr::I32Store16 => memory(out, 0x3B, 1),
+            Instr::MemorySize => out.extend([0x3F, 0x00]),
+            Instr::MemoryGrow => out.extend([0x40, 0x00]),
+            Instr::I32Const(value) => {
+                out.push(0x41);
+                write_i64(out, value as i64);
+            }
+            Instr::I64Const(value) => {
+                out.push(0x42);
+                write_i64(out, value);
+            }
+            Instr::I32Eqz => out.push(0x45),
+            Instr::I32Eq => out.push(0x46),
+            Instr::I32LtS => out.push(0x48),
+            Instr::I32LtU => out.push(0x49),
+            Instr::I32GtS => out.push(0x4A),
+            Instr::I32GeS => out.push(0x4E),
+            Instr::I32GtU => out.push(0x4B),
+            Instr::I32GeU => out.push(0x4F),
+            Instr::I64Eqz => out.push(0x50),
+            Instr::I32Add => out.push(0x6A),
+            Instr::I32Sub => out.push(0x6B),
+            Instr::I32Mul => out.push(0x6C),
+            Instr::I32DivS => out.push(0x6D),
+            Instr::I32DivU => out.push(0x6E),
+            Instr::I32RemS => out.push(0x6F),
+            Instr::I64Add => out.push(0x7C),
+            Instr::I64Mul => out.push(0x7E),
+            Instr::I64ExtendI32U => out.push(0xAD),
+            Instr::MemoryCopy => out.extend([0xFC, 10, 0x00, 0x00]),
+            Instr::MemoryFill => out.extend([0xFC, 11, 0x00]),
+        }
+    }
+}
+
+/// Appends `value` as an unsigned LEB128 number.
+pub(crate) fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Appends `value` as a signed LEB128 number.
+pub(crate) fn write_i64(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        // Done once the remaining bits are all copies of the sign bit of this byte
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
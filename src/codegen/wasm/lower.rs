@@ -0,0 +1,605 @@
+//! Lowering of programs to a WebAssembly module.
+
+use super::instruction::{Instr, ValType};
+use super::module::{FuncType, Function, Global, Import, Module};
+use crate::bytecode::{Bytecode, Instruction};
+use crate::codegen::CodegenOptions;
+use crate::interpreter::{CellWidth, EofPolicy, TapeConfig};
+use crate::parser::BfOp;
+
+// Function indices, imports first
+const OUTPUT: u32 = 0;
+const INPUT: u32 = 1;
+const ERROR: u32 = 2;
+const GROW: u32 = 3;
+const MOVE: u32 = 4;
+
+// Global indices
+const LEN: u32 = 0;
+const ORIGIN: u32 = 1;
+
+// Error kinds passed to the `error` import
+const UNDERFLOW: i32 = 1;
+const OVERFLOW: i32 = 2;
+const LIMIT: i32 = 3;
+const EOF: i32 = 4;
+
+const PAGE: usize = 65536;
+
+/// Compiles `program` to a WebAssembly module in the text format.
+///
+/// See [`emit_wasm`] for the interface of the module.
+pub fn emit_wat(program: &[BfOp], options: &CodegenOptions) -> String {
+    build(program, options).to_text()
+}
+
+/// Compiles `program` to a WebAssembly module in the binary format.
+///
+/// # Details
+/// The tape lives at the start of the exported `memory`, and the module exports
+/// `run: () -> ()`. It imports three functions from `env`:
+/// - `output(byte: i32)` writes a byte.
+/// - `input() -> i32` reads a byte, or returns -1 at the end of the input. The host
+///   should show any pending output first.
+/// - `error(kind, line, column, a, b: i32)` reports a runtime error, after which the
+///   module traps. `kind` is 1 for a pointer underflow and 2 for an overflow, with the
+///   size of the move in `a` and the position of the pointer in `b`; 3 when the tape
+///   limit is exceeded, with the limit in `a` and the position in `b`; and 4 for an
+///   unexpected end of input.
+///
+/// The memory is 32-bit, so growing tapes are limited to 2 GiB.
+pub fn emit_wasm(program: &[BfOp], options: &CodegenOptions) -> Vec<u8> {
+    build(program, options).to_binary()
+}
+
+/// How cells of a given width are loaded and stored.
+#[derive(Clone, Copy)]
+struct Cells {
+    /// The size of a cell in bytes.
+    width: i32,
+    load: Instr,
+    store: Instr,
+    /// Whether cells are `i64` values rather than `i32` values.
+    wide: bool,
+}
+
+impl Cells {
+    fn new(cell_width: CellWidth) -> Self {
+        let (load, store) = match cell_width {
+            CellWidth::U8 => (Instr::I32Load8U, Instr::I32Store8),
+            CellWidth::U16 => (Instr::I32Load16U, Instr::I32Store16),
+            CellWidth::U32 => (Instr::I32Load, Instr::I32Store),
+            CellWidth::U64 => (Instr::I64Load, Instr::I64Store),
+        };
+        Cells {
+            width: cell_width.bits() as i32 / 8,
+            load,
+            store,
+            wide: cell_width == CellWidth::U64,
+        }
+    }
+
+    fn value_type(self) -> ValType {
+        if self.wide {
+            ValType::I64
+        } else {
+            ValType::I32
+        }
+    }
+
+    fn constant(self, value: i64) -> Instr {
+        if self.wide {
+            Instr::I64Const(value)
+        } else {
+            Instr::I32Const(value as i32)
+        }
+    }
+}
+
+fn build(program: &[BfOp], options: &CodegenOptions) -> Module {
+    let cells = Cells::new(options.cell_width);
+    let width = cells.width as usize;
+    // Keep the tape within the 32-bit address space
+    let limit = (i32::MAX as usize) / width;
+    let initial = options.initial_cells().min(limit);
+    let bytes = initial * width;
+
+    let i32s = |count| vec![ValType::I32; count];
+    let imports = vec![
+        Import {
+            module: "env",
+            name: "output",
+            ty: FuncType {
+                params: i32s(1),
+                results: vec![],
+            },
+        },
+        Import {
+            module: "env",
+            name: "input",
+            ty: FuncType {
+                params: vec![],
+                results: i32s(1),
+            },
+        },
+        Import {
+            module: "env",
+            name: "error",
+            ty: FuncType {
+                params: i32s(5),
+                results: vec![],
+            },
+        },
+    ];
+
+    let functions = vec![
+        grow_function(cells),
+        move_function(cells, options.tape, limit as i32),
+        run_function(program, options, cells),
+    ];
+
+    Module {
+        imports,
+        functions,
+        globals: vec![
+            Global {
+                name: "len",
+                ty: ValType::I32,
+                init: bytes as i32,
+            },
+            Global {
+                name: "origin",
+                ty: ValType::I32,
+                init: 0,
+            },
+        ],
+        memory_pages: bytes.div_ceil(PAGE).max(1) as u32,
+        memory_export: "memory",
+    }
+}
+
+/// Builds `$grow`, which resizes the tape to a number of cells, growing the memory
+/// as needed. The new cells are zero, as the memory only ever grows.
+fn grow_function(cells: Cells) -> Function {
+    const CELLS: u32 = 0;
+    const BYTES: u32 = 1;
+    const EXTRA: u32 = 2;
+
+    use Instr::*;
+    let body = vec![
+        LocalGet(CELLS),
+        I32Const(cells.width),
+        I32Mul,
+        LocalTee(BYTES),
+        // The number of missing pages
+        I32Const(PAGE as i32 - 1),
+        I32Add,
+        I32Const(PAGE as i32),
+        I32DivU,
+        MemorySize,
+        I32Sub,
+        LocalTee(EXTRA),
+        I32Const(0),
+        I32GtS,
+        If,
+        LocalGet(EXTRA),
+        MemoryGrow,
+        I32Const(-1),
+        I32Eq,
+        If,
+        Unreachable,
+        End,
+        End,
+        LocalGet(BYTES),
+        GlobalSet(LEN),
+    ];
+
+    Function {
+        name: "grow",
+        export: None,
+        params: vec![("cells", ValType::I32)],
+        results: vec![],
+        locals: vec![("bytes", ValType::I32), ("extra", ValType::I32)],
+        body,
+    }
+}
+
+/// Builds `$move`, which takes the address of the current cell, an offset in cells
+/// and the position of the command, and returns the new address, following the tape
+/// layout.
+fn move_function(cells: Cells, tape: TapeConfig, limit: i32) -> Function {
+    const P: u32 = 0;
+    const OFFSET: u32 = 1;
+    const LINE: u32 = 2;
+    const COLUMN: u32 = 3;
+    const TARGET: u32 = 4;
+    const NEW: u32 = 5;
+    const COUNT: u32 = 6;
+    const MISSING: u32 = 7;
+    const ADDED: u32 = 8;
+
+    use Instr::*;
+    let width = cells.width;
+    let fail = |kind: i32, a: &[Instr], b: &[Instr]| {
+        let mut out = vec![I32Const(kind), LocalGet(LINE), LocalGet(COLUMN)];
+        out.extend_from_slice(a);
+        out.extend_from_slice(b);
+        out.extend([Call(ERROR), Unreachable]);
+        out
+    };
+    let magnitude = [
+        I32Const(0),
+        LocalGet(OFFSET),
+        I32Sub,
+        LocalGet(OFFSET),
+        LocalGet(OFFSET),
+        I32Const(0),
+        I32LtS,
+        Select,
+    ];
+    let index = [LocalGet(P), I32Const(width), I32DivU];
+    let count = [GlobalGet(LEN), I32Const(width), I32DivU];
+    let position: &[Instr] = match tape {
+        TapeConfig::Bidirectional { .. } => &[
+            LocalGet(P),
+            GlobalGet(ORIGIN),
+            I32Sub,
+            I32Const(width),
+            I32DivS,
+        ],
+        _ => &index,
+    };
+    let limit = match tape {
+        TapeConfig::Growing { max, .. } | TapeConfig::Bidirectional { max, .. } => {
+            max.min(limit as usize) as i32
+        }
+        _ => limit,
+    };
+    let underflow = || {
+        let mut out = vec![LocalGet(OFFSET), I32Const(0), I32LtS, If];
+        out.extend(fail(UNDERFLOW, &magnitude, position));
+        out.push(End);
+        out
+    };
+
+    // The target is within the tape most of the time
+    let mut body = vec![
+        LocalGet(P),
+        LocalGet(OFFSET),
+        I32Const(width),
+        I32Mul,
+        I32Add,
+        LocalTee(TARGET),
+        GlobalGet(LEN),
+        I32LtU,
+        If,
+        LocalGet(TARGET),
+        Return,
+        End,
+    ];
+
+    // Grows the tape to the right, geometrically and up to the limit
+    let mut grow_right = index.to_vec();
+    grow_right.extend([
+        LocalGet(OFFSET),
+        I32Add,
+        LocalTee(TARGET),
+        I32Const(limit),
+        I32GeU,
+        If,
+    ]);
+    grow_right.extend(fail(LIMIT, &[I32Const(limit)], position));
+    grow_right.push(End);
+    grow_right.extend([LocalGet(TARGET), I32Const(1), I32Add, LocalSet(NEW)]);
+    grow_right.extend(count);
+    grow_right.extend([
+        I32Const(2),
+        I32Mul,
+        LocalTee(COUNT),
+        LocalGet(NEW),
+        LocalGet(COUNT),
+        LocalGet(NEW),
+        I32GtU,
+        Select,
+        LocalSet(NEW),
+        I32Const(limit),
+        LocalGet(NEW),
+        LocalGet(NEW),
+        I32Const(limit),
+        I32GtU,
+        Select,
+        Call(GROW),
+        LocalGet(TARGET),
+        I32Const(width),
+        I32Mul,
+    ]);
+
+    match tape {
+        TapeConfig::Fixed { .. } => {
+            body.extend(underflow());
+            body.extend(fail(OVERFLOW, &magnitude, position));
+        }
+        TapeConfig::Wrapping { .. } => {
+            body.extend(index);
+            body.extend([LocalGet(OFFSET), I32Add]);
+            body.extend(count);
+            body.extend([
+                I32RemS,
+                LocalTee(TARGET),
+                I32Const(0),
+                I32LtS,
+                If,
+                LocalGet(TARGET),
+            ]);
+            body.extend(count);
+            body.extend([
+                I32Add,
+                LocalSet(TARGET),
+                End,
+                LocalGet(TARGET),
+                I32Const(width),
+                I32Mul,
+            ]);
+        }
+        TapeConfig::Growing { .. } => {
+            body.extend(underflow());
+            body.extend(grow_right);
+        }
+        TapeConfig::Bidirectional { .. } => {
+            body.extend([LocalGet(OFFSET), I32Const(0), I32LtS, If]);
+            // Grow to the left, by at least the current length
+            body.extend([I32Const(0), LocalGet(OFFSET), I32Sub]);
+            body.extend(index);
+            body.extend([I32Sub, LocalSet(MISSING)]);
+            body.extend(count);
+            body.extend([
+                LocalTee(COUNT),
+                LocalGet(MISSING),
+                I32Add,
+                I32Const(limit),
+                I32GtU,
+                If,
+            ]);
+            body.extend(fail(LIMIT, &[I32Const(limit)], position));
+            body.extend([
+                End,
+                LocalGet(MISSING),
+                LocalGet(COUNT),
+                LocalGet(MISSING),
+                LocalGet(COUNT),
+                I32GtU,
+                Select,
+                LocalSet(ADDED),
+                I32Const(limit),
+                LocalGet(COUNT),
+                I32Sub,
+                LocalTee(NEW),
+                LocalGet(ADDED),
+                LocalGet(ADDED),
+                LocalGet(NEW),
+                I32GtU,
+                Select,
+                LocalSet(ADDED),
+                LocalGet(COUNT),
+                LocalGet(ADDED),
+                I32Add,
+                Call(GROW),
+                // Shift the cells right, and clear the ones in front
+                LocalGet(ADDED),
+                I32Const(width),
+                I32Mul,
+                LocalTee(NEW),
+                I32Const(0),
+                LocalGet(COUNT),
+                I32Const(width),
+                I32Mul,
+                MemoryCopy,
+                I32Const(0),
+                I32Const(0),
+                LocalGet(NEW),
+                MemoryFill,
+                GlobalGet(ORIGIN),
+                LocalGet(NEW),
+                I32Add,
+                GlobalSet(ORIGIN),
+                LocalGet(P),
+                LocalGet(NEW),
+                I32Add,
+                LocalGet(OFFSET),
+                I32Const(width),
+                I32Mul,
+                I32Add,
+                Return,
+                End,
+            ]);
+            body.extend(grow_right);
+        }
+    }
+
+    let i32 = ValType::I32;
+    Function {
+        name: "move",
+        export: None,
+        params: vec![("p", i32), ("offset", i32), ("line", i32), ("column", i32)],
+        results: vec![i32],
+        locals: vec![
+            ("target", i32),
+            ("new", i32),
+            ("count", i32),
+            ("missing", i32),
+            ("added", i32),
+        ],
+        body,
+    }
+}
+
+/// Builds the exported `$run`, which holds the address of the current cell in `$p`.
+fn run_function(program: &[BfOp], options: &CodegenOptions, cells: Cells) -> Function {
+    const P: u32 = 0;
+    const BYTE: u32 = 1;
+    const VALUE: u32 = 2;
+    const ADDRESS: u32 = 3;
+
+    use Instr::*;
+
+    /// Moves `$p` by `offset` cells through `$move`, for the command at `line` and `column`.
+    fn move_by(offset: isize, line: i32, column: i32) -> [Instr; 6] {
+        let offset = offset.clamp(i32::MIN as isize, i32::MAX as isize) as i32;
+        [
+            LocalGet(P),
+            I32Const(offset),
+            I32Const(line),
+            I32Const(column),
+            Call(MOVE),
+            LocalSet(P),
+        ]
+    }
+
+    // Leaves the address of the cell `offset` cells away in `$address`. Outside the tape,
+    // the pointer moves there and back, so that the tape grows, wraps or fails like the
+    // move would
+    let at_offset = |offset: isize, line: i32, column: i32| {
+        let offset = offset.clamp(-(i32::MAX as isize), i32::MAX as isize);
+        // Addresses past the tape stay past it, as the tape is below 2 GiB
+        let bytes = (offset as i64 * cells.width as i64).clamp(i32::MIN as i64, i32::MAX as i64);
+        let mut out = vec![
+            LocalGet(P),
+            I32Const(bytes as i32),
+            I32Add,
+            LocalTee(ADDRESS),
+            GlobalGet(LEN),
+            I32GeU,
+            If,
+        ];
+        out.extend(move_by(offset, line, column));
+        out.extend([LocalGet(P), LocalSet(ADDRESS)]);
+        out.extend(move_by(-offset, line, column));
+        out.push(End);
+        out
+    };
+
+    let bytecode = Bytecode::compile(program);
+    let mut body = Vec::new();
+    for (instruction, span) in bytecode.instructions.iter().zip(&bytecode.spans) {
+        let (line, column) = (span.line as i32, span.column as i32);
+        match *instruction {
+            Instruction::Move(offset) => body.extend(move_by(offset, line, column)),
+            Instruction::Add(delta) => {
+                let delta = options.wrap_delta(delta) as i64;
+                let add = if cells.wide { I64Add } else { I32Add };
+                body.extend([
+                    LocalGet(P),
+                    LocalGet(P),
+                    cells.load,
+                    cells.constant(delta),
+                    add,
+                    cells.store,
+                ]);
+            }
+            Instruction::Output => body.extend([LocalGet(P), I32Load8U, Call(OUTPUT)]),
+            Instruction::Input => {
+                body.extend([Call(INPUT), LocalTee(BYTE), I32Const(0), I32GeS, If]);
+                body.extend([LocalGet(P), LocalGet(BYTE)]);
+                if cells.wide {
+                    body.push(I64ExtendI32U);
+                }
+                body.push(cells.store);
+                match options.eof_policy {
+                    EofPolicy::Unchanged => {}
+                    EofPolicy::Zero => {
+                        body.extend([Else, LocalGet(P), cells.constant(0), cells.store])
+                    }
+                    EofPolicy::MinusOne => {
+                        body.extend([Else, LocalGet(P), cells.constant(-1), cells.store])
+                    }
+                    EofPolicy::Error => body.extend([
+                        Else,
+                        I32Const(EOF),
+                        I32Const(line),
+                        I32Const(column),
+                        I32Const(0),
+                        I32Const(0),
+                        Call(ERROR),
+                        Unreachable,
+                    ]),
+                }
+                body.push(End);
+            }
+            Instruction::JumpIfZero(_) => {
+                let eqz = if cells.wide { I64Eqz } else { I32Eqz };
+                body.extend([Block, Loop, LocalGet(P), cells.load, eqz, BrIf(1)]);
+            }
+            Instruction::JumpIfNonZero(_) => body.extend([Br(0), End, End]),
+            Instruction::Clear => body.extend([LocalGet(P), cells.constant(0), cells.store]),
+            Instruction::Set(value) => {
+                let value = options.wrap_delta(value) as i64;
+                body.extend([LocalGet(P), cells.constant(value), cells.store]);
+            }
+            Instruction::MulAdd { offset, factor } => {
+                let factor = options.wrap_delta(factor) as i64;
+                let (eqz, add, mul) = if cells.wide {
+                    (I64Eqz, I64Add, I64Mul)
+                } else {
+                    (I32Eqz, I32Add, I32Mul)
+                };
+                body.extend([
+                    Block,
+                    LocalGet(P),
+                    cells.load,
+                    LocalTee(VALUE),
+                    eqz,
+                    BrIf(0),
+                ]);
+                body.extend(at_offset(offset, line, column));
+                body.extend([
+                    LocalGet(ADDRESS),
+                    LocalGet(ADDRESS),
+                    cells.load,
+                    LocalGet(VALUE),
+                    cells.constant(factor),
+                    mul,
+                    add,
+                    cells.store,
+                    End,
+                ]);
+            }
+            Instruction::Scan(stride) => {
+                let eqz = if cells.wide { I64Eqz } else { I32Eqz };
+                body.extend([Block, Loop, LocalGet(P), cells.load, eqz, BrIf(1)]);
+                body.extend(move_by(stride, line, column));
+                body.extend([Br(0), End, End]);
+            }
+            Instruction::AddAt { offset, delta } => {
+                let add = if cells.wide { I64Add } else { I32Add };
+                let delta = options.wrap_delta(delta) as i64;
+                body.extend(at_offset(offset, line, column));
+                body.extend([
+                    LocalGet(ADDRESS),
+                    LocalGet(ADDRESS),
+                    cells.load,
+                    cells.constant(delta),
+                    add,
+                    cells.store,
+                ]);
+            }
+            Instruction::OutputAt(offset) => {
+                body.extend(at_offset(offset, line, column));
+                body.extend([LocalGet(ADDRESS), I32Load8U, Call(OUTPUT)]);
+            }
+        }
+    }
+
+    Function {
+        name: "run",
+        export: Some("run"),
+        params: vec![],
+        results: vec![],
+        locals: vec![
+            ("p", ValType::I32),
+            ("byte", ValType::I32),
+            ("value", cells.value_type()),
+            ("address", ValType::I32),
+        ],
+        body,
+    }
+}
@@ -0,0 +1,7 @@
+//! The WebAssembly backend, which encodes modules by hand in either format.
+
+mod instruction;
+mod lower;
+mod module;
+
+pub use lower::{emit_wasm, emit_wat};
@@ -0,0 +1,300 @@
+//! A WebAssembly module, rendered either in the text format or in the binary format.
+
+use super::instruction::{write_u32, Instr, Names, ValType};
+use std::fmt::Write;
+
+/// The type of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FuncType {
+    pub(crate) params: Vec<ValType>,
+    pub(crate) results: Vec<ValType>,
+}
+
+/// A function imported from the host.
+pub(crate) struct Import {
+    pub(crate) module: &'static str,
+    pub(crate) name: &'static str,
+    pub(crate) ty: FuncType,
+}
+
+/// A function defined by the module.
+pub(crate) struct Function {
+    pub(crate) name: &'static str,
+    /// The name the function is exported under, if any.
+    pub(crate) export: Option<&'static str>,
+    pub(crate) params: Vec<(&'static str, ValType)>,
+    pub(crate) results: Vec<ValType>,
+    pub(crate) locals: Vec<(&'static str, ValType)>,
+    pub(crate) body: Vec<Instr>,
+}
+
+impl Function {
+    fn ty(&self) -> FuncType {
+        FuncType {
+            params: self.params.iter().map(|&(_, ty)| ty).collect(),
+            results: self.results.clone(),
+        }
+    }
+}
+
+/// A mutable global, initialized to a constant.
+pub(crate) struct Global {
+    pub(crate) name: &'static str,
+    pub(crate) ty: ValType,
+    pub(crate) init: i32,
+}
+
+/// A module with a single exported memory.
+///
+/// Imported functions come first in the function index space, followed by the
+/// defined functions, in order.
+pub(crate) struct Module {
+    pub(crate) imports: Vec<Import>,
+    pub(crate) functions: Vec<Function>,
+    pub(crate) globals: Vec<Global>,
+    /// The initial size of the memory, in 64 KiB pages.
+    pub(crate) memory_pages: u32,
+    pub(crate) memory_export: &'static str,
+}
+
+impl Module {
+    /// The distinct function types, in order of first use.
+    fn types(&self) -> Vec<FuncType> {
+        let mut types: Vec<FuncType> = Vec::new();
+        let all = self
+            .imports
+            .iter()
+            .map(|import| import.ty.clone())
+            .chain(self.functions.iter().map(Function::ty));
+        for ty in all {
+            if !types.contains(&ty) {
+                types.push(ty);
+            }
+        }
+        types
+    }
+
+    fn type_index(types: &[FuncType], ty: &FuncType) -> u32 {
+        types.iter().position(|other| other == ty).unwrap() as u32
+    }
+
+    /// Renders the module in the text format.
+    pub(crate) fn to_text(&self) -> String {
+        let functions: Vec<String> = self
+            .imports
+            .iter()
+            .map(|import| import.name.to_string())
+            .chain(
+                self.functions
+                    .iter()
+                    .map(|function| function.name.to_string()),
+            )
+            .collect();
+        let globals: Vec<String> = self
+            .globals
+            .iter()
+            .map(|global| global.name.to_string())
+            .collect();
+
+        let mut out = String::from("(module\n");
+        for import in &self.imports {
+            let _ = writeln!(
+                out,
+                "  (import \"{}\" \"{}\" (func ${}{}))",
+                import.module,
+                import.name,
+                import.name,
+                signature(&import.ty.params, &import.ty.results)
+            );
+        }
+        let _ = writeln!(
+            out,
+            "  (memory (export \"{}\") {})",
+            self.memory_export, self.memory_pages
+        );
+        for global in &self.globals {
+            let _ = writeln!(
+                out,
+                "  (global ${} (mut {}) ({}.const {}))",
+                global.name,
+                global.ty.text(),
+                global.ty.text(),
+                global.init
+            );
+        }
+
+        for function in &self.functions {
+            let locals: Vec<String> = function
+                .params
+                .iter()
+                .chain(&function.locals)
+                .map(|&(name, _)| name.to_string())
+                .collect();
+            let names = Names {
+                functions: &functions,
+                globals: &globals,
+                locals: &locals,
+            };
+
+            let _ = write!(out, "\n  (func ${}", function.name);
+            if let Some(export) = function.export {
+                let _ = write!(out, " (export \"{}\")", export);
+            }
+            for (name, ty) in &function.params {
+                let _ = write!(out, " (param ${} {})", name, ty.text());
+            }
+            out.push_str(&signature(&[], &function.results));
+            out.push('\n');
+            if !function.locals.is_empty() {
+                out.push_str("   ");
+                for (name, ty) in &function.locals {
+                    let _ = write!(out, " (local ${} {})", name, ty.text());
+                }
+                out.push('\n');
+            }
+
+            let mut depth = 2;
+            for &instr in &function.body {
+                if matches!(instr, Instr::End | Instr::Else) {
+                    depth -= 1;
+                }
+                let _ = writeln!(out, "{}{}", "  ".repeat(depth), instr.text(&names));
+                if instr.opens_block() || instr == Instr::Else {
+                    depth += 1;
+                }
+            }
+            out.push_str("  )\n");
+        }
+        out.push_str(")\n");
+        out
+    }
+
+    /// Encodes the module in the binary format.
+    pub(crate) fn to_binary(&self) -> Vec<u8> {
+        let types = self.types();
+        let mut out = b"\0asm".to_vec();
+        out.extend(1u32.to_le_bytes());
+
+        // Type section
+        section(&mut out, 1, types.len(), |out| {
+            for ty in &types {
+                out.push(0x60);
+                value_types(out, &ty.params);
+                value_types(out, &ty.results);
+            }
+        });
+
+        // Import section
+        section(&mut out, 2, self.imports.len(), |out| {
+            for import in &self.imports {
+                name(out, import.module);
+                name(out, import.name);
+                out.push(0x00);
+                write_u32(out, Self::type_index(&types, &import.ty));
+            }
+        });
+
+        // Function section
+        section(&mut out, 3, self.functions.len(), |out| {
+            for function in &self.functions {
+                write_u32(out, Self::type_index(&types, &function.ty()));
+            }
+        });
+
+        // Memory section, with a minimum and no maximum
+        section(&mut out, 5, 1, |out| {
+            out.push(0x00);
+            write_u32(out, self.memory_pages);
+        });
+
+        // Global section
+        section(&mut out, 6, self.globals.len(), |out| {
+            for global in &self.globals {
+                out.extend([global.ty.code(), 0x01]);
+                Instr::I32Const(global.init).encode(out);
+                Instr::End.encode(out);
+            }
+        });
+
+        // Export section
+        let exports: Vec<(&str, u8, u32)> = std::iter::once((self.memory_export, 0x02, 0))
+            .chain(
+                self.functions
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, function)| {
+                        let index = (self.imports.len() + i) as u32;
+                        function.export.map(|export| (export, 0x00, index))
+                    }),
+            )
+            .collect();
+        section(&mut out, 7, exports.len(), |out| {
+            for &(export, kind, index) in &exports {
+                name(out, export);
+                out.push(kind);
+                write_u32(out, index);
+            }
+        });
+
+        // Code section
+        section(&mut out, 10, self.functions.len(), |out| {
+            for function in &self.functions {
+                let mut code = Vec::new();
+                // Locals are declared in runs of the same type
+                let mut runs: Vec<(u32, ValType)> = Vec::new();
+                for &(_, ty) in &function.locals {
+                    match runs.last_mut() {
+                        Some((count, last)) if *last == ty => *count += 1,
+                        _ => runs.push((1, ty)),
+                    }
+                }
+                write_u32(&mut code, runs.len() as u32);
+                for (count, ty) in runs {
+                    write_u32(&mut code, count);
+                    code.push(ty.code());
+                }
+                for &instr in &function.body {
+                    instr.encode(&mut code);
+                }
+                Instr::End.encode(&mut code);
+
+                write_u32(out, code.len() as u32);
+                out.extend(code);
+            }
+        });
+
+        out
+    }
+}
+
+/// Renders the `(param ...)` and `(result ...)` clauses of a function type.
+fn signature(params: &[ValType], results: &[ValType]) -> String {
+    let mut out = String::new();
+    for ty in params {
+        let _ = write!(out, " (param {})", ty.text());
+    }
+    for ty in results {
+        let _ = write!(out, " (result {})", ty.text());
+    }
+    out
+}
+
+/// Appends a section with `count` entries written by `write`, prefixed by its id and size.
+fn section(out: &mut Vec<u8>, id: u8, count: usize, write: impl FnOnce(&mut Vec<u8>)) {
+    let mut contents = Vec::new();
+    write_u32(&mut contents, count as u32);
+    write(&mut contents);
+    out.push(id);
+    write_u32(out, contents.len() as u32);
+    out.extend(contents);
+}
+
+fn value_types(out: &mut Vec<u8>, types: &[ValType]) {
+    write_u32(out, types.len() as u32);
+    out.extend(types.iter().map(|ty| ty.code()));
+}
+
+fn name(out: &mut Vec<u8>, name: &str) {
+    write_u32(out, name.len() as u32);
+    out.extend(name.as_bytes());
+}
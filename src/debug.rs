@@ -5,9 +5,8 @@
 //!
 //! These utilities are available only when the `debug` feature is enabled.
 
-use crate::parser::BfOp;
-#[cfg(feature = "optimizer")]
-use crate::parser::OptimizedOp;
+use bf_rs::parser::OptimizedOp;
+use bf_rs::parser::{BfOp, BfOpKind};
 use std::collections::HashMap;
 
 pub fn print_op_stats(ops: &[BfOp]) {
@@ -62,6 +61,23 @@ pub fn print_op_stats(ops: &[BfOp]) {
             "  Clear cell: {}",
             optimized_stats.get("clear_cell").unwrap_or(&0)
         );
+        println!(
+            "  Set cell: {}",
+            optimized_stats.get("set_cell").unwrap_or(&0)
+        );
+        println!(
+            "  Multiply-add: {}",
+            optimized_stats.get("mul_add").unwrap_or(&0)
+        );
+        println!("  Scan: {}", optimized_stats.get("scan").unwrap_or(&0));
+        println!(
+            "  Offset add: {}",
+            optimized_stats.get("add_at").unwrap_or(&0)
+        );
+        println!(
+            "  Offset output: {}",
+            optimized_stats.get("output_at").unwrap_or(&0)
+        );
         let optimized_total: usize = optimized_stats.values().sum();
         println!("Total Optimized Operations: {}", optimized_total);
     } else {
@@ -75,9 +91,9 @@ fn count_ops(
     optimized_stats: &mut HashMap<&'static str, usize>,
 ) {
     for op in ops {
-        match op {
+        match &op.kind {
             // Basic BF operations
-            BfOp::PointerIncrement(n) => {
+            BfOpKind::PointerIncrement(n) => {
                 let count = n.unsigned_abs();
                 *basic_stats.entry("pointer_movement").or_insert(0) += count;
 
@@ -87,7 +103,7 @@ fn count_ops(
                     *basic_stats.entry("decrement_pointer").or_insert(0) += count;
                 }
             }
-            BfOp::Increment(n) => {
+            BfOpKind::Increment(n) => {
                 let count = n.0.unsigned_abs() as usize;
                 *basic_stats.entry("byte_operation").or_insert(0) += count;
 
@@ -97,53 +113,80 @@ fn count_ops(
                     *basic_stats.entry("decrement_byte").or_insert(0) += count;
                 }
             }
-            BfOp::OutputByte => *basic_stats.entry("output_byte").or_insert(0) += 1,
-            BfOp::InputByte => *basic_stats.entry("input_byte").or_insert(0) += 1,
-            BfOp::Loop(body) => {
+            BfOpKind::OutputByte => *basic_stats.entry("output_byte").or_insert(0) += 1,
+            BfOpKind::InputByte => *basic_stats.entry("input_byte").or_insert(0) += 1,
+            BfOpKind::Loop(body) => {
                 *basic_stats.entry("loop").or_insert(0) += 1;
                 count_ops(body, basic_stats, optimized_stats);
             }
 
             // Optimized operations
-            #[cfg(feature = "optimizer")]
-            BfOp::Optimized(opt_op) => match opt_op {
+            BfOpKind::Optimized(opt_op) => match opt_op {
                 OptimizedOp::ClearCell => {
                     *optimized_stats.entry("clear_cell").or_insert(0) += 1;
                 }
+                OptimizedOp::SetCell(_) => {
+                    *optimized_stats.entry("set_cell").or_insert(0) += 1;
+                }
+                OptimizedOp::MulAdd { .. } => {
+                    *optimized_stats.entry("mul_add").or_insert(0) += 1;
+                }
+                OptimizedOp::ScanRight(_) | OptimizedOp::ScanLeft(_) => {
+                    *optimized_stats.entry("scan").or_insert(0) += 1;
+                }
+                OptimizedOp::AddAt { .. } => {
+                    *optimized_stats.entry("add_at").or_insert(0) += 1;
+                }
+                OptimizedOp::OutputAt(_) => {
+                    *optimized_stats.entry("output_at").or_insert(0) += 1;
+                }
             },
         }
     }
 }
 
 /// Prints the program's operations to stdout with indentation.
+/// Each line is annotated with the source location of the operation.
 pub fn print_ops(ops: &[BfOp], indent_level: usize) {
     for op in ops {
-        match op {
-            BfOp::Loop(body) => {
-                println!("{}[", " ".repeat(indent_level));
+        match &op.kind {
+            BfOpKind::Loop(body) => {
+                println!("{}[ ; {}", " ".repeat(indent_level), location(op));
                 print_ops(body, indent_level + 2);
                 println!("{}]", " ".repeat(indent_level));
             }
-            _ => println!("{}{}", " ".repeat(indent_level), op),
+            _ => println!("{}{} ; {}", " ".repeat(indent_level), op, location(op)),
         }
     }
 }
 
 /// Writes the program's operations to a file with indentation.
+/// Each line is annotated with the source location of the operation.
 pub fn write_ops_to_file(
     ops: &[BfOp],
     file: &mut impl std::io::Write,
     indent_level: usize,
 ) -> std::io::Result<()> {
     for op in ops {
-        match op {
-            BfOp::Loop(body) => {
-                writeln!(file, "{}[", " ".repeat(indent_level))?;
+        match &op.kind {
+            BfOpKind::Loop(body) => {
+                writeln!(file, "{}[ ; {}", " ".repeat(indent_level), location(op))?;
                 write_ops_to_file(body, file, indent_level + 2)?;
                 writeln!(file, "{}]", " ".repeat(indent_level))?;
             }
-            _ => writeln!(file, "{}{}", " ".repeat(indent_level), op)?,
+            _ => writeln!(
+                file,
+                "{}{} ; {}",
+                " ".repeat(indent_level),
+                op,
+                location(op)
+            )?,
         }
     }
     Ok(())
 }
+
+/// Formats the source location of an operation as `line:column`.
+fn location(op: &BfOp) -> String {
+    format!("{}:{}", op.span.line, op.span.column)
+}
@@ -0,0 +1,364 @@
+//! Human-readable diagnostics for Brainfuck errors.
+//!
+//! This module turns a `BfError` into a `Diagnostic` that points at the offending
+//! source location, and renders it as a caret-underlined snippet:
+//!
+//! ```text
+//! error: unmatched loop start
+//!  --> program.bf:3:4
+//!   |
+//! 3 | +++[>+<-
+//!   |    ^ this `[` is never closed
+//!   |
+//!   = note: every `[` needs a matching `]` later in the program
+//! ```
+
+use crate::error::BfError;
+use crate::interpreter::InterpreterError;
+use crate::lexer::Span;
+use crate::parser::ParseError;
+use std::fmt::Write;
+
+/// How a diagnostic should be rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Plain text without any escape sequences.
+    Plain,
+    /// Text colored with ANSI escape sequences, for terminals.
+    Ansi,
+}
+
+/// A message attached to a region of the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    /// Primary labels mark the cause of the error, secondary labels add context.
+    pub primary: bool,
+}
+
+/// A renderable description of an error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>) -> Self {
+        Diagnostic {
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Builds a diagnostic for `error`, which was produced while processing `source`.
+    pub fn from_error(source: &str, error: &BfError) -> Self {
+        match error {
+            BfError::Parse(e) => Self::from_parse_error(source, e),
+            BfError::Runtime(e) => Self::from_interpreter_error(e),
+            BfError::Io(e) => Diagnostic::new(format!("I/O error: {}", e)),
+        }
+    }
+
+    fn from_parse_error(source: &str, error: &ParseError) -> Self {
+        match error {
+            ParseError::UnmatchedLoopStart { span } => {
+                let diagnostic = Diagnostic::new("unmatched loop start")
+                    .with_label(*span, "this `[` is never closed");
+                let diagnostic = match last_bracket_after(source, span.end, b']') {
+                    Some(partner) => diagnostic
+                        .with_secondary_label(partner, "perhaps this `]` was meant to close it"),
+                    None => diagnostic,
+                };
+                diagnostic.with_note("every `[` needs a matching `]` later in the program")
+            }
+            ParseError::UnmatchedLoopEnd { span } => {
+                let diagnostic = Diagnostic::new("unmatched loop end")
+                    .with_label(*span, "this `]` has no matching `[`");
+                let diagnostic = match last_bracket_before(source, span.start, b'[') {
+                    Some(partner) => diagnostic.with_secondary_label(
+                        partner,
+                        "perhaps this `[` was meant to be closed here",
+                    ),
+                    None => diagnostic,
+                };
+                diagnostic.with_note("every `]` needs a matching `[` earlier in the program")
+            }
+        }
+    }
+
+    fn from_interpreter_error(error: &InterpreterError) -> Self {
+        match error {
+            InterpreterError::PointerUnderflow {
+                position,
+                attempted_move,
+                span,
+            } => Diagnostic::new("pointer underflow")
+                .with_label(
+                    *span,
+                    format!(
+                        "moves the pointer {} left from cell {}",
+                        count(*attempted_move as u64, "cell"),
+                        position
+                    ),
+                )
+                .with_note("the tape starts at cell 0 and cannot grow to the left; see the `--tape` option"),
+            InterpreterError::PointerOverflow {
+                position,
+                attempted_move,
+                span,
+            } => Diagnostic::new("pointer overflow")
+                .with_label(
+                    *span,
+                    format!(
+                        "moves the pointer {} right from cell {}",
+                        count(*attempted_move as u64, "cell"),
+                        position
+                    ),
+                )
+                .with_note("the tape has a fixed size; see the `--tape` option"),
+            InterpreterError::TapeLimitExceeded {
+                position,
+                limit,
+                span,
+            } => Diagnostic::new("tape limit exceeded")
+                .with_label(
+                    *span,
+                    format!(
+                        "moves the pointer beyond {} from cell {}",
+                        count(*limit as u64, "cell"),
+                        position
+                    ),
+                )
+                .with_note("the tape cannot grow any further; see the `--tape` option"),
+            InterpreterError::UnexpectedEof { span } => Diagnostic::new("unexpected end of input")
+                .with_label(*span, "reads past the end of input")
+                .with_note("end of input is treated as an error; see the `--eof` option"),
+            InterpreterError::OutOfFuel { executed, span } => Diagnostic::new("out of fuel")
+                .with_label(*span, "execution stopped here")
+                .with_note(format!(
+                    "{} executed before the fuel ran out; see the `--fuel` option",
+                    count_executed(*executed)
+                )),
+            InterpreterError::Timeout { executed, span } => Diagnostic::new("timed out")
+                .with_label(*span, "execution stopped in this loop")
+                .with_note(format!(
+                    "{} executed before the timeout; see the `--timeout` option",
+                    count_executed(*executed)
+                )),
+            InterpreterError::InputError(e) => Diagnostic::new(format!("input error: {}", e)),
+            InterpreterError::OutputError(e) => Diagnostic::new(format!("output error: {}", e)),
+        }
+    }
+
+    /// Renders the diagnostic against `source`, which was read from `origin`.
+    pub fn render(&self, source: &str, origin: &str, style: Style) -> String {
+        let paint = Painter { style };
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "{}{}",
+            paint.error("error"),
+            paint.bold(&format!(": {}", self.message))
+        );
+
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        labels.sort_by_key(|label| (label.span.line, !label.primary));
+
+        let gutter_width = labels
+            .iter()
+            .map(|label| label.span.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let gutter = " ".repeat(gutter_width);
+
+        if let Some(primary) = labels.iter().find(|label| label.primary) {
+            let _ = writeln!(
+                out,
+                "{}{} {}:{}:{}",
+                gutter,
+                paint.gutter("-->"),
+                origin,
+                primary.span.line,
+                primary.span.column
+            );
+        }
+
+        if !labels.is_empty() {
+            let _ = writeln!(out, "{} {}", gutter, paint.gutter("|"));
+        }
+
+        let mut previous_line = None;
+        for label in &labels {
+            let line_number = label.span.line;
+            let line = source.lines().nth(line_number - 1).unwrap_or("");
+
+            if previous_line != Some(line_number) {
+                if previous_line.is_some_and(|previous| line_number > previous + 1) {
+                    let _ = writeln!(out, "{}", paint.gutter("..."));
+                }
+                let _ = writeln!(
+                    out,
+                    "{} {} {}",
+                    paint.gutter(&format!("{:>width$}", line_number, width = gutter_width)),
+                    paint.gutter("|"),
+                    line
+                );
+                previous_line = Some(line_number);
+            }
+
+            let (padding, width) = underline_geometry(line, label.span);
+            let marker = if label.primary { "^" } else { "-" }.repeat(width);
+            let underline = format!("{} {}", marker, label.message);
+            let _ = writeln!(
+                out,
+                "{} {} {}{}",
+                gutter,
+                paint.gutter("|"),
+                padding,
+                if label.primary {
+                    paint.error(&underline)
+                } else {
+                    paint.secondary(&underline)
+                }
+            );
+        }
+
+        if !labels.is_empty() && !self.notes.is_empty() {
+            let _ = writeln!(out, "{} {}", gutter, paint.gutter("|"));
+        }
+        for note in &self.notes {
+            let _ = writeln!(
+                out,
+                "{} {} {}",
+                gutter,
+                paint.gutter("="),
+                paint.bold(&format!("note: {}", note))
+            );
+        }
+
+        out
+    }
+}
+
+/// Computes the whitespace before an underline and the number of markers for `span` on `line`.
+///
+/// Tabs in the source are kept in the padding so the markers line up in any terminal.
+/// Spans covering several lines are underlined up to the end of their first line.
+fn underline_geometry(line: &str, span: Span) -> (String, usize) {
+    let before = span.column.saturating_sub(1);
+    let padding = line
+        .chars()
+        .take(before)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    let span_len = span.end - span.start;
+    let mut covered = 0;
+    let width = line
+        .chars()
+        .skip(before)
+        .take_while(|c| {
+            covered += c.len_utf8();
+            covered <= span_len
+        })
+        .count();
+    (padding, width.max(1))
+}
+
+/// Formats `number` followed by `noun`, in the plural unless `number` is 1.
+fn count(number: u64, noun: &str) -> String {
+    if number == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", number, noun)
+    }
+}
+
+/// Formats how many operations were executed, as the subject of a sentence.
+fn count_executed(executed: u64) -> String {
+    let verb = if executed == 1 { "was" } else { "were" };
+    format!("{} {}", count(executed, "operation"), verb)
+}
+
+/// Finds the last `bracket` at or after byte offset `from`.
+fn last_bracket_after(source: &str, from: usize, bracket: u8) -> Option<Span> {
+    let offset = source.as_bytes()[from..]
+        .iter()
+        .rposition(|&byte| byte == bracket)?;
+    Some(span_at(source, from + offset))
+}
+
+/// Finds the last `bracket` before byte offset `to`.
+fn last_bracket_before(source: &str, to: usize, bracket: u8) -> Option<Span> {
+    let offset = source.as_bytes()[..to]
+        .iter()
+        .rposition(|&byte| byte == bracket)?;
+    Some(span_at(source, offset))
+}
+
+/// Builds the span of the single byte at `offset`, computing its line and column.
+fn span_at(source: &str, offset: usize) -> Span {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let column = source[line_start..offset].chars().count() + 1;
+    Span::new(offset, offset + 1, line, column)
+}
+
+/// Applies the colors of a `Style` to pieces of text.
+struct Painter {
+    style: Style,
+}
+
+impl Painter {
+    fn paint(&self, code: &str, text: &str) -> String {
+        match self.style {
+            Style::Plain => text.to_string(),
+            Style::Ansi => format!("\x1b[{}m{}\x1b[0m", code, text),
+        }
+    }
+
+    fn error(&self, text: &str) -> String {
+        self.paint("1;31", text)
+    }
+
+    fn secondary(&self, text: &str) -> String {
+        self.paint("1;36", text)
+    }
+
+    fn gutter(&self, text: &str) -> String {
+        self.paint("1;34", text)
+    }
+
+    fn bold(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+}
@@ -0,0 +1,129 @@
+use crate::interpreter::InterpreterError;
+use crate::lexer::Span;
+use std::time::{Duration, Instant};
+
+/// Units of fuel spent between two checks of the clock.
+const CLOCK_CHECK_INTERVAL: u64 = 65_536;
+
+/// Bounds how long a program may run, in executed operations and in wall-clock time.
+///
+/// Folded operations are charged for every command they replace, so `+++` costs
+/// as much as three separate `+`. Each loop condition check costs one unit.
+/// Other operations from the optimizer cost one unit however many commands they stand
+/// for, except that a scan pays a tick each time it reaches the end of the allocated cells.
+#[derive(Debug, Clone)]
+pub(crate) struct Budget {
+    /// Total fuel available, or `u64::MAX` when unlimited.
+    fuel: u64,
+    /// Fuel left for the current execution.
+    remaining: u64,
+    /// Maximum wall-clock time of an execution.
+    timeout: Option<Duration>,
+    /// Instant at which the current execution times out.
+    deadline: Option<Instant>,
+    /// The clock is checked once the remaining fuel drops below this value.
+    next_clock_check: u64,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget {
+            fuel: u64::MAX,
+            remaining: u64::MAX,
+            timeout: None,
+            deadline: None,
+            next_clock_check: 0,
+        }
+    }
+}
+
+impl Budget {
+    pub(crate) fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel.unwrap_or(u64::MAX);
+        self.remaining = self.fuel;
+    }
+
+    pub(crate) fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Refills the fuel and starts the clock for a new execution.
+    pub(crate) fn start(&mut self) {
+        self.remaining = self.fuel;
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        // Without a deadline, the remaining fuel can never drop below zero
+        self.next_clock_check = match self.deadline {
+            Some(_) => self.remaining.saturating_sub(CLOCK_CHECK_INTERVAL),
+            None => 0,
+        };
+    }
+
+    /// Whether any limit is set, so executions need to be metered.
+    pub(crate) fn is_limited(&self) -> bool {
+        self.fuel != u64::MAX || self.timeout.is_some()
+    }
+
+    /// Number of operations executed so far, weighted like the fuel.
+    pub(crate) fn executed(&self) -> u64 {
+        self.fuel - self.remaining
+    }
+
+    /// Charges `weight` units of fuel for the operation at `span`.
+    /// Does nothing unless `METERED`.
+    #[inline]
+    pub(crate) fn charge<const METERED: bool>(
+        &mut self,
+        weight: u64,
+        span: Span,
+    ) -> Result<(), InterpreterError> {
+        if !METERED {
+            return Ok(());
+        }
+        if weight > self.remaining {
+            return Err(self.out_of_fuel(span));
+        }
+        self.remaining -= weight;
+        Ok(())
+    }
+
+    /// Charges one loop condition check at `span`, checking the clock from time to time.
+    /// Does nothing unless `METERED`.
+    ///
+    /// Only loops can run for an unbounded time, so checking the clock here is enough.
+    #[inline]
+    pub(crate) fn tick<const METERED: bool>(&mut self, span: Span) -> Result<(), InterpreterError> {
+        if !METERED {
+            return Ok(());
+        }
+        self.charge::<METERED>(1, span)?;
+        if self.remaining < self.next_clock_check {
+            return self.check_clock(span);
+        }
+        Ok(())
+    }
+
+    #[cold]
+    fn out_of_fuel(&mut self, span: Span) -> InterpreterError {
+        // Account for the fuel that was actually spent before running out
+        self.remaining = 0;
+        InterpreterError::OutOfFuel {
+            executed: self.executed(),
+            span,
+        }
+    }
+
+    #[cold]
+    fn check_clock(&mut self, span: Span) -> Result<(), InterpreterError> {
+        self.next_clock_check = self.remaining.saturating_sub(CLOCK_CHECK_INTERVAL);
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(InterpreterError::Timeout {
+                executed: self.executed(),
+                span,
+            });
+        }
+        Ok(())
+    }
+}
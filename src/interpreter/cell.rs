@@ -0,0 +1,105 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A memory cell type the interpreter can operate on.
+///
+/// Cells wrap around on overflow. Since the I/O operations work on bytes,
+/// wider cells follow these rules:
+/// - `.` writes the lowest 8 bits of the cell.
+/// - `,` stores the read byte zero-extended to the full cell width.
+pub trait Cell: Copy + Default + PartialEq + fmt::Debug {
+    /// The value with every bit set, which is -1 in two's complement.
+    const MAX: Self;
+
+    /// Adds a signed delta, wrapping around at the cell width.
+    ///
+    /// The delta is reduced modulo 2^`BITS` first, which matches folding
+    /// the same number of `+` or `-` commands one at a time.
+    fn wrapping_add_delta(self, delta: i64) -> Self;
+
+    /// Adds `value` times a signed `factor`, wrapping around at the cell width.
+    fn wrapping_mul_add(self, value: Self, factor: i64) -> Self;
+
+    /// Converts a byte read from input into a cell value.
+    fn from_byte(byte: u8) -> Self;
+
+    /// Converts a cell value into the byte written to output.
+    fn to_byte(self) -> u8;
+
+    fn is_zero(self) -> bool {
+        self == Self::default()
+    }
+}
+
+macro_rules! impl_cell {
+    ($($ty:ty),*) => {
+        $(
+            impl Cell for $ty {
+                const MAX: Self = <$ty>::MAX;
+
+                fn wrapping_add_delta(self, delta: i64) -> Self {
+                    self.wrapping_add(delta as $ty)
+                }
+
+                fn wrapping_mul_add(self, value: Self, factor: i64) -> Self {
+                    self.wrapping_add(value.wrapping_mul(factor as $ty))
+                }
+
+                fn from_byte(byte: u8) -> Self {
+                    byte as $ty
+                }
+
+                fn to_byte(self) -> u8 {
+                    self as u8
+                }
+            }
+        )*
+    };
+}
+
+impl_cell!(u8, u16, u32, u64);
+
+/// The cell widths that can be selected at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CellWidth {
+    #[default]
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl CellWidth {
+    /// Width of the cells in bits.
+    pub fn bits(self) -> u32 {
+        match self {
+            CellWidth::U8 => u8::BITS,
+            CellWidth::U16 => u16::BITS,
+            CellWidth::U32 => u32::BITS,
+            CellWidth::U64 => u64::BITS,
+        }
+    }
+}
+
+impl fmt::Display for CellWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.bits())
+    }
+}
+
+impl FromStr for CellWidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(CellWidth::U8),
+            "16" => Ok(CellWidth::U16),
+            "32" => Ok(CellWidth::U32),
+            "64" => Ok(CellWidth::U64),
+            _ => Err(format!(
+                "invalid cell width `{}`, expected 8, 16, 32 or 64",
+                s
+            )),
+        }
+    }
+}
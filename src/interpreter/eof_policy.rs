@@ -0,0 +1,47 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// What the `,` operation does once the input is exhausted.
+///
+/// Brainfuck implementations disagree on this, so programs written for
+/// one convention may misbehave under another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EofPolicy {
+    /// Leave the current cell unchanged.
+    #[default]
+    Unchanged,
+    /// Store 0 in the current cell.
+    Zero,
+    /// Store -1 in the current cell, which is the maximum value of the cell type.
+    MinusOne,
+    /// Stop execution with `InterpreterError::UnexpectedEof`.
+    Error,
+}
+
+impl fmt::Display for EofPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EofPolicy::Unchanged => write!(f, "unchanged"),
+            EofPolicy::Zero => write!(f, "zero"),
+            EofPolicy::MinusOne => write!(f, "minus-one"),
+            EofPolicy::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl FromStr for EofPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unchanged" => Ok(EofPolicy::Unchanged),
+            "zero" | "0" => Ok(EofPolicy::Zero),
+            "minus-one" | "-1" => Ok(EofPolicy::MinusOne),
+            "error" => Ok(EofPolicy::Error),
+            _ => Err(format!(
+                "invalid EOF policy `{}`, expected unchanged, zero, minus-one or error",
+                s
+            )),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::lexer::Span;
 use std::error::Error;
 use std::{fmt, io};
 
@@ -8,6 +9,42 @@ pub enum InterpreterError {
     PointerUnderflow {
         position: usize,
         attempted_move: usize,
+        /// Source location of the offending pointer movement
+        span: Span,
+    },
+    /// Attempted to move the pointer past the end of a fixed-size tape
+    PointerOverflow {
+        position: usize,
+        attempted_move: usize,
+        /// Source location of the offending pointer movement
+        span: Span,
+    },
+    /// Attempted to grow the tape beyond its configured maximum number of cells
+    TapeLimitExceeded {
+        /// Position of the pointer relative to the cell it started at
+        position: isize,
+        limit: usize,
+        /// Source location of the offending pointer movement
+        span: Span,
+    },
+    /// Attempted to read past the end of input with `EofPolicy::Error`
+    UnexpectedEof {
+        /// Source location of the offending `,`
+        span: Span,
+    },
+    /// Ran out of fuel before the program finished
+    OutOfFuel {
+        /// Operations executed before stopping, weighted like the fuel
+        executed: u64,
+        /// Source location of the operation that could not be paid for
+        span: Span,
+    },
+    /// Ran longer than the configured timeout
+    Timeout {
+        /// Operations executed before stopping, weighted like the fuel
+        executed: u64,
+        /// Source location of the loop that was running
+        span: Span,
     },
     /// Error while reading from input
     InputError(io::Error),
@@ -21,8 +58,30 @@ impl fmt::Display for InterpreterError {
             InterpreterError::PointerUnderflow {
                 position,
                 attempted_move,
-            } => write!(f, "Pointer underflow: attempted to move left {} steps when pointer was at position {}",
-                    attempted_move, position),
+                span,
+            } => write!(f, "Pointer underflow at {}: attempted to move left {} steps when pointer was at position {}",
+                    span, attempted_move, position),
+            InterpreterError::PointerOverflow {
+                position,
+                attempted_move,
+                span,
+            } => write!(f, "Pointer overflow at {}: attempted to move right {} steps when pointer was at position {}",
+                    span, attempted_move, position),
+            InterpreterError::TapeLimitExceeded {
+                position,
+                limit,
+                span,
+            } => write!(f, "Tape limit exceeded at {}: the tape cannot grow beyond {} cells, pointer was at position {}",
+                    span, limit, position),
+            InterpreterError::UnexpectedEof { span } => {
+                write!(f, "Unexpected end of input at {}", span)
+            }
+            InterpreterError::OutOfFuel { executed, span } => {
+                write!(f, "Out of fuel at {} after executing {} operations", span, executed)
+            }
+            InterpreterError::Timeout { executed, span } => {
+                write!(f, "Timed out at {} after executing {} operations", span, executed)
+            }
             InterpreterError::InputError(err) => write!(f, "Input error: {}", err),
             InterpreterError::OutputError(err) => write!(f, "Output error: {}", err),
         }
@@ -1,25 +1,82 @@
-use crate::interpreter::InterpreterError;
-use crate::parser::BfOp;
-#[cfg(feature = "optimizer")]
+use crate::interpreter::budget::Budget;
+use crate::interpreter::tape::Tape;
+use crate::interpreter::{Cell, EofPolicy, InterpreterError, OutputMode, TapeConfig};
+use crate::lexer::Span;
 use crate::parser::OptimizedOp;
+use crate::parser::{BfOp, BfOpKind};
 use std::io;
-use std::io::{Read, Write};
+use std::io::{BufWriter, Read, Write};
+use std::time::Duration;
 
 /// The `Interpreter` struct represents the state of the Brainfuck interpreter.
-pub struct Interpreter {
-    /// Memory used by the interpreter.
-    memory: Vec<u8>,
-    /// Pointer to the current position in the memory.
-    pointer: usize,
+///
+/// The interpreter is generic over the type of its memory cells, which defaults to `u8`.
+pub struct Interpreter<C: Cell = u8> {
+    /// Memory used by the interpreter, along with the pointer into it.
+    pub(crate) tape: Tape<C>,
+    /// What `,` does once the input is exhausted.
+    eof_policy: EofPolicy,
+    /// Limits on how long a program may run.
+    pub(crate) budget: Budget,
+    /// When the output is flushed.
+    pub(crate) output_mode: OutputMode,
 }
 
-impl Interpreter {
-    pub fn new() -> Self {
+impl<C: Cell> Default for Interpreter<C> {
+    fn default() -> Self {
         Interpreter {
-            memory: vec![0; 30_000], // Initialize memory with standard 30_000 cells
-            pointer: 0,
+            tape: Tape::new(TapeConfig::default()), // Standard 30_000 cells, growing to the right
+            eof_policy: EofPolicy::default(),
+            budget: Budget::default(),
+            output_mode: OutputMode::default(),
         }
     }
+}
+
+impl Interpreter {
+    /// Create an interpreter with standard 8-bit cells.
+    ///
+    /// Use `Interpreter::<C>::default()` for other cell types.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<C: Cell> Interpreter<C> {
+    /// Set the layout of the tape, replacing the current memory with a blank tape.
+    pub fn with_tape(mut self, config: TapeConfig) -> Self {
+        self.tape = Tape::new(config);
+        self
+    }
+
+    /// Set what `,` does once the input is exhausted.
+    pub fn with_eof_policy(mut self, eof_policy: EofPolicy) -> Self {
+        self.eof_policy = eof_policy;
+        self
+    }
+
+    /// Limit each execution to `fuel` units, or remove the limit with `None`.
+    ///
+    /// Every operation costs one unit, except folded operations which cost one unit per
+    /// command they replace, and loop condition checks which cost one unit each.
+    /// The fuel is charged for the operations of `ops` as given, so an optimized program
+    /// usually needs less: a clear or multiplication loop costs one unit in all.
+    pub fn with_fuel(mut self, fuel: Option<u64>) -> Self {
+        self.budget.set_fuel(fuel);
+        self
+    }
+
+    /// Limit the wall-clock time of each execution, or remove the limit with `None`.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.budget.set_timeout(timeout);
+        self
+    }
+
+    /// Set when the output is flushed.
+    pub fn with_output_mode(mut self, output_mode: OutputMode) -> Self {
+        self.output_mode = output_mode;
+        self
+    }
 
     pub fn run(&mut self, program: &[BfOp]) -> Result<(), InterpreterError> {
         let stdout = io::stdout();
@@ -27,71 +84,224 @@ impl Interpreter {
         let stdin = io::stdin();
         let mut stdin_handle = stdin.lock();
 
-        self.execute(program, &mut stdout_handle, &mut stdin_handle)
+        if self.output_mode == OutputMode::Unbuffered {
+            self.execute(program, &mut stdout_handle, &mut stdin_handle)
+        } else {
+            // Stdout only buffers lines by itself, so buffer it further
+            let mut buffered = BufWriter::new(stdout_handle);
+            self.execute(program, &mut buffered, &mut stdin_handle)
+        }
     }
 
+    /// Executes `ops` against the current memory, using the given I/O handles.
+    ///
+    /// # Details
+    /// Loops are executed with an explicit stack of frames instead of through recursion,
+    /// so the nesting depth is limited only by the available heap memory.
+    ///
+    /// The output is flushed according to the output mode, and always once execution ends,
+    /// even when it ends with an error.
     pub fn execute(
         &mut self,
         ops: &[BfOp],
         stdout: &mut impl Write,
         stdin: &mut impl Read,
     ) -> Result<(), InterpreterError> {
-        for op in ops {
-            match op {
-                BfOp::PointerIncrement(offset) => {
-                    if offset.is_negative() {
-                        let magnitude = offset.wrapping_abs() as usize;
-                        if self.pointer >= magnitude {
-                            self.pointer -= magnitude;
+        let result = if self.budget.is_limited() {
+            self.execute_ops::<true>(ops, stdout, stdin)
+        } else {
+            self.execute_ops::<false>(ops, stdout, stdin)
+        };
+
+        let flushed = stdout.flush().map_err(InterpreterError::OutputError);
+        result.and(flushed)
+    }
+
+    /// Executes `ops`, enforcing the fuel and timeout only when `METERED`,
+    /// so that unlimited runs do not pay for the bookkeeping.
+    fn execute_ops<const METERED: bool>(
+        &mut self,
+        ops: &[BfOp],
+        stdout: &mut impl Write,
+        stdin: &mut impl Read,
+    ) -> Result<(), InterpreterError> {
+        let mut budget = self.budget.clone();
+        budget.start();
+
+        // The sequences containing the loops being executed, with the index of each loop
+        let mut frames: Vec<(&[BfOp], usize)> = Vec::new();
+        let mut current = ops;
+        let mut index = 0;
+
+        loop {
+            let Some(op) = current.get(index) else {
+                // End of a sequence: either the program is done, or a loop body finished
+                match frames.last() {
+                    None => break,
+                    Some(&(enclosing, loop_index)) => {
+                        budget.tick::<METERED>(enclosing[loop_index].span)?;
+                        if !self.tape.get().is_zero() {
+                            index = 0;
                         } else {
-                            return Err(InterpreterError::PointerUnderflow {
-                                position: self.pointer,
-                                attempted_move: magnitude,
-                            });
-                        }
-                    } else {
-                        self.pointer += *offset as usize;
-                        while self.pointer >= self.memory.len() {
-                            self.memory.push(0);
+                            frames.pop();
+                            current = enclosing;
+                            index = loop_index + 1;
                         }
+                        continue;
                     }
                 }
-                BfOp::Increment(count) => {
-                    self.memory[self.pointer] =
-                        self.memory[self.pointer].wrapping_add_signed(count.0);
+            };
+
+            match &op.kind {
+                BfOpKind::PointerIncrement(offset) => {
+                    budget.charge::<METERED>(offset.unsigned_abs() as u64, op.span)?;
+                    self.tape.move_by(*offset, op.span)?
                 }
-                BfOp::OutputByte => {
-                    stdout
-                        .write_all(&[self.memory[self.pointer]])
-                        .map_err(|e| InterpreterError::OutputError(e))?;
-                    stdout
-                        .flush()
-                        .map_err(|e| InterpreterError::OutputError(e))?;
+                BfOpKind::Increment(count) => {
+                    budget.charge::<METERED>(count.0.unsigned_abs(), op.span)?;
+                    self.tape.set(self.tape.get().wrapping_add_delta(count.0));
                 }
-                BfOp::InputByte => {
-                    let mut buffer = [0];
-                    match stdin.read_exact(&mut buffer) {
-                        Ok(_) => self.memory[self.pointer] = buffer[0],
-                        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
-                            // Handle EOF by keeping the cell unchanged
-                        }
-                        Err(e) => return Err(InterpreterError::InputError(e)),
-                    }
+                BfOpKind::OutputByte => {
+                    budget.charge::<METERED>(1, op.span)?;
+                    self.output_byte(stdout)?;
+                }
+                BfOpKind::InputByte => {
+                    budget.charge::<METERED>(1, op.span)?;
+                    self.input_byte(stdout, stdin, op.span)?;
                 }
-                BfOp::Loop(body) => {
-                    while self.memory[self.pointer] != 0 {
-                        self.execute(body, stdout, stdin)?
+                BfOpKind::Loop(body) => {
+                    budget.tick::<METERED>(op.span)?;
+                    if !self.tape.get().is_zero() {
+                        frames.push((current, index));
+                        current = body;
+                        index = 0;
+                        continue;
                     }
                 }
-                #[cfg(feature = "optimizer")]
-                BfOp::Optimized(opt_op) => match opt_op {
+                BfOpKind::Optimized(opt_op) => match opt_op {
                     OptimizedOp::ClearCell => {
-                        self.memory[self.pointer] = 0;
+                        budget.charge::<METERED>(1, op.span)?;
+                        self.tape.set(C::default());
+                    }
+                    OptimizedOp::SetCell(value) => {
+                        budget.charge::<METERED>(1, op.span)?;
+                        self.tape.set(C::default().wrapping_add_delta(*value));
+                    }
+                    OptimizedOp::MulAdd { offset, factor } => {
+                        budget.charge::<METERED>(1, op.span)?;
+                        self.mul_add(*offset, *factor, op.span)?;
+                    }
+                    OptimizedOp::ScanRight(stride) => {
+                        self.scan::<METERED>(&mut budget, *stride as isize, op.span)?
+                    }
+                    OptimizedOp::ScanLeft(stride) => {
+                        self.scan::<METERED>(&mut budget, -(*stride as isize), op.span)?
+                    }
+                    OptimizedOp::AddAt { offset, delta } => {
+                        budget.charge::<METERED>(delta.unsigned_abs(), op.span)?;
+                        let cell = self.tape.cell_at(*offset, op.span)?;
+                        *cell = cell.wrapping_add_delta(*delta);
+                    }
+                    OptimizedOp::OutputAt(offset) => {
+                        budget.charge::<METERED>(1, op.span)?;
+                        let value = *self.tape.cell_at(*offset, op.span)?;
+                        self.output_cell(value, stdout)?;
                     }
                 },
             }
+
+            index += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the current cell times `factor` to the cell `offset` cells away, unless the
+    /// current cell is 0. The pointer moves there and back, so the tape grows, wraps or
+    /// reports errors like it would for the loop the operation replaces.
+    pub(crate) fn mul_add(
+        &mut self,
+        offset: isize,
+        factor: i64,
+        span: Span,
+    ) -> Result<(), InterpreterError> {
+        let value = self.tape.get();
+        if value.is_zero() {
+            return Ok(());
+        }
+
+        self.tape.move_by(offset, span)?;
+        self.tape
+            .set(self.tape.get().wrapping_mul_add(value, factor));
+        self.tape.move_by(-offset, span)
+    }
+
+    /// Moves the pointer by `stride` until the current cell is 0. Every time the scan
+    /// leaves the allocated cells counts as a loop iteration, so that scanning a wrapping
+    /// tape without any zero cell still runs out of fuel or time.
+    pub(crate) fn scan<const METERED: bool>(
+        &mut self,
+        budget: &mut Budget,
+        stride: isize,
+        span: Span,
+    ) -> Result<(), InterpreterError> {
+        budget.charge::<METERED>(1, span)?;
+        while !self.tape.scan(stride, span)? {
+            budget.tick::<METERED>(span)?;
         }
+        Ok(())
+    }
+
+    /// Writes the current cell to `stdout`, flushing according to the output mode.
+    pub(crate) fn output_byte(&self, stdout: &mut impl Write) -> Result<(), InterpreterError> {
+        self.output_cell(self.tape.get(), stdout)
+    }
+
+    /// Writes `value` to `stdout`, flushing according to the output mode.
+    pub(crate) fn output_cell(
+        &self,
+        value: C,
+        stdout: &mut impl Write,
+    ) -> Result<(), InterpreterError> {
+        let byte = value.to_byte();
+        stdout
+            .write_all(&[byte])
+            .map_err(InterpreterError::OutputError)?;
+
+        let flush = match self.output_mode {
+            OutputMode::Unbuffered => true,
+            OutputMode::LineBuffered => byte == b'\n',
+            OutputMode::FullyBuffered | OutputMode::FlushBeforeInput => false,
+        };
+        if flush {
+            stdout.flush().map_err(InterpreterError::OutputError)?;
+        }
+        Ok(())
+    }
 
+    /// Reads a byte from `stdin` into the current cell, applying the EOF policy.
+    /// The `span` of the reading operation is reported in errors.
+    pub(crate) fn input_byte(
+        &mut self,
+        stdout: &mut impl Write,
+        stdin: &mut impl Read,
+        span: Span,
+    ) -> Result<(), InterpreterError> {
+        if self.output_mode == OutputMode::FlushBeforeInput {
+            stdout.flush().map_err(InterpreterError::OutputError)?;
+        }
+
+        let mut buffer = [0];
+        match stdin.read_exact(&mut buffer) {
+            Ok(_) => self.tape.set(C::from_byte(buffer[0])),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => match self.eof_policy {
+                EofPolicy::Unchanged => {}
+                EofPolicy::Zero => self.tape.set(C::default()),
+                EofPolicy::MinusOne => self.tape.set(C::MAX),
+                EofPolicy::Error => return Err(InterpreterError::UnexpectedEof { span }),
+            },
+            Err(e) => return Err(InterpreterError::InputError(e)),
+        }
         Ok(())
     }
 }
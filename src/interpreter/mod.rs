@@ -2,8 +2,19 @@
 //!
 //! This module provides an interpreter for Brainfuck programs.
 
+mod budget;
+mod cell;
+mod eof_policy;
 mod error;
+#[allow(clippy::module_inception)]
 mod interpreter;
+mod output_mode;
+mod tape;
+mod vm;
 
+pub use cell::{Cell, CellWidth};
+pub use eof_policy::EofPolicy;
 pub use error::InterpreterError;
 pub use interpreter::Interpreter;
+pub use output_mode::OutputMode;
+pub use tape::TapeConfig;
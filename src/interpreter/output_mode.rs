@@ -0,0 +1,47 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// When the output written by `.` is flushed.
+///
+/// Flushing after every byte makes output-heavy programs much slower,
+/// while buffering too much can hide prompts from interactive programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// Flush after every byte.
+    #[default]
+    Unbuffered,
+    /// Flush after every newline.
+    LineBuffered,
+    /// Flush only when the buffer is full and when execution ends.
+    FullyBuffered,
+    /// Flush before every `,` and when execution ends, so prompts are shown before reading.
+    FlushBeforeInput,
+}
+
+impl fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputMode::Unbuffered => write!(f, "unbuffered"),
+            OutputMode::LineBuffered => write!(f, "line"),
+            OutputMode::FullyBuffered => write!(f, "full"),
+            OutputMode::FlushBeforeInput => write!(f, "before-input"),
+        }
+    }
+}
+
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unbuffered" => Ok(OutputMode::Unbuffered),
+            "line" => Ok(OutputMode::LineBuffered),
+            "full" => Ok(OutputMode::FullyBuffered),
+            "before-input" => Ok(OutputMode::FlushBeforeInput),
+            _ => Err(format!(
+                "invalid output mode `{}`, expected unbuffered, line, full or before-input",
+                s
+            )),
+        }
+    }
+}
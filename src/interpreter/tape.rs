@@ -0,0 +1,341 @@
+use crate::interpreter::{Cell, InterpreterError};
+use crate::lexer::Span;
+use std::fmt;
+use std::str::FromStr;
+
+/// The standard number of cells of a Brainfuck tape.
+const DEFAULT_CELLS: usize = 30_000;
+
+/// How the interpreter's tape is laid out and how it reacts to the pointer leaving it.
+///
+/// Limits are expressed in cells, so the memory used by a tape also depends on the cell width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapeConfig {
+    /// A tape of exactly `cells` cells. Moving past either end is an error.
+    Fixed { cells: usize },
+    /// A tape of `initial` cells that grows to the right, up to `max` cells.
+    /// Moving left of the first cell is an error.
+    Growing { initial: usize, max: usize },
+    /// A tape of `cells` cells where moving past either end wraps around to the other.
+    Wrapping { cells: usize },
+    /// A tape of `initial` cells that grows in both directions, up to `max` cells in total.
+    Bidirectional { initial: usize, max: usize },
+}
+
+impl Default for TapeConfig {
+    fn default() -> Self {
+        TapeConfig::Growing {
+            initial: DEFAULT_CELLS,
+            max: usize::MAX,
+        }
+    }
+}
+
+impl fmt::Display for TapeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TapeConfig::Fixed { cells } => write!(f, "fixed:{}", cells),
+            TapeConfig::Growing { max, .. } if *max == usize::MAX => write!(f, "growing"),
+            TapeConfig::Growing { max, .. } => write!(f, "growing:{}", max),
+            TapeConfig::Wrapping { cells } => write!(f, "wrap:{}", cells),
+            TapeConfig::Bidirectional { max, .. } if *max == usize::MAX => {
+                write!(f, "bidirectional")
+            }
+            TapeConfig::Bidirectional { max, .. } => write!(f, "bidirectional:{}", max),
+        }
+    }
+}
+
+impl FromStr for TapeConfig {
+    type Err = String;
+
+    /// Parses `fixed:<cells>`, `wrap:<cells>`, `growing[:<max>]` or `bidirectional[:<max>]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, size) = match s.split_once(':') {
+            Some((kind, size)) => {
+                let size = size
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&size| size > 0)
+                    .ok_or_else(|| format!("invalid tape size `{}`", size))?;
+                (kind, Some(size))
+            }
+            None => (s, None),
+        };
+
+        match (kind, size) {
+            ("fixed", Some(cells)) => Ok(TapeConfig::Fixed { cells }),
+            ("wrap", Some(cells)) => Ok(TapeConfig::Wrapping { cells }),
+            ("growing", max) => {
+                let max = max.unwrap_or(usize::MAX);
+                Ok(TapeConfig::Growing {
+                    initial: DEFAULT_CELLS.min(max),
+                    max,
+                })
+            }
+            ("bidirectional", max) => {
+                let max = max.unwrap_or(usize::MAX);
+                Ok(TapeConfig::Bidirectional {
+                    initial: DEFAULT_CELLS.min(max),
+                    max,
+                })
+            }
+            _ => Err(format!(
+                "invalid tape `{}`, expected fixed:<cells>, wrap:<cells>, growing[:<max>] or bidirectional[:<max>]",
+                s
+            )),
+        }
+    }
+}
+
+/// Number of cells checked at once when scanning for a zero cell. Checking a whole chunk
+/// without stopping early lets the compiler vectorize the comparisons, like `memchr`.
+const SCAN_CHUNK: usize = 32;
+
+/// The memory of the interpreter, along with the pointer into it.
+pub(crate) struct Tape<C: Cell> {
+    /// The cells currently allocated.
+    cells: Vec<C>,
+    /// Index of the current cell in `cells`.
+    pointer: usize,
+    /// Index in `cells` of the cell the pointer started at.
+    /// Only bidirectional tapes move it, when growing to the left.
+    origin: usize,
+    config: TapeConfig,
+}
+
+impl<C: Cell> Tape<C> {
+    pub(crate) fn new(config: TapeConfig) -> Self {
+        let len = match config {
+            TapeConfig::Fixed { cells } | TapeConfig::Wrapping { cells } => cells,
+            TapeConfig::Growing { initial, .. } | TapeConfig::Bidirectional { initial, .. } => {
+                initial
+            }
+        };
+
+        Tape {
+            cells: vec![C::default(); len.max(1)],
+            pointer: 0,
+            origin: 0,
+            config,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get(&self) -> C {
+        self.cells[self.pointer]
+    }
+
+    #[inline]
+    pub(crate) fn set(&mut self, value: C) {
+        self.cells[self.pointer] = value;
+    }
+
+    /// Position of the pointer relative to the cell it started at.
+    fn position(&self) -> isize {
+        self.pointer as isize - self.origin as isize
+    }
+
+    /// Returns the address of the first cell, the pointer and the number of cells,
+    /// for native code that moves the pointer by itself within the allocated cells.
+    #[cfg(feature = "jit")]
+    pub(crate) fn raw_parts(&mut self) -> (*mut C, usize, usize) {
+        (self.cells.as_mut_ptr(), self.pointer, self.cells.len())
+    }
+
+    /// Moves the pointer to `pointer`, which must be within the allocated cells.
+    #[cfg(feature = "jit")]
+    pub(crate) fn set_pointer(&mut self, pointer: usize) {
+        debug_assert!(pointer < self.cells.len(), "Pointer outside the tape");
+        self.pointer = pointer;
+    }
+
+    /// Moves the pointer by `offset` cells, growing or wrapping the tape as configured.
+    /// The `span` of the moving operation is reported in errors.
+    #[inline]
+    pub(crate) fn move_by(&mut self, offset: isize, span: Span) -> Result<(), InterpreterError> {
+        // Moving left past the first cell wraps to a huge index, so a single check
+        // covers every move that stays within the allocated cells
+        let target = self.pointer.wrapping_add_signed(offset);
+        if target < self.cells.len() {
+            self.pointer = target;
+            return Ok(());
+        }
+
+        self.move_out_of_bounds(offset, span)
+    }
+
+    /// Returns the cell `offset` cells away from the current one, without moving the pointer.
+    ///
+    /// When that cell is outside the allocated cells, the pointer moves there and back,
+    /// so that the tape grows, wraps or fails at `span` like the move would.
+    pub(crate) fn cell_at(
+        &mut self,
+        offset: isize,
+        span: Span,
+    ) -> Result<&mut C, InterpreterError> {
+        let target = self.pointer.wrapping_add_signed(offset);
+        if target < self.cells.len() {
+            return Ok(&mut self.cells[target]);
+        }
+
+        // Moving back never fails, as it returns to an allocated cell
+        self.move_out_of_bounds(offset, span)?;
+        let target = self.pointer;
+        self.move_by(-offset, span)?;
+        Ok(&mut self.cells[target])
+    }
+
+    /// Moves the pointer by `stride` until the current cell is 0, like `[>]` or `[<<]`.
+    ///
+    /// Returns `false` when the scan left the allocated cells, after the move that grew
+    /// or wrapped the tape, so that callers can check their budget before scanning on.
+    /// Leaving the tape otherwise fails like the loop's move would, at `span`.
+    pub(crate) fn scan(&mut self, stride: isize, span: Span) -> Result<bool, InterpreterError> {
+        let step = stride.unsigned_abs();
+        if stride > 0 {
+            let cells = &self.cells[self.pointer..];
+            let found = if step == 1 {
+                find_zero(cells)
+            } else {
+                cells.iter().step_by(step).position(|cell| cell.is_zero())
+            };
+            if let Some(steps) = found {
+                self.pointer += steps * step;
+                return Ok(true);
+            }
+            // Stop on the last cell within the tape, and leave it like the loop would
+            self.pointer += (cells.len() - 1) / step * step;
+        } else {
+            let cells = &self.cells[..=self.pointer];
+            let found = if step == 1 {
+                rfind_zero(cells).map(|index| self.pointer - index)
+            } else {
+                cells
+                    .iter()
+                    .rev()
+                    .step_by(step)
+                    .position(|cell| cell.is_zero())
+            };
+            if let Some(steps) = found {
+                self.pointer -= steps * step;
+                return Ok(true);
+            }
+            self.pointer -= self.pointer / step * step;
+        }
+
+        self.move_by(stride, span)?;
+        Ok(false)
+    }
+
+    /// Handles a move that leaves the allocated cells.
+    #[cold]
+    fn move_out_of_bounds(&mut self, offset: isize, span: Span) -> Result<(), InterpreterError> {
+        let len = self.cells.len();
+
+        if let TapeConfig::Wrapping { .. } = self.config {
+            let target = (self.pointer as i128 + offset as i128).rem_euclid(len as i128);
+            self.pointer = target as usize;
+            return Ok(());
+        }
+
+        if offset < 0 {
+            let magnitude = offset.unsigned_abs();
+            match self.config {
+                TapeConfig::Bidirectional { max, .. } => {
+                    let missing = magnitude - self.pointer;
+                    self.grow_left(missing, max, span)?;
+                    self.pointer -= magnitude;
+                    Ok(())
+                }
+                _ => Err(InterpreterError::PointerUnderflow {
+                    position: self.pointer,
+                    attempted_move: magnitude,
+                    span,
+                }),
+            }
+        } else {
+            let magnitude = offset as usize;
+            let target = self.pointer.saturating_add(magnitude);
+            match self.config {
+                TapeConfig::Growing { max, .. } | TapeConfig::Bidirectional { max, .. } => {
+                    if target >= max {
+                        return Err(self.limit_exceeded(max, span));
+                    }
+                    // Grow geometrically, so walking right one cell at a time stays cheap
+                    let new_len = target.saturating_add(1).max(len.saturating_mul(2)).min(max);
+                    self.cells.resize(new_len, C::default());
+                    self.pointer = target;
+                    Ok(())
+                }
+                _ => Err(InterpreterError::PointerOverflow {
+                    position: self.pointer,
+                    attempted_move: magnitude,
+                    span,
+                }),
+            }
+        }
+    }
+
+    /// Adds at least `missing` cells before the first one, without exceeding `max` cells.
+    fn grow_left(
+        &mut self,
+        missing: usize,
+        max: usize,
+        span: Span,
+    ) -> Result<(), InterpreterError> {
+        let len = self.cells.len();
+        if len.saturating_add(missing) > max {
+            return Err(self.limit_exceeded(max, span));
+        }
+
+        let added = missing.max(len).min(max - len);
+        let mut cells = vec![C::default(); added];
+        cells.append(&mut self.cells);
+        self.cells = cells;
+        self.pointer += added;
+        self.origin += added;
+        Ok(())
+    }
+
+    fn limit_exceeded(&self, limit: usize, span: Span) -> InterpreterError {
+        InterpreterError::TapeLimitExceeded {
+            position: self.position(),
+            limit,
+            span,
+        }
+    }
+}
+
+/// Whether any of `cells` is 0, checking all of them.
+#[inline]
+fn any_zero<C: Cell>(cells: &[C]) -> bool {
+    cells
+        .iter()
+        .fold(false, |found, cell| found | cell.is_zero())
+}
+
+/// Index of the first zero cell, searching a chunk at a time.
+fn find_zero<C: Cell>(cells: &[C]) -> Option<usize> {
+    let mut start = 0;
+    for chunk in cells.chunks_exact(SCAN_CHUNK) {
+        if any_zero(chunk) {
+            break;
+        }
+        start += SCAN_CHUNK;
+    }
+    let index = cells[start..].iter().position(|cell| cell.is_zero())?;
+    Some(start + index)
+}
+
+/// Index of the last zero cell, searching a chunk at a time from the end.
+fn rfind_zero<C: Cell>(cells: &[C]) -> Option<usize> {
+    let mut end = cells.len();
+    for chunk in cells.rchunks_exact(SCAN_CHUNK) {
+        if any_zero(chunk) {
+            break;
+        }
+        end -= SCAN_CHUNK;
+    }
+    cells[..end].iter().rposition(|cell| cell.is_zero())
+}
@@ -0,0 +1,120 @@
+use crate::bytecode::{Bytecode, Instruction};
+use crate::interpreter::{Cell, Interpreter, InterpreterError, OutputMode};
+use std::io;
+use std::io::{BufWriter, Read, Write};
+
+impl<C: Cell> Interpreter<C> {
+    /// Runs `program` against the standard input and output, like `run` does.
+    pub fn run_bytecode(&mut self, program: &Bytecode) -> Result<(), InterpreterError> {
+        let stdout = io::stdout();
+        let mut stdout_handle = stdout.lock();
+        let stdin = io::stdin();
+        let mut stdin_handle = stdin.lock();
+
+        if self.output_mode == OutputMode::Unbuffered {
+            self.execute_bytecode(program, &mut stdout_handle, &mut stdin_handle)
+        } else {
+            let mut buffered = BufWriter::new(stdout_handle);
+            self.execute_bytecode(program, &mut buffered, &mut stdin_handle)
+        }
+    }
+
+    /// Executes `program` against the current memory, using the given I/O handles.
+    ///
+    /// This behaves exactly like `execute` on the program the bytecode was compiled from,
+    /// including the fuel charged, but runs in a single dispatch loop over flat instructions.
+    pub fn execute_bytecode(
+        &mut self,
+        program: &Bytecode,
+        stdout: &mut impl Write,
+        stdin: &mut impl Read,
+    ) -> Result<(), InterpreterError> {
+        let result = if self.budget.is_limited() {
+            self.dispatch::<true>(program, stdout, stdin)
+        } else {
+            self.dispatch::<false>(program, stdout, stdin)
+        };
+
+        let flushed = stdout.flush().map_err(InterpreterError::OutputError);
+        result.and(flushed)
+    }
+
+    /// Runs the dispatch loop, enforcing the fuel and timeout only when `METERED`.
+    fn dispatch<const METERED: bool>(
+        &mut self,
+        program: &Bytecode,
+        stdout: &mut impl Write,
+        stdin: &mut impl Read,
+    ) -> Result<(), InterpreterError> {
+        let mut budget = self.budget.clone();
+        budget.start();
+
+        let instructions = &program.instructions;
+        let spans = &program.spans;
+        let mut pc = 0;
+
+        while let Some(instruction) = instructions.get(pc) {
+            match *instruction {
+                Instruction::Move(offset) => {
+                    budget.charge::<METERED>(offset.unsigned_abs() as u64, spans[pc])?;
+                    self.tape.move_by(offset, spans[pc])?;
+                }
+                Instruction::Add(delta) => {
+                    budget.charge::<METERED>(delta.unsigned_abs(), spans[pc])?;
+                    self.tape.set(self.tape.get().wrapping_add_delta(delta));
+                }
+                Instruction::Output => {
+                    budget.charge::<METERED>(1, spans[pc])?;
+                    self.output_byte(stdout)?;
+                }
+                Instruction::Input => {
+                    budget.charge::<METERED>(1, spans[pc])?;
+                    self.input_byte(stdout, stdin, spans[pc])?;
+                }
+                Instruction::JumpIfZero(target) => {
+                    budget.tick::<METERED>(spans[pc])?;
+                    if self.tape.get().is_zero() {
+                        pc = target;
+                        continue;
+                    }
+                }
+                Instruction::JumpIfNonZero(target) => {
+                    budget.tick::<METERED>(spans[pc])?;
+                    if !self.tape.get().is_zero() {
+                        pc = target;
+                        continue;
+                    }
+                }
+                Instruction::Clear => {
+                    budget.charge::<METERED>(1, spans[pc])?;
+                    self.tape.set(C::default());
+                }
+                Instruction::Set(value) => {
+                    budget.charge::<METERED>(1, spans[pc])?;
+                    self.tape.set(C::default().wrapping_add_delta(value));
+                }
+                Instruction::MulAdd { offset, factor } => {
+                    budget.charge::<METERED>(1, spans[pc])?;
+                    self.mul_add(offset, factor, spans[pc])?;
+                }
+                Instruction::Scan(stride) => {
+                    self.scan::<METERED>(&mut budget, stride, spans[pc])?;
+                }
+                Instruction::AddAt { offset, delta } => {
+                    budget.charge::<METERED>(delta.unsigned_abs(), spans[pc])?;
+                    let cell = self.tape.cell_at(offset, spans[pc])?;
+                    *cell = cell.wrapping_add_delta(delta);
+                }
+                Instruction::OutputAt(offset) => {
+                    budget.charge::<METERED>(1, spans[pc])?;
+                    let value = *self.tape.cell_at(offset, spans[pc])?;
+                    self.output_cell(value, stdout)?;
+                }
+            }
+
+            pc += 1;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use crate::bytecode::{Bytecode, Instruction};
+use crate::x86::{Assembler, Condition, Label, Reg, CELLS, POINTER};
+
+/// Offsets of the fields of the runtime context that the native code reads and writes.
+pub(crate) const BASE: i32 = 0;
+pub(crate) const INDEX: i32 = 8;
+pub(crate) const LEN: i32 = 16;
+pub(crate) const MOVE_POINTER: i32 = 24;
+pub(crate) const OUTPUT: i32 = 32;
+pub(crate) const INPUT: i32 = 40;
+
+/// The register holding the address of the runtime context.
+const CONTEXT: Reg = Reg::Rbx;
+/// The register holding the number of allocated cells.
+const CELL_COUNT: Reg = Reg::R14;
+
+/// Compiles `bytecode` to a native function operating on cells of `width` bytes.
+///
+/// # Details
+/// The function follows the System V calling convention and takes the address of the
+/// runtime context as its only argument. It returns zero once the program ends, or the
+/// non-zero status of the first callback that failed.
+///
+/// The current cell is kept as an index into the cells, so moves only call back into
+/// Rust when they leave the allocated cells. Since callbacks may grow the tape, the
+/// cells are reloaded from the context after every callback.
+pub(crate) fn compile(bytecode: &Bytecode, width: u8) -> Vec<u8> {
+    let mut asm = Assembler::new();
+    let count = bytecode.instructions.len();
+    // One label per instruction, plus one for the end of the program
+    let labels: Vec<_> = (0..=count).map(|_| asm.new_label()).collect();
+    let exit = asm.new_label();
+    // Moves that leave the allocated cells, handled out of line
+    let mut slow_moves = Vec::new();
+    let mut slow_mul_adds = Vec::new();
+    let mut slow_offset_ops = Vec::new();
+
+    // Save the callee-saved registers, keeping the stack aligned to 16 bytes for calls
+    for reg in [CONTEXT, CELLS, POINTER, CELL_COUNT] {
+        asm.push(reg);
+    }
+    asm.sub_imm(Reg::Rsp, 8);
+    asm.mov(CONTEXT, Reg::Rdi);
+    reload(&mut asm);
+
+    for (pc, instruction) in bytecode.instructions.iter().enumerate() {
+        asm.bind(labels[pc]);
+        match *instruction {
+            Instruction::Move(offset) => {
+                let slow = asm.new_label();
+                let resume = asm.new_label();
+                move_within_cells(&mut asm, offset, slow);
+                asm.bind(resume);
+                slow_moves.push((slow, resume, pc, offset));
+            }
+            Instruction::Add(delta) => asm.add_cell(width, 0, delta),
+            Instruction::Output => call_back(&mut asm, OUTPUT, pc, None, exit),
+            Instruction::Input => call_back(&mut asm, INPUT, pc, None, exit),
+            Instruction::JumpIfZero(target) => {
+                asm.cmp_cell_zero(width, 0);
+                asm.jcc(Condition::Equal, labels[target]);
+            }
+            Instruction::JumpIfNonZero(target) => {
+                asm.cmp_cell_zero(width, 0);
+                asm.jcc(Condition::NotEqual, labels[target]);
+            }
+            Instruction::Clear => asm.set_cell(width, 0, 0),
+            Instruction::Set(value) => asm.set_cell(width, 0, value),
+            Instruction::MulAdd { offset, factor } => {
+                let slow = asm.new_label();
+                let resume = asm.new_label();
+                asm.cmp_cell_zero(width, 0);
+                asm.jcc(Condition::Equal, resume);
+                // Offsets too large to address directly always take the slow path
+                let disp = offset.checked_mul(width as isize);
+                match disp.and_then(|disp| i32::try_from(disp).ok()) {
+                    Some(_) => {
+                        let offset = offset as i32;
+                        asm.lea(Reg::Rax, POINTER, offset);
+                        asm.cmp(Reg::Rax, CELL_COUNT);
+                        asm.jcc(Condition::AboveOrEqual, slow);
+                        asm.mul_cell(width, 0, factor);
+                        asm.add_cell_reg(width, offset, Reg::Rax);
+                    }
+                    None => asm.jmp(slow),
+                }
+                asm.bind(resume);
+                slow_mul_adds.push((slow, resume, pc, offset, factor));
+            }
+            Instruction::Scan(stride) => {
+                // Moves that leave the allocated cells resume at the next check
+                let slow = asm.new_label();
+                let check = asm.new_label();
+                let done = asm.new_label();
+                asm.bind(check);
+                asm.cmp_cell_zero(width, 0);
+                asm.jcc(Condition::Equal, done);
+                move_within_cells(&mut asm, stride, slow);
+                asm.jmp(check);
+                asm.bind(done);
+                slow_moves.push((slow, check, pc, stride));
+            }
+            Instruction::AddAt { offset, .. } | Instruction::OutputAt(offset) => {
+                let slow = asm.new_label();
+                let resume = asm.new_label();
+                let disp = offset.checked_mul(width as isize);
+                match disp.and_then(|disp| i32::try_from(disp).ok()) {
+                    Some(_) => {
+                        let offset = offset as i32;
+                        asm.lea(Reg::Rax, POINTER, offset);
+                        asm.cmp(Reg::Rax, CELL_COUNT);
+                        asm.jcc(Condition::AboveOrEqual, slow);
+                        match *instruction {
+                            Instruction::AddAt { delta, .. } => asm.add_cell(width, offset, delta),
+                            // The output callback prints the current cell, so point at
+                            // the target cell for the call
+                            _ => {
+                                asm.mov(POINTER, Reg::Rax);
+                                call_back(&mut asm, OUTPUT, pc, None, exit);
+                                asm.lea(POINTER, POINTER, -offset);
+                            }
+                        }
+                    }
+                    None => asm.jmp(slow),
+                }
+                asm.bind(resume);
+                slow_offset_ops.push((slow, resume, pc, offset, *instruction));
+            }
+        }
+    }
+
+    // Hand the final position of the pointer back, and return successfully
+    asm.bind(labels[count]);
+    asm.store(CONTEXT, INDEX, POINTER);
+    asm.xor(Reg::Rax, Reg::Rax);
+
+    asm.bind(exit);
+    asm.add_imm(Reg::Rsp, 8);
+    for reg in [CELL_COUNT, POINTER, CELLS, CONTEXT] {
+        asm.pop(reg);
+    }
+    asm.ret();
+
+    for (slow, resume, pc, offset) in slow_moves {
+        asm.bind(slow);
+        call_back(&mut asm, MOVE_POINTER, pc, Some(offset), exit);
+        asm.jmp(resume);
+    }
+
+    // Move to the target cell and back through the callbacks, keeping the product in
+    // the stack slot reserved for alignment
+    for (slow, resume, pc, offset, factor) in slow_mul_adds {
+        asm.bind(slow);
+        asm.mul_cell(width, 0, factor);
+        asm.store(Reg::Rsp, 0, Reg::Rax);
+        call_back(&mut asm, MOVE_POINTER, pc, Some(offset), exit);
+        asm.load(Reg::Rax, Reg::Rsp, 0);
+        asm.add_cell_reg(width, 0, Reg::Rax);
+        call_back(&mut asm, MOVE_POINTER, pc, Some(-offset), exit);
+        asm.jmp(resume);
+    }
+
+    // Move to the target cell and back through the callbacks
+    for (slow, resume, pc, offset, instruction) in slow_offset_ops {
+        asm.bind(slow);
+        call_back(&mut asm, MOVE_POINTER, pc, Some(offset), exit);
+        match instruction {
+            Instruction::AddAt { delta, .. } => asm.add_cell(width, 0, delta),
+            _ => call_back(&mut asm, OUTPUT, pc, None, exit),
+        }
+        call_back(&mut asm, MOVE_POINTER, pc, Some(-offset), exit);
+        asm.jmp(resume);
+    }
+
+    asm.finish()
+}
+
+/// Moves the pointer by `offset`, jumping to `slow` instead when the move leaves the
+/// allocated cells.
+fn move_within_cells(asm: &mut Assembler, offset: isize, slow: Label) {
+    // A move past the first cell wraps to a huge index, so a single unsigned comparison
+    // covers both ends of the tape
+    match i32::try_from(offset) {
+        Ok(disp) => asm.lea(Reg::Rax, POINTER, disp),
+        Err(_) => {
+            asm.mov_imm(Reg::Rax, offset as i64);
+            asm.add(Reg::Rax, POINTER);
+        }
+    }
+    asm.cmp(Reg::Rax, CELL_COUNT);
+    asm.jcc(Condition::AboveOrEqual, slow);
+    asm.mov(POINTER, Reg::Rax);
+}
+
+/// Calls the callback stored at `callback` in the context, for the instruction at `pc`.
+/// Jumps to `exit` when the callback fails, and reloads the tape otherwise.
+fn call_back(asm: &mut Assembler, callback: i32, pc: usize, offset: Option<isize>, exit: Label) {
+    asm.store(CONTEXT, INDEX, POINTER);
+    asm.mov(Reg::Rdi, CONTEXT);
+    asm.mov_imm(Reg::Rsi, pc as i64);
+    if let Some(offset) = offset {
+        asm.mov_imm(Reg::Rdx, offset as i64);
+    }
+    asm.call_indirect(CONTEXT, callback);
+    asm.test(Reg::Rax, Reg::Rax);
+    asm.jcc(Condition::NotEqual, exit);
+    reload(asm);
+}
+
+/// Loads the cells, the pointer and the number of cells from the context.
+fn reload(asm: &mut Assembler) {
+    asm.load(CELLS, CONTEXT, BASE);
+    asm.load(POINTER, CONTEXT, INDEX);
+    asm.load(CELL_COUNT, CONTEXT, LEN);
+}
@@ -0,0 +1,80 @@
+use std::ffi::{c_int, c_void};
+use std::io;
+use std::ptr;
+
+const PROT_READ: c_int = 0x1;
+const PROT_WRITE: c_int = 0x2;
+const PROT_EXEC: c_int = 0x4;
+const MAP_PRIVATE: c_int = 0x02;
+const MAP_ANONYMOUS: c_int = 0x20;
+const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+extern "C" {
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: i64,
+    ) -> *mut c_void;
+    fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+/// Machine code in pages that are executable, but never writable at the same time.
+pub(crate) struct ExecutableMemory {
+    pointer: *mut c_void,
+    len: usize,
+}
+
+impl ExecutableMemory {
+    /// Maps fresh pages, copies `code` into them and makes them executable.
+    pub(crate) fn new(code: &[u8]) -> io::Result<Self> {
+        let len = code.len().max(1);
+
+        // SAFETY: Mapping fresh anonymous pages does not touch any existing memory
+        let pointer = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if pointer == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let memory = ExecutableMemory { pointer, len };
+
+        // SAFETY: The pages were just mapped writable and hold at least `code.len()` bytes
+        unsafe {
+            ptr::copy_nonoverlapping(code.as_ptr(), pointer.cast::<u8>(), code.len());
+            if mprotect(pointer, len, PROT_READ | PROT_EXEC) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(memory)
+    }
+
+    /// Address of the first byte of code.
+    pub(crate) fn as_ptr(&self) -> *const u8 {
+        self.pointer.cast::<u8>()
+    }
+}
+
+// SAFETY: The pages are never written once they are executable
+unsafe impl Send for ExecutableMemory {}
+unsafe impl Sync for ExecutableMemory {}
+
+impl Drop for ExecutableMemory {
+    fn drop(&mut self) {
+        // SAFETY: The pages were mapped by `new` and are not referenced anymore
+        unsafe {
+            munmap(self.pointer, self.len);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+#![cfg(feature = "jit")]
+
+//! Brainfuck JIT compiler.
+//!
+//! This module compiles Brainfuck programs to native x86-64 machine code, which runs
+//! directly on the interpreter's tape. The native code only calls back into Rust for
+//! `.` and `,`, and when the pointer leaves the allocated cells, so every tape layout,
+//! EOF policy and output mode behaves exactly as in the interpreter.
+//!
+//! On other architectures and operating systems, and for executions with a fuel or time
+//! limit, programs run on the bytecode VM instead.
+//!
+//! This module is available only when the `jit` feature is enabled.
+
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+mod compiler;
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+mod memory;
+mod program;
+mod runtime;
+
+pub use program::JitProgram;
@@ -0,0 +1,41 @@
+use crate::bytecode::Bytecode;
+use crate::interpreter::Cell;
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+use crate::jit::{compiler, memory::ExecutableMemory};
+use crate::parser::BfOp;
+use std::io;
+use std::marker::PhantomData;
+
+/// A program compiled for `Interpreter::execute_jit`, specialized for cells of type `C`.
+pub struct JitProgram<C: Cell> {
+    /// The program as bytecode, which maps instructions back to the source,
+    /// and runs the program where native code is not available.
+    pub(crate) bytecode: Bytecode,
+    /// The native code of the program.
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    pub(crate) code: ExecutableMemory,
+    cell: PhantomData<C>,
+}
+
+impl<C: Cell> JitProgram<C> {
+    /// Compiles a program, optimized or not, to native code.
+    ///
+    /// Fails only when executable memory cannot be allocated.
+    pub fn compile(ops: &[BfOp]) -> io::Result<Self> {
+        let bytecode = Bytecode::compile(ops);
+        #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+        let code = ExecutableMemory::new(&compiler::compile(&bytecode, size_of::<C>() as u8))?;
+
+        Ok(JitProgram {
+            bytecode,
+            #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+            code,
+            cell: PhantomData,
+        })
+    }
+
+    /// Whether the program runs as native code on this platform.
+    pub fn is_native(&self) -> bool {
+        cfg!(all(target_arch = "x86_64", target_os = "linux"))
+    }
+}
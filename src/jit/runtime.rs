@@ -0,0 +1,189 @@
+use crate::interpreter::{Cell, Interpreter, InterpreterError, OutputMode};
+use crate::jit::JitProgram;
+use std::io;
+use std::io::{BufWriter, Read, Write};
+
+impl<C: Cell> Interpreter<C> {
+    /// Runs `program` against the standard input and output, like `run` does.
+    pub fn run_jit(&mut self, program: &JitProgram<C>) -> Result<(), InterpreterError> {
+        let stdout = io::stdout();
+        let mut stdout_handle = stdout.lock();
+        let stdin = io::stdin();
+        let mut stdin_handle = stdin.lock();
+
+        if self.output_mode == OutputMode::Unbuffered {
+            self.execute_jit(program, &mut stdout_handle, &mut stdin_handle)
+        } else {
+            let mut buffered = BufWriter::new(stdout_handle);
+            self.execute_jit(program, &mut buffered, &mut stdin_handle)
+        }
+    }
+
+    /// Executes `program` against the current memory, using the given I/O handles.
+    ///
+    /// This behaves exactly like `execute` on the program the JIT program was compiled from.
+    /// Where native code is not available, and when a fuel or time limit is set, the program
+    /// runs on the bytecode VM instead.
+    pub fn execute_jit<W: Write, R: Read>(
+        &mut self,
+        program: &JitProgram<C>,
+        stdout: &mut W,
+        stdin: &mut R,
+    ) -> Result<(), InterpreterError> {
+        #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+        if !self.budget.is_limited() {
+            let result = native::execute(self, program, stdout, stdin);
+            let flushed = stdout.flush().map_err(InterpreterError::OutputError);
+            return result.and(flushed);
+        }
+
+        self.execute_bytecode(&program.bytecode, stdout, stdin)
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+mod native {
+    use crate::interpreter::{Cell, Interpreter, InterpreterError};
+    use crate::jit::compiler::{BASE, INDEX, INPUT, LEN, MOVE_POINTER, OUTPUT};
+    use crate::jit::JitProgram;
+    use crate::lexer::Span;
+    use std::io;
+    use std::io::{Read, Write};
+    use std::mem::offset_of;
+
+    /// A function the native code calls with the context, the index of the calling
+    /// instruction, and a pointer offset for moves. Returns zero on success.
+    type Callback<T> = unsafe extern "sysv64" fn(*mut T, usize, isize) -> u64;
+
+    /// The state shared between the native code and the callbacks.
+    ///
+    /// The native code only accesses the leading fields, at the offsets in `compiler`.
+    #[repr(C)]
+    struct Context<'a, C: Cell, W, R> {
+        base: *mut C,
+        index: usize,
+        len: usize,
+        move_pointer: Callback<Self>,
+        output: Callback<Self>,
+        input: Callback<Self>,
+        interpreter: &'a mut Interpreter<C>,
+        stdout: &'a mut W,
+        stdin: &'a mut R,
+        spans: &'a [Span],
+        /// The error of the callback that stopped the execution.
+        error: Option<InterpreterError>,
+    }
+
+    type Layout = Context<'static, u8, io::Sink, io::Empty>;
+    const _: () = {
+        assert!(offset_of!(Layout, base) == BASE as usize);
+        assert!(offset_of!(Layout, index) == INDEX as usize);
+        assert!(offset_of!(Layout, len) == LEN as usize);
+        assert!(offset_of!(Layout, move_pointer) == MOVE_POINTER as usize);
+        assert!(offset_of!(Layout, output) == OUTPUT as usize);
+        assert!(offset_of!(Layout, input) == INPUT as usize);
+    };
+
+    impl<C: Cell, W: Write, R: Read> Context<'_, C, W, R> {
+        /// Runs `action` on the interpreter for the instruction at `pc`, keeping the
+        /// pointer and the cells in sync with the native code.
+        fn call(
+            &mut self,
+            pc: usize,
+            action: impl FnOnce(
+                &mut Interpreter<C>,
+                &mut W,
+                &mut R,
+                Span,
+            ) -> Result<(), InterpreterError>,
+        ) -> u64 {
+            self.interpreter.tape.set_pointer(self.index);
+            let result = action(self.interpreter, self.stdout, self.stdin, self.spans[pc]);
+            (self.base, self.index, self.len) = self.interpreter.tape.raw_parts();
+
+            match result {
+                Ok(()) => 0,
+                Err(error) => {
+                    self.error = Some(error);
+                    1
+                }
+            }
+        }
+    }
+
+    unsafe extern "sysv64" fn move_pointer<C: Cell, W: Write, R: Read>(
+        context: *mut Context<'_, C, W, R>,
+        pc: usize,
+        offset: isize,
+    ) -> u64 {
+        // SAFETY: The native code passes back the context it was called with
+        let context = unsafe { &mut *context };
+        context.call(pc, |interpreter, _, _, span| {
+            interpreter.tape.move_by(offset, span)
+        })
+    }
+
+    unsafe extern "sysv64" fn output<C: Cell, W: Write, R: Read>(
+        context: *mut Context<'_, C, W, R>,
+        pc: usize,
+        _: isize,
+    ) -> u64 {
+        // SAFETY: The native code passes back the context it was called with
+        let context = unsafe { &mut *context };
+        context.call(pc, |interpreter, stdout, _, _| {
+            interpreter.output_byte(stdout)
+        })
+    }
+
+    unsafe extern "sysv64" fn input<C: Cell, W: Write, R: Read>(
+        context: *mut Context<'_, C, W, R>,
+        pc: usize,
+        _: isize,
+    ) -> u64 {
+        // SAFETY: The native code passes back the context it was called with
+        let context = unsafe { &mut *context };
+        context.call(pc, |interpreter, stdout, stdin, span| {
+            interpreter.input_byte(stdout, stdin, span)
+        })
+    }
+
+    /// Runs the native code of `program` on the interpreter's tape.
+    pub(super) fn execute<C: Cell, W: Write, R: Read>(
+        interpreter: &mut Interpreter<C>,
+        program: &JitProgram<C>,
+        stdout: &mut W,
+        stdin: &mut R,
+    ) -> Result<(), InterpreterError> {
+        let (base, index, len) = interpreter.tape.raw_parts();
+        let mut context = Context {
+            base,
+            index,
+            len,
+            move_pointer: move_pointer::<C, W, R>,
+            output: output::<C, W, R>,
+            input: input::<C, W, R>,
+            interpreter,
+            stdout,
+            stdin,
+            spans: &program.bytecode.spans,
+            error: None,
+        };
+
+        // SAFETY: The code was compiled for cells of type `C` and follows the System V
+        // calling convention, taking the context as its only argument
+        let status = unsafe {
+            let entry: unsafe extern "sysv64" fn(*mut Context<'_, C, W, R>) -> u64 =
+                std::mem::transmute(program.code.as_ptr());
+            entry(&mut context)
+        };
+
+        context.interpreter.tape.set_pointer(context.index);
+        match context.error.take() {
+            Some(error) => Err(error),
+            None => {
+                debug_assert_eq!(status, 0, "Native code failed without an error");
+                Ok(())
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::lexer::Token;
+use crate::lexer::{Span, Token, TokenKind};
 
 /// Lexer for Brainfuck programs.
 pub struct Lexer<'a> {
@@ -6,6 +6,10 @@ pub struct Lexer<'a> {
     source: &'a [u8],
     /// The current position in the source string.
     position: usize,
+    /// The current line in the source string, starting at 1.
+    line: usize,
+    /// The current column in the source string, starting at 1.
+    column: usize,
 }
 
 impl<'a> Lexer<'a> {
@@ -13,6 +17,8 @@ impl<'a> Lexer<'a> {
         Lexer {
             source: source.as_bytes(),
             position: 0,
+            line: 1,
+            column: 1,
         }
     }
 
@@ -27,20 +33,34 @@ impl<'a> Lexer<'a> {
     fn new_token(&mut self) -> Option<Token> {
         while self.position < self.source.len() {
             let current_byte = self.source[self.position];
-            self.position += 1;
+            let span = Span::new(self.position, self.position + 1, self.line, self.column);
+            self.advance(current_byte);
 
-            match current_byte {
-                b'>' => return Some(Token::IncrementPointer),
-                b'<' => return Some(Token::DecrementPointer),
-                b'+' => return Some(Token::IncrementByte),
-                b'-' => return Some(Token::DecrementByte),
-                b'.' => return Some(Token::OutputByte),
-                b',' => return Some(Token::InputByte),
-                b'[' => return Some(Token::LoopStart),
-                b']' => return Some(Token::LoopEnd),
+            let kind = match current_byte {
+                b'>' => TokenKind::IncrementPointer,
+                b'<' => TokenKind::DecrementPointer,
+                b'+' => TokenKind::IncrementByte,
+                b'-' => TokenKind::DecrementByte,
+                b'.' => TokenKind::OutputByte,
+                b',' => TokenKind::InputByte,
+                b'[' => TokenKind::LoopStart,
+                b']' => TokenKind::LoopEnd,
                 _ => continue, // Ignore non-command characters
-            }
+            };
+            return Some(Token::new(kind, span));
         }
         None
     }
+
+    /// Move past the current byte, keeping track of line and column.
+    fn advance(&mut self, byte: u8) {
+        self.position += 1;
+        if byte == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else if byte & 0xC0 != 0x80 {
+            // UTF-8 continuation bytes belong to the previous character
+            self.column += 1;
+        }
+    }
 }
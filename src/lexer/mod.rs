@@ -2,8 +2,11 @@
 //!
 //! This module provides a lexer for Brainfuck programs.
 
+#[allow(clippy::module_inception)]
 mod lexer;
+mod span;
 mod tokens;
 
 pub use lexer::Lexer;
-pub use tokens::Token;
+pub use span::Span;
+pub use tokens::{Token, TokenKind};
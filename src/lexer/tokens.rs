@@ -1,9 +1,17 @@
+use crate::lexer::Span;
 use std::fmt;
 use std::fmt::Formatter;
 
-/// The Token enum represents the different Brainfuck commands.
+/// A single Brainfuck command together with its location in the source.
 #[derive(Debug, Clone, PartialEq)]
-pub enum Token {
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// The TokenKind enum represents the different Brainfuck commands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
     IncrementPointer, // >
     DecrementPointer, // <
     IncrementByte,    // +
@@ -14,17 +22,29 @@ pub enum Token {
     LoopEnd,          // ]
 }
 
+impl Token {
+    pub fn new(kind: TokenKind, span: Span) -> Self {
+        Token { kind, span }
+    }
+}
+
 impl fmt::Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+impl fmt::Display for TokenKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Token::IncrementPointer => write!(f, ">"),
-            Token::DecrementPointer => write!(f, "<"),
-            Token::IncrementByte => write!(f, "+"),
-            Token::DecrementByte => write!(f, "-"),
-            Token::OutputByte => write!(f, "."),
-            Token::InputByte => write!(f, ","),
-            Token::LoopStart => write!(f, "["),
-            Token::LoopEnd => write!(f, "]"),
+            TokenKind::IncrementPointer => write!(f, ">"),
+            TokenKind::DecrementPointer => write!(f, "<"),
+            TokenKind::IncrementByte => write!(f, "+"),
+            TokenKind::DecrementByte => write!(f, "-"),
+            TokenKind::OutputByte => write!(f, "."),
+            TokenKind::InputByte => write!(f, ","),
+            TokenKind::LoopStart => write!(f, "["),
+            TokenKind::LoopEnd => write!(f, "]"),
         }
     }
 }
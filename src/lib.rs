@@ -1,7 +1,12 @@
+pub mod bytecode;
+pub mod codegen;
+pub mod diagnostics;
 pub mod interpreter;
+#[cfg(feature = "jit")]
+pub mod jit;
 pub mod lexer;
-#[cfg(feature = "optimizer")]
 pub mod optimizer;
 pub mod parser;
+mod x86;
 
 pub mod error;
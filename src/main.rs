@@ -1,39 +1,90 @@
-use crate::{error::BfError, interpreter::Interpreter, lexer::Lexer, parser::Parser};
+use bf_rs::{
+    bytecode::Bytecode,
+    codegen::CodegenOptions,
+    diagnostics::{Diagnostic, Style},
+    error::BfError,
+    interpreter::{Cell, CellWidth, Interpreter},
+    lexer::Lexer,
+    parser::{BfOp, Parser},
+};
+use cli::{Backend, Command, Options};
 use std::fs;
-
-#[cfg(feature = "optimizer")]
-use crate::optimizer::Optimizer;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::ExitCode;
 
 #[cfg(feature = "debug")]
 use std::fs::File;
 
+#[cfg(feature = "jit")]
+use bf_rs::jit::JitProgram;
+
+mod cli;
 #[cfg(feature = "debug")]
 mod debug;
-mod error;
-mod interpreter;
-mod lexer;
-#[cfg(feature = "optimizer")]
-mod optimizer;
-mod parser;
-
-fn main() -> Result<(), BfError> {
+
+fn main() -> ExitCode {
     let args: Vec<String> = std::env::args().collect();
 
     if args.len() < 2 {
-        eprintln!("Usage: {} <brainfuck_file>", args[0]);
-        return Ok(());
+        eprintln!("{}", cli::usage(&args[0]));
+        return ExitCode::SUCCESS;
     }
 
-    let source = fs::read_to_string(&args[1])?;
+    let options = match cli::parse_args(&args[1..]) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, cli::usage(&args[0]));
+            return ExitCode::FAILURE;
+        }
+    };
+    for warning in &options.warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    let source = match fs::read_to_string(&options.path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}", BfError::from(e));
+            return ExitCode::FAILURE;
+        }
+    };
 
     // Step 1: Lexical analysis - convert source to tokens
     let mut lexer = Lexer::new(&source);
     let tokens = lexer.tokenize();
 
-    // Step 2: Parsing - convert tokens to abstract syntax tree
+    // Step 2: Parsing - convert tokens to abstract syntax tree, reporting every error at once
     let mut parser = Parser::new(tokens);
-    let program = parser.parse()?;
+    let (program, errors) = parser.parse_recovering();
+    if !errors.is_empty() {
+        for error in errors {
+            report(&source, &options.path, &BfError::from(error));
+        }
+        return ExitCode::FAILURE;
+    }
 
+    match run(program, &options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            report(&source, &options.path, &error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints a diagnostic for `error` to stderr, colored when stderr is a terminal.
+fn report(source: &str, origin: &str, error: &BfError) {
+    let style = if std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none() {
+        Style::Ansi
+    } else {
+        Style::Plain
+    };
+    let diagnostic = Diagnostic::from_error(source, error);
+    eprintln!("{}", diagnostic.render(source, origin, style));
+}
+
+fn run(program: Vec<BfOp>, options: &Options) -> Result<(), BfError> {
     #[cfg(feature = "debug")]
     {
         std::fs::create_dir_all("out")?;
@@ -47,10 +98,11 @@ fn main() -> Result<(), BfError> {
     }
 
     // Step 2.1: Optimization - apply optimization rules
-    #[cfg(feature = "optimizer")]
     let program = {
-        let optimizer = Optimizer::new();
-        let optimized = optimizer.optimize(program);
+        let (optimized, report) = options.optimizer.optimize_with_report(program);
+        if options.optimization_report {
+            eprintln!("{}", report);
+        }
 
         #[cfg(feature = "debug")]
         {
@@ -62,9 +114,55 @@ fn main() -> Result<(), BfError> {
         optimized
     };
 
-    // Step 3: Execution
-    let mut interpreter = Interpreter::new();
-    interpreter.run(&program)?;
+    // Step 3: Execution, or compilation ahead of time
+    if let Command::Build { emit, output } = &options.command {
+        let codegen_options = CodegenOptions {
+            cell_width: options.cell_width,
+            eof_policy: options.eof_policy,
+            tape: options.tape,
+        };
+        let output = match output {
+            Some(output) => Path::new(output).to_path_buf(),
+            None => Path::new(&options.path).with_extension(emit.extension()),
+        };
+        if output == Path::new(&options.path) {
+            return Err(BfError::Io(std::io::Error::other(
+                "the output would overwrite the program, choose another path with `-o`",
+            )));
+        }
+        fs::write(&output, emit.compile(&program, &codegen_options))?;
+        if let Some((extension, contents)) = emit.companion() {
+            fs::write(output.with_extension(extension), contents)?;
+        }
+        #[cfg(unix)]
+        if emit.is_executable() {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&output, fs::Permissions::from_mode(0o755))?;
+        }
+        return Ok(());
+    }
+
+    match options.cell_width {
+        CellWidth::U8 => execute::<u8>(&program, options),
+        CellWidth::U16 => execute::<u16>(&program, options),
+        CellWidth::U32 => execute::<u32>(&program, options),
+        CellWidth::U64 => execute::<u64>(&program, options),
+    }
+}
 
+/// Executes the program with cells of type `C`.
+fn execute<C: Cell>(program: &[BfOp], options: &Options) -> Result<(), BfError> {
+    let mut interpreter = Interpreter::<C>::default()
+        .with_eof_policy(options.eof_policy)
+        .with_tape(options.tape)
+        .with_fuel(options.fuel)
+        .with_timeout(options.timeout)
+        .with_output_mode(options.output_mode);
+    match options.backend {
+        Backend::Tree => interpreter.run(program)?,
+        Backend::Bytecode => interpreter.run_bytecode(&Bytecode::compile(program))?,
+        #[cfg(feature = "jit")]
+        Backend::Jit => interpreter.run_jit(&JitProgram::compile(program)?)?,
+    }
     Ok(())
 }
@@ -0,0 +1,47 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How much the optimizer does, selected with `-O0` through `-O3`.
+///
+/// Each level enables the passes of the levels below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptimizationLevel {
+    /// No passes at all.
+    O0,
+    /// Only passes replacing a single loop or constant.
+    O1,
+    /// Also passes rewriting whole loops into straight-line code.
+    O2,
+    /// Every pass.
+    #[default]
+    O3,
+}
+
+impl fmt::Display for OptimizationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            OptimizationLevel::O0 => 0,
+            OptimizationLevel::O1 => 1,
+            OptimizationLevel::O2 => 2,
+            OptimizationLevel::O3 => 3,
+        };
+        write!(f, "-O{}", level)
+    }
+}
+
+impl FromStr for OptimizationLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(OptimizationLevel::O0),
+            "1" => Ok(OptimizationLevel::O1),
+            "2" => Ok(OptimizationLevel::O2),
+            "3" => Ok(OptimizationLevel::O3),
+            _ => Err(format!(
+                "invalid optimization level `{}`, expected 0, 1, 2 or 3",
+                s
+            )),
+        }
+    }
+}
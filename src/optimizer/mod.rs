@@ -1,16 +1,22 @@
-#![cfg(feature = "optimizer")]
-
 //! Brainfuck optimizer.
 //!
 //! This module provides an optimizer for Brainfuck programs,
 //! which applies various optimization rules to improve the performance
 //! and efficiency of the code.
 //!
-//! This module is available only when the `optimizer` feature is enabled.
+//! The passes rewriting whole loops and blocks are heavier, and are only compiled with the
+//! `optimizer` feature. The operations they produce are always part of `OptimizedOp`, so
+//! the types of the library do not depend on the feature, only which passes exist.
 
+mod level;
 mod optimization_rule;
+#[allow(clippy::module_inception)]
 mod optimizer;
+mod report;
 mod rules;
 
+pub use level::OptimizationLevel;
 pub use optimization_rule::OptimizationRule;
 pub use optimizer::Optimizer;
+pub use report::{OptimizationReport, PassReport};
+pub use rules::{MULTIPLY_LOOPS, OFFSET_OPS, SCAN_LOOPS};
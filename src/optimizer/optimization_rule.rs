@@ -2,8 +2,21 @@ use crate::parser::BfOp;
 
 /// Represents an optimization that can be applied to a Brainfuck program.
 pub trait OptimizationRule {
+    /// A short name for the rule, like `clear-loops`, used in reports and to toggle it.
+    /// It should not change between releases.
+    fn name(&self) -> &'static str;
+
+    /// A one-line description of what the rule does, for the usage text.
+    fn description(&self) -> &'static str;
+
     /// Applies the optimization rule to a slice of BfOp operations.
     /// Returns `Some((Vec<BfOp>, usize))` with the optimized operations
     /// and the number of original operations consumed if any optimizations were made.
     fn apply(&self, ops: &[BfOp]) -> Option<(Vec<BfOp>, usize)>;
+
+    /// Applies the optimization rule at the start of the program, where every cell is 0.
+    /// Only rules relying on that need to override this.
+    fn apply_at_start(&self, ops: &[BfOp]) -> Option<(Vec<BfOp>, usize)> {
+        self.apply(ops)
+    }
 }
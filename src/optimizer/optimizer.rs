@@ -1,73 +1,251 @@
 use crate::optimizer::rules::*;
-use crate::optimizer::OptimizationRule;
-use crate::parser::BfOp;
+use crate::optimizer::{OptimizationLevel, OptimizationReport, OptimizationRule, PassReport};
+use crate::parser::{BfOp, BfOpKind};
+use std::fmt;
+use std::fmt::Formatter;
+
+/// The number of iterations after which the optimizer stops, even if rules still fire.
+const DEFAULT_MAX_ITERATIONS: usize = 16;
+
+/// The names of the default rules that only exist with the `optimizer` feature.
+const FEATURE_RULES: [&str; 3] = [MULTIPLY_LOOPS, SCAN_LOOPS, OFFSET_OPS];
 
 /// Optimizer for Brainfuck programs.
+///
+/// Each rule is a pass over the whole program, and the passes run in the order the rules
+/// were registered. They are repeated until an iteration changes nothing, so that rules
+/// can match the operations produced by other rules, like a clear followed by an increment.
 pub struct Optimizer {
-    rules: Vec<Box<dyn OptimizationRule>>,
+    passes: Vec<Pass>,
+    max_iterations: usize,
+}
+
+/// A registered rule, which only runs while enabled.
+struct Pass {
+    rule: Box<dyn OptimizationRule>,
+    enabled: bool,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Optimizer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let enabled = self.passes.iter().filter(|pass| pass.enabled);
+        f.debug_struct("Optimizer")
+            .field(
+                "passes",
+                &enabled.map(|pass| pass.rule.name()).collect::<Vec<_>>(),
+            )
+            .field("max_iterations", &self.max_iterations)
+            .finish()
+    }
 }
 
 impl Optimizer {
     /// Create an optimizer with the default set of optimization rules.
     pub fn new() -> Self {
-        let mut optimizer = Self { rules: Vec::new() };
-        optimizer.register_default_rules();
+        Self::with_level(OptimizationLevel::default())
+    }
+
+    /// Create an optimizer with every default rule registered, enabling those of `level`
+    /// and below.
+    pub fn with_level(level: OptimizationLevel) -> Self {
+        let mut optimizer = Self::empty();
+        for (rule_level, rule) in Self::default_rules() {
+            optimizer.passes.push(Pass {
+                rule,
+                enabled: rule_level <= level,
+            });
+        }
         optimizer
     }
 
     /// Create an optimizer with no rules.
-    #[allow(dead_code)]
     pub fn empty() -> Self {
-        Self { rules: Vec::new() }
+        Self {
+            passes: Vec::new(),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        }
     }
 
-    /// Register an optimization rule.
+    /// Limit the number of times the passes are repeated.
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// The default rules in the order they run, with the lowest level enabling each of them.
+    /// Without the `optimizer` feature, only the light rules exist.
+    pub fn default_rules() -> Vec<(OptimizationLevel, Box<dyn OptimizationRule>)> {
+        vec![
+            (OptimizationLevel::O1, Box::new(ClearLoopRule {})),
+            #[cfg(feature = "optimizer")]
+            (OptimizationLevel::O2, Box::new(MultiplyLoopRule {})),
+            #[cfg(feature = "optimizer")]
+            (OptimizationLevel::O2, Box::new(ScanLoopRule {})),
+            #[cfg(feature = "optimizer")]
+            (OptimizationLevel::O3, Box::new(OffsetOpsRule {})),
+            (OptimizationLevel::O1, Box::new(SetCellRule {})),
+            (OptimizationLevel::O1, Box::new(DeadLoopRule {})),
+            // Register other rules here
+        ]
+    }
+
+    /// Register an optimization rule, enabled.
     pub fn register_rule(&mut self, rule: Box<dyn OptimizationRule>) {
-        self.rules.push(rule);
+        self.passes.push(Pass {
+            rule,
+            enabled: true,
+        });
     }
 
-    /// Register the default set of optimization rules.
-    fn register_default_rules(&mut self) {
-        self.register_rule(Box::new(ClearLoopRule {}));
-        // Register other rules here
+    /// Enable or disable the registered rule called `name`, whatever the level.
+    ///
+    /// Returns whether there was such a rule. The rules that only exist with the `optimizer`
+    /// feature are known in every build, so that the same toggles work with or without it,
+    /// but without the feature toggling them does nothing.
+    pub fn set_rule_enabled(&mut self, name: &str, enabled: bool) -> Result<bool, String> {
+        match self.passes.iter_mut().find(|pass| pass.rule.name() == name) {
+            Some(pass) => {
+                pass.enabled = enabled;
+                Ok(true)
+            }
+            None if !cfg!(feature = "optimizer") && FEATURE_RULES.contains(&name) => Ok(false),
+            None => {
+                let names: Vec<_> = self.passes.iter().map(|pass| pass.rule.name()).collect();
+                Err(format!(
+                    "unknown optimization pass `{}`, expected one of {}",
+                    name,
+                    names.join(", ")
+                ))
+            }
+        }
     }
 
     /// Optimize a Brainfuck program.
     pub fn optimize(&self, program: Vec<BfOp>) -> Vec<BfOp> {
-        self.optimize_ops(program)
+        self.optimize_with_report(program).0
     }
 
-    fn optimize_ops(&self, ops: Vec<BfOp>) -> Vec<BfOp> {
-        let mut result = Vec::with_capacity(ops.len());
-        let mut i = 0;
-
-        while i < ops.len() {
-            let mut optimized = false;
-
-            // Try to find a pattern at the current position
-            for rule in &self.rules {
-                if let Some((replacement, consumed)) = rule.apply(&ops[i..]) {
-                    result.extend(replacement);
-                    // Skip ahead based on pattern length returned by the rule
-                    i += consumed;
-                    optimized = true;
-                    break;
-                }
-            }
+    /// Optimize a Brainfuck program, reporting how often each rule fired.
+    pub fn optimize_with_report(&self, program: Vec<BfOp>) -> (Vec<BfOp>, OptimizationReport) {
+        let mut report = OptimizationReport {
+            iterations: 0,
+            converged: false,
+            passes: self
+                .enabled()
+                .map(|rule| PassReport {
+                    name: rule.name(),
+                    fires: 0,
+                    ops_removed: 0,
+                })
+                .collect(),
+            ops_before: count_ops(&program),
+            ops_after: 0,
+        };
 
-            // If no optimization was applied, just copy the current operation
-            if !optimized {
-                match &ops[i] {
-                    BfOp::Loop(body) => {
-                        let optimized_body = self.optimize_ops(body.clone());
-                        result.push(BfOp::Loop(optimized_body));
-                    }
-                    op => result.push(op.clone()),
-                }
-                i += 1;
+        let mut program = program;
+        while report.iterations < self.max_iterations {
+            report.iterations += 1;
+            let mut changed = false;
+            for (rule, pass) in self.enabled().zip(&mut report.passes) {
+                let (fires, ops) = (pass.fires, count_ops(&program));
+                program = run_pass(rule, program, &mut pass.fires);
+                pass.ops_removed += ops.saturating_sub(count_ops(&program));
+                changed |= pass.fires > fires;
+            }
+            if !changed {
+                report.converged = true;
+                break;
             }
         }
 
-        result
+        report.ops_after = count_ops(&program);
+        (program, report)
+    }
+
+    /// The rules that run, in order.
+    fn enabled(&self) -> impl Iterator<Item = &dyn OptimizationRule> {
+        self.passes
+            .iter()
+            .filter(|pass| pass.enabled)
+            .map(|pass| pass.rule.as_ref())
+    }
+}
+
+/// Applies `rule` wherever it matches in `program`, counting the matches in `fires`.
+/// Loop bodies are only optimized when the rule did not match the loop itself.
+///
+/// # Details
+/// Loops are walked with an explicit stack instead of through recursion, so the nesting
+/// depth is limited only by the available heap memory. Their bodies are moved into the
+/// result rather than copied.
+fn run_pass(rule: &dyn OptimizationRule, program: Vec<BfOp>, fires: &mut usize) -> Vec<BfOp> {
+    // The sequences enclosing the loops being rewritten, with the index of the loop in
+    // each, and what each was rewritten to so far
+    let mut frames: Vec<(Vec<BfOp>, usize, Vec<BfOp>)> = Vec::new();
+    let mut result = Vec::with_capacity(program.len());
+    let mut ops = program;
+    let mut index = 0;
+
+    loop {
+        if index >= ops.len() {
+            // End of a sequence: either the program is done, or a loop body is finished
+            let Some((enclosing, loop_index, enclosing_result)) = frames.pop() else {
+                break;
+            };
+            let body = std::mem::replace(&mut result, enclosing_result);
+            ops = enclosing;
+            result.push(BfOp::new(BfOpKind::Loop(body), ops[loop_index].span));
+            index = loop_index + 1;
+            continue;
+        }
+
+        // Try to find a pattern at the current position. Only the program itself starts
+        // with every cell at 0, loop bodies do not
+        let matched = if frames.is_empty() && index == 0 {
+            rule.apply_at_start(&ops)
+        } else {
+            rule.apply(&ops[index..])
+        };
+        if let Some((replacement, consumed)) = matched {
+            result.extend(replacement);
+            // Skip ahead based on pattern length returned by the rule
+            index += consumed;
+            *fires += 1;
+            continue;
+        }
+
+        // If no optimization was applied, keep the current operation, rewriting loop bodies
+        if let BfOpKind::Loop(body) = &mut ops[index].kind {
+            let body = std::mem::take(body);
+            let enclosing = std::mem::replace(&mut ops, body);
+            frames.push((enclosing, index, std::mem::take(&mut result)));
+            index = 0;
+            continue;
+        }
+        result.push(ops[index].clone());
+        index += 1;
+    }
+
+    result
+}
+
+/// Counts the operations in `ops`, including those in loop bodies.
+fn count_ops(ops: &[BfOp]) -> usize {
+    let mut count = 0;
+    // Loop bodies still to count, on the heap rather than the call stack
+    let mut pending = vec![ops];
+    while let Some(ops) = pending.pop() {
+        count += ops.len();
+        pending.extend(ops.iter().filter_map(|op| match &op.kind {
+            BfOpKind::Loop(body) => Some(body.as_slice()),
+            _ => None,
+        }));
     }
+    count
 }
@@ -0,0 +1,60 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+/// What the optimizer did to a program, as returned by `Optimizer::optimize_with_report`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptimizationReport {
+    /// The number of times every pass ran over the program.
+    pub iterations: usize,
+    /// Whether the last iteration changed nothing, rather than hitting the limit.
+    pub converged: bool,
+    /// What each pass did, in the order the passes run.
+    pub passes: Vec<PassReport>,
+    /// The number of operations before optimizing, counting those in loop bodies.
+    pub ops_before: usize,
+    /// The number of operations after optimizing, counting those in loop bodies.
+    pub ops_after: usize,
+}
+
+/// What one pass did over every iteration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassReport {
+    /// The name of the rule.
+    pub name: &'static str,
+    /// How many times the rule matched.
+    pub fires: usize,
+    /// How many operations the pass removed, counting those in loop bodies.
+    pub ops_removed: usize,
+}
+
+impl OptimizationReport {
+    /// The number of operations the optimizer removed.
+    pub fn ops_removed(&self) -> usize {
+        self.ops_before.saturating_sub(self.ops_after)
+    }
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Optimized {} ops down to {} ({} removed) in {} iteration{}",
+            self.ops_before,
+            self.ops_after,
+            self.ops_removed(),
+            self.iterations,
+            if self.iterations == 1 { "" } else { "s" }
+        )?;
+        if !self.converged {
+            write!(f, ", stopping at the limit")?;
+        }
+        for pass in &self.passes {
+            write!(
+                f,
+                "\n  {}: {} ({} ops removed)",
+                pass.name, pass.fires, pass.ops_removed
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,21 +1,30 @@
 use crate::optimizer::OptimizationRule;
-use crate::parser::{BfOp, OptimizedOp};
+use crate::parser::{BfOp, BfOpKind, OptimizedOp};
 
 /// Rule to optimize clear loops like `[+]` or `[-]` to set memory cell to 0.
 pub struct ClearLoopRule {}
 
 impl OptimizationRule for ClearLoopRule {
+    fn name(&self) -> &'static str {
+        "clear-loops"
+    }
+
+    fn description(&self) -> &'static str {
+        "Set the cell to zero for `[-]` and `[+]`"
+    }
+
     fn apply(&self, ops: &[BfOp]) -> Option<(Vec<BfOp>, usize)> {
         if ops.is_empty() {
             return None;
         }
 
-        if let BfOp::Loop(body) = &ops[0] {
+        if let BfOpKind::Loop(body) = &ops[0].kind {
             if body.len() == 1 {
-                if let BfOp::Increment(_) = body[0] {
+                if let BfOpKind::Increment(_) = body[0].kind {
                     // Detected a `[+]` or `[-]` loop, set cell to 0
                     // Consumes 1 BfOp (the loop itself) and replaces it with a clear cell operation
-                    return Some((vec![BfOp::Optimized(OptimizedOp::ClearCell)], 1));
+                    let clear = BfOp::new(BfOpKind::Optimized(OptimizedOp::ClearCell), ops[0].span);
+                    return Some((vec![clear], 1));
                 }
             }
         }
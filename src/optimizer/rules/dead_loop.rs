@@ -0,0 +1,84 @@
+use crate::optimizer::OptimizationRule;
+use crate::parser::{BfOp, BfOpKind, OptimizedOp};
+
+/// Rule to remove loops that can never run, because the current cell is known to be 0
+/// when they start.
+///
+/// That is the case at the start of the program, as with comment loops, and right after
+/// anything leaving the current cell at 0: another loop, a clear or a scan. Operations
+/// that leave the current cell alone in between, like output, keep it known to be 0.
+pub struct DeadLoopRule {}
+
+impl OptimizationRule for DeadLoopRule {
+    fn name(&self) -> &'static str {
+        "dead-loops"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove loops that start on a cell known to be 0"
+    }
+
+    fn apply(&self, ops: &[BfOp]) -> Option<(Vec<BfOp>, usize)> {
+        let first = ops.first()?;
+        let zeroes_cell = matches!(
+            first.kind,
+            BfOpKind::Loop(_)
+                | BfOpKind::Optimized(
+                    OptimizedOp::ClearCell | OptimizedOp::ScanRight(_) | OptimizedOp::ScanLeft(_)
+                )
+        );
+        if !zeroes_cell {
+            return None;
+        }
+
+        let (kept, consumed) = strip_dead_loops(&ops[1..])?;
+        let mut replacement = vec![first.clone()];
+        replacement.extend(kept);
+        Some((replacement, consumed + 1))
+    }
+
+    fn apply_at_start(&self, ops: &[BfOp]) -> Option<(Vec<BfOp>, usize)> {
+        strip_dead_loops(ops)
+    }
+}
+
+/// Removes the loops from `ops` while the current cell stays 0, returning the operations
+/// kept and the number consumed, up to the last loop removed.
+fn strip_dead_loops(ops: &[BfOp]) -> Option<(Vec<BfOp>, usize)> {
+    let mut consumed = 0;
+
+    for (i, op) in ops.iter().enumerate() {
+        let keeps_zero = match &op.kind {
+            BfOpKind::Loop(_) => {
+                // Everything up to here is kept, except the loops
+                consumed = i + 1;
+                continue;
+            }
+            BfOpKind::OutputByte => true,
+            BfOpKind::Optimized(optimized) => match optimized {
+                OptimizedOp::ClearCell
+                | OptimizedOp::ScanRight(_)
+                | OptimizedOp::ScanLeft(_)
+                // Adds a multiple of 0
+                | OptimizedOp::MulAdd { .. }
+                | OptimizedOp::OutputAt(_) => true,
+                OptimizedOp::AddAt { offset, .. } => *offset != 0,
+                OptimizedOp::SetCell(_) => false,
+            },
+            _ => false,
+        };
+        if !keeps_zero {
+            break;
+        }
+    }
+
+    if consumed == 0 {
+        return None;
+    }
+    let kept = ops[..consumed]
+        .iter()
+        .filter(|op| !matches!(op.kind, BfOpKind::Loop(_)))
+        .cloned()
+        .collect();
+    Some((kept, consumed))
+}
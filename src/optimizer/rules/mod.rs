@@ -1,3 +1,29 @@
 mod clear_loop;
+mod dead_loop;
+#[cfg(feature = "optimizer")]
+mod multiply_loop;
+#[cfg(feature = "optimizer")]
+mod offset_ops;
+#[cfg(feature = "optimizer")]
+mod scan_loop;
+mod set_cell;
 
 pub use clear_loop::ClearLoopRule;
+pub use dead_loop::DeadLoopRule;
+#[cfg(feature = "optimizer")]
+pub use multiply_loop::MultiplyLoopRule;
+#[cfg(feature = "optimizer")]
+pub use offset_ops::OffsetOpsRule;
+#[cfg(feature = "optimizer")]
+pub use scan_loop::ScanLoopRule;
+pub use set_cell::SetCellRule;
+
+// The names of the passes that only exist with the `optimizer` feature, compiled without
+// it too so that they can still be named, like in `--opt`.
+
+/// Name of the pass turning multiplication loops into `MulAdd` operations.
+pub const MULTIPLY_LOOPS: &str = "multiply-loops";
+/// Name of the pass turning loops like `[>]` into scans.
+pub const SCAN_LOOPS: &str = "scan-loops";
+/// Name of the pass addressing cells by offset.
+pub const OFFSET_OPS: &str = "offset-ops";
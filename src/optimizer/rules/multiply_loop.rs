@@ -0,0 +1,84 @@
+use crate::optimizer::rules::MULTIPLY_LOOPS;
+use crate::optimizer::OptimizationRule;
+use crate::parser::{BfOp, BfOpKind, OptimizedOp};
+use std::num::Wrapping;
+
+/// Rule to optimize multiplication loops like `[->+>++<<]`, which add multiples of the
+/// current cell to nearby cells and then leave it at 0.
+///
+/// The loop body may only move the pointer and change cells, must return the pointer to
+/// where it started, and must change the current cell by exactly one per iteration.
+/// It must also change every cell it moves to, so that moving off the tape fails in the
+/// `MulAdd` of that cell like it did in the loop. The loop becomes one `MulAdd` per cell,
+/// in the order the body first moves to them, followed by a clear of the current cell.
+///
+/// Cells at different offsets are assumed to be different, which only fails on wrapping
+/// tapes smaller than the distance the loop moves from the current cell.
+pub struct MultiplyLoopRule {}
+
+impl OptimizationRule for MultiplyLoopRule {
+    fn name(&self) -> &'static str {
+        MULTIPLY_LOOPS
+    }
+
+    fn description(&self) -> &'static str {
+        "Turn loops like `[->++<]` into multiply-adds"
+    }
+
+    fn apply(&self, ops: &[BfOp]) -> Option<(Vec<BfOp>, usize)> {
+        let BfOpKind::Loop(body) = &ops.first()?.kind else {
+            return None;
+        };
+
+        // The net change per iteration of each cell the body moves to, by offset from the
+        // current one, in the order the body first gets there
+        let mut offset = 0isize;
+        let mut changes: Vec<(isize, Wrapping<i64>)> = vec![(0, Wrapping(0))];
+        for op in body {
+            match op.kind {
+                BfOpKind::PointerIncrement(delta) => {
+                    offset = offset.checked_add(delta)?;
+                    if !changes.iter().any(|&(target, _)| target == offset) {
+                        changes.push((offset, Wrapping(0)));
+                    }
+                }
+                BfOpKind::Increment(delta) => {
+                    let (_, change) = changes.iter_mut().find(|(target, _)| *target == offset)?;
+                    *change += delta;
+                }
+                _ => return None,
+            }
+        }
+        if offset != 0 {
+            return None;
+        }
+
+        // Counting down from the current value runs the body that many times, while
+        // counting up runs it as many times as the negated value
+        let (_, step) = changes[0];
+        let sign = match step.0 {
+            -1 => Wrapping(1),
+            1 => Wrapping(-1),
+            _ => return None,
+        };
+        // Without a `MulAdd` for a cell, nothing would check that it is on the tape
+        if changes[1..].iter().any(|(_, change)| change.0 == 0) {
+            return None;
+        }
+
+        let span = ops[0].span;
+        let mut replacement: Vec<BfOp> = changes[1..]
+            .iter()
+            .map(|&(offset, change)| {
+                let factor = (change * sign).0;
+                BfOp::new(
+                    BfOpKind::Optimized(OptimizedOp::MulAdd { offset, factor }),
+                    span,
+                )
+            })
+            .collect();
+        replacement.push(BfOp::new(BfOpKind::Optimized(OptimizedOp::ClearCell), span));
+
+        Some((replacement, 1))
+    }
+}
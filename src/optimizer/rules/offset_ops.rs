@@ -0,0 +1,73 @@
+use crate::optimizer::rules::OFFSET_OPS;
+use crate::optimizer::OptimizationRule;
+use crate::parser::{BfOp, BfOpKind, OptimizedOp};
+
+/// Rule to delay pointer movement in straight-line code like `>+>+>+<<<`, addressing
+/// cells by their offset instead and moving the pointer once at the end.
+///
+/// A move is only delayed when the next operation changes or prints the cell it lands
+/// on, so that leaving the tape still fails before any further output, like the move
+/// would. Any other move ends the block, and becomes its single pointer adjustment.
+/// The block must save at least one move to be rewritten.
+pub struct OffsetOpsRule {}
+
+impl OptimizationRule for OffsetOpsRule {
+    fn name(&self) -> &'static str {
+        OFFSET_OPS
+    }
+
+    fn description(&self) -> &'static str {
+        "Address cells by offset instead of moving there"
+    }
+
+    fn apply(&self, ops: &[BfOp]) -> Option<(Vec<BfOp>, usize)> {
+        let mut replacement = Vec::new();
+        let mut offset = 0isize;
+        let mut last_move = None;
+        // Whether the last move landed on a cell that no operation has used yet
+        let mut pending = false;
+        let mut consumed = 0;
+
+        for op in ops {
+            let kind = match op.kind {
+                BfOpKind::PointerIncrement(_) if pending => break,
+                BfOpKind::PointerIncrement(delta) => {
+                    // Moving back from the cell must fit into a move too
+                    let Some(moved) = offset
+                        .checked_add(delta)
+                        .filter(|&moved| moved != isize::MIN)
+                    else {
+                        break;
+                    };
+                    offset = moved;
+                    last_move = Some(op.span);
+                    pending = true;
+                    consumed += 1;
+                    continue;
+                }
+                BfOpKind::Increment(delta) if offset == 0 => BfOpKind::Increment(delta),
+                BfOpKind::Increment(delta) => BfOpKind::Optimized(OptimizedOp::AddAt {
+                    offset,
+                    delta: delta.0,
+                }),
+                BfOpKind::OutputByte if offset == 0 => BfOpKind::OutputByte,
+                BfOpKind::OutputByte => BfOpKind::Optimized(OptimizedOp::OutputAt(offset)),
+                _ => break,
+            };
+            replacement.push(BfOp::new(kind, op.span));
+            pending = false;
+            consumed += 1;
+        }
+
+        // The adjustment fails like the last move, or cannot fail as its cell was used
+        if offset != 0 {
+            let span = last_move?;
+            replacement.push(BfOp::new(BfOpKind::PointerIncrement(offset), span));
+        }
+        if replacement.len() >= consumed {
+            return None;
+        }
+
+        Some((replacement, consumed))
+    }
+}
@@ -0,0 +1,40 @@
+use crate::optimizer::rules::SCAN_LOOPS;
+use crate::optimizer::OptimizationRule;
+use crate::parser::{BfOp, BfOpKind, OptimizedOp};
+
+/// Rule to optimize scan loops like `[>]`, `[<]` or `[>>>]`, which move the pointer
+/// until it reaches a zero cell.
+///
+/// The scan takes the span of the move inside the loop, so that running off the tape
+/// is reported at the same place as before.
+pub struct ScanLoopRule {}
+
+impl OptimizationRule for ScanLoopRule {
+    fn name(&self) -> &'static str {
+        SCAN_LOOPS
+    }
+
+    fn description(&self) -> &'static str {
+        "Turn loops like `[>]` into a search for a zero"
+    }
+
+    fn apply(&self, ops: &[BfOp]) -> Option<(Vec<BfOp>, usize)> {
+        let BfOpKind::Loop(body) = &ops.first()?.kind else {
+            return None;
+        };
+        let [step] = body.as_slice() else {
+            return None;
+        };
+        let BfOpKind::PointerIncrement(delta) = step.kind else {
+            return None;
+        };
+
+        let scan = match delta {
+            1.. => OptimizedOp::ScanRight(delta as usize),
+            // The stride of a left scan must fit back into a move
+            ..=-1 if delta != isize::MIN => OptimizedOp::ScanLeft(delta.unsigned_abs()),
+            _ => return None,
+        };
+        Some((vec![BfOp::new(BfOpKind::Optimized(scan), step.span)], 1))
+    }
+}
@@ -0,0 +1,55 @@
+use crate::optimizer::OptimizationRule;
+use crate::parser::{BfOp, BfOpKind, OptimizedOp};
+use std::num::Wrapping;
+
+/// Rule to fuse clears and increments of the current cell, like `[-]+++`, into a single
+/// operation setting the cell to a constant.
+///
+/// Increments right before a clear or another constant are dead, so they are dropped.
+/// This rule only matches the operations of other rules, so it runs after them.
+pub struct SetCellRule {}
+
+impl OptimizationRule for SetCellRule {
+    fn name(&self) -> &'static str {
+        "set-cells"
+    }
+
+    fn description(&self) -> &'static str {
+        "Fuse clears and increments into constants"
+    }
+
+    fn apply(&self, ops: &[BfOp]) -> Option<(Vec<BfOp>, usize)> {
+        // The value the cell is set to so far, and the operation that set it
+        let mut value = None;
+        let mut span = ops.first()?.span;
+        let mut consumed = 0;
+
+        for op in ops {
+            value = match (&op.kind, value) {
+                (BfOpKind::Optimized(OptimizedOp::ClearCell), _) => {
+                    span = op.span;
+                    Some(Wrapping(0))
+                }
+                (&BfOpKind::Optimized(OptimizedOp::SetCell(constant)), _) => {
+                    span = op.span;
+                    Some(Wrapping(constant))
+                }
+                (&BfOpKind::Increment(delta), Some(value)) => Some(value + delta),
+                // Dead unless nothing sets the cell afterwards
+                (BfOpKind::Increment(_), None) => None,
+                _ => break,
+            };
+            consumed += 1;
+        }
+
+        let value = value?;
+        if consumed < 2 {
+            return None;
+        }
+        let kind = match value.0 {
+            0 => OptimizedOp::ClearCell,
+            value => OptimizedOp::SetCell(value),
+        };
+        Some((vec![BfOp::new(BfOpKind::Optimized(kind), span)], consumed))
+    }
+}
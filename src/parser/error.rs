@@ -1,3 +1,4 @@
+use crate::lexer::Span;
 use std::error::Error;
 use std::fmt;
 
@@ -5,27 +6,30 @@ use std::fmt;
 #[derive(Debug)]
 pub enum ParseError {
     /// A loop start `[` without a matching `]` after it
-    UnmatchedLoopStart { position: Option<usize> },
+    UnmatchedLoopStart { span: Span },
     /// A loop end `]` without a matching `[` before it
-    UnmatchedLoopEnd { position: Option<usize> },
+    UnmatchedLoopEnd { span: Span },
+}
+
+impl ParseError {
+    /// The source location of the offending bracket.
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnmatchedLoopStart { span } | ParseError::UnmatchedLoopEnd { span } => {
+                *span
+            }
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ParseError::UnmatchedLoopStart { position } => {
-                write!(
-                    f,
-                    "Unmatched loop start{}",
-                    position.map_or(String::new(), |p| format!(" at position {}", p))
-                )
+            ParseError::UnmatchedLoopStart { span } => {
+                write!(f, "Unmatched loop start at {}", span)
             }
-            ParseError::UnmatchedLoopEnd { position } => {
-                write!(
-                    f,
-                    "Unmatched loop end{}",
-                    position.map_or(String::new(), |p| format!(" at position {}", p))
-                )
+            ParseError::UnmatchedLoopEnd { span } => {
+                write!(f, "Unmatched loop end at {}", span)
             }
         }
     }
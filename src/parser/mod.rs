@@ -4,10 +4,10 @@
 
 mod error;
 mod ops;
+#[allow(clippy::module_inception)]
 mod parser;
 
 pub use error::ParseError;
-pub use ops::BfOp;
-#[cfg(feature = "optimizer")]
 pub use ops::OptimizedOp;
+pub use ops::{BfOp, BfOpKind};
 pub use parser::Parser;
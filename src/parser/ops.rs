@@ -1,33 +1,121 @@
+use crate::lexer::Span;
 use std::fmt;
 use std::fmt::Formatter;
 use std::num::Wrapping;
 
-/// The BfOp enum represents the different Brainfuck operations.
+/// A Brainfuck operation together with the source region it was parsed from.
+#[derive(Debug, PartialEq)]
+pub struct BfOp {
+    pub kind: BfOpKind,
+    pub span: Span,
+}
+
+/// The BfOpKind enum represents the different Brainfuck operations.
 #[derive(Debug, Clone, PartialEq)]
-pub enum BfOp {
-    PointerIncrement(isize), // > or <
-    Increment(Wrapping<i8>), // + or -
-    OutputByte,              // .
-    InputByte,               // ,
-    Loop(Vec<BfOp>),         // [ ... ]
+pub enum BfOpKind {
+    PointerIncrement(isize),  // > or <
+    Increment(Wrapping<i64>), // + or -
+    OutputByte,               // .
+    InputByte,                // ,
+    Loop(Vec<BfOp>),          // [ ... ]
 
     // Optimized operations
-    #[cfg(feature = "optimizer")]
     Optimized(OptimizedOp),
 }
 
 /// The OptimizedOp enum represents optimized operations that can be applied to Brainfuck programs.
-/// These are not standard Brainfuck operations, and are toggled by the `optimizer` feature.
-#[cfg(feature = "optimizer")]
+/// These are not standard Brainfuck operations, and only the optimizer produces them.
 #[derive(Debug, Clone, PartialEq)]
 pub enum OptimizedOp {
     ClearCell, // [+] or [-]
+    /// Sets the current cell to the given value, wrapping around.
+    SetCell(i64), // [-]+++
+    /// Adds the current cell times `factor` to the cell `offset` cells away, wrapping
+    /// around, unless the current cell is 0.
+    MulAdd {
+        offset: isize,
+        factor: i64,
+    }, // [->+>++<<]
+    /// Moves the pointer right by the stride until the current cell is 0.
+    ScanRight(usize), // [>] or [>>>]
+    /// Moves the pointer left by the stride until the current cell is 0.
+    ScanLeft(usize), // [<] or [<<<]
+    /// Adds `delta` to the cell `offset` cells away, wrapping around, without moving
+    /// the pointer.
+    AddAt {
+        offset: isize,
+        delta: i64,
+    }, // >>+<<
+    /// Writes the cell `offset` cells away to output, without moving the pointer.
+    OutputAt(isize), // >>.<<
+}
+
+impl BfOp {
+    pub fn new(kind: BfOpKind, span: Span) -> Self {
+        BfOp { kind, span }
+    }
+}
+
+impl Drop for BfOp {
+    fn drop(&mut self) {
+        // Dropping nested loops recursively would overflow the stack on deeply nested
+        // programs, so the nested bodies are moved onto a heap-allocated worklist instead.
+        if let BfOpKind::Loop(body) = &mut self.kind {
+            if body.iter().all(|op| !matches!(op.kind, BfOpKind::Loop(_))) {
+                return;
+            }
+
+            let mut pending = std::mem::take(body);
+            while let Some(mut op) = pending.pop() {
+                if let BfOpKind::Loop(body) = &mut op.kind {
+                    pending.append(body);
+                }
+            }
+        }
+    }
+}
+
+impl Clone for BfOp {
+    fn clone(&self) -> Self {
+        let BfOpKind::Loop(body) = &self.kind else {
+            return BfOp::new(self.kind.clone(), self.span);
+        };
+
+        // Cloning nested loops recursively would overflow the stack on deeply nested
+        // programs, so the copies of the enclosing bodies are kept on a worklist instead.
+        let mut frames = vec![(body.iter(), Vec::with_capacity(body.len()), self.span)];
+        loop {
+            let (ops, copies, _) = frames.last_mut().expect("The outermost loop is open");
+            let Some(op) = ops.next() else {
+                // End of a body: wrap it up, and either return it or move on in its parent
+                let (_, copies, span) = frames.pop().expect("The outermost loop is open");
+                let copy = BfOp::new(BfOpKind::Loop(copies), span);
+                match frames.last_mut() {
+                    Some((_, enclosing, _)) => enclosing.push(copy),
+                    None => return copy,
+                }
+                continue;
+            };
+            match &op.kind {
+                BfOpKind::Loop(body) => {
+                    frames.push((body.iter(), Vec::with_capacity(body.len()), op.span))
+                }
+                kind => copies.push(BfOp::new(kind.clone(), op.span)),
+            }
+        }
+    }
 }
 
 impl fmt::Display for BfOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+impl fmt::Display for BfOpKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            BfOp::PointerIncrement(offset) => {
+            BfOpKind::PointerIncrement(offset) => {
                 if *offset == 0 {
                     write!(f, "")
                 } else {
@@ -44,14 +132,14 @@ impl fmt::Display for BfOp {
                     }
                 }
             }
-            BfOp::Increment(count) => {
+            BfOpKind::Increment(count) => {
                 if count.0 == 0 {
                     write!(f, "")
                 } else {
                     let (symbol, value) = if count.0 > 0 {
-                        ("+", count.0)
+                        ("+", count.0 as u64)
                     } else {
-                        ("-", count.0.abs())
+                        ("-", count.0.unsigned_abs())
                     };
 
                     if value == 1 {
@@ -61,26 +149,30 @@ impl fmt::Display for BfOp {
                     }
                 }
             }
-            BfOp::OutputByte => write!(f, "."),
-            BfOp::InputByte => write!(f, ","),
-            BfOp::Loop(ops) => {
+            BfOpKind::OutputByte => write!(f, "."),
+            BfOpKind::InputByte => write!(f, ","),
+            BfOpKind::Loop(ops) => {
                 write!(f, "[")?;
                 for op in ops {
                     write!(f, "{}", op)?;
                 }
                 write!(f, "]")
             }
-            #[cfg(feature = "optimizer")]
-            BfOp::Optimized(opt_op) => opt_op.fmt(f),
+            BfOpKind::Optimized(opt_op) => opt_op.fmt(f),
         }
     }
 }
 
-#[cfg(feature = "optimizer")]
 impl fmt::Display for OptimizedOp {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             OptimizedOp::ClearCell => write!(f, "[0]"),
+            OptimizedOp::SetCell(value) => write!(f, "={}", value),
+            OptimizedOp::MulAdd { offset, factor } => write!(f, "[p{:+}+=p*{}]", offset, factor),
+            OptimizedOp::ScanRight(stride) => write!(f, "[>*{}]", stride),
+            OptimizedOp::ScanLeft(stride) => write!(f, "[<*{}]", stride),
+            OptimizedOp::AddAt { offset, delta } => write!(f, "p{:+}+={}", offset, delta),
+            OptimizedOp::OutputAt(offset) => write!(f, "p{:+}.", offset),
         }
     }
 }
@@ -1,4 +1,5 @@
-use crate::{lexer::Token, parser::BfOp, parser::ParseError};
+use crate::lexer::{Span, Token, TokenKind};
+use crate::parser::{BfOp, BfOpKind, ParseError};
 use std::collections::VecDeque;
 use std::num::Wrapping;
 
@@ -6,179 +7,157 @@ use std::num::Wrapping;
 pub struct Parser {
     /// The tokens to be parsed.
     tokens: VecDeque<Token>,
-    /// The current position in the token stream.
-    position: usize,
+    /// Whether errors are collected instead of aborting the parse.
+    recovering: bool,
+    /// The errors collected so far while recovering.
+    errors: Vec<ParseError>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
         Parser {
             tokens: tokens.into(),
-            position: 0,
+            recovering: false,
+            errors: Vec::new(),
         }
     }
 
+    /// Parses the program, stopping at the first error.
     pub fn parse(&mut self) -> Result<Vec<BfOp>, ParseError> {
-        self.parse_sequence(false, None)
+        self.recovering = false;
+        self.parse_program()
     }
 
-    fn parse_sequence(
-        &mut self,
-        inside_loop: bool,
-        loop_start_pos: Option<usize>,
-    ) -> Result<Vec<BfOp>, ParseError> {
-        let mut ops = Vec::new();
+    /// Parses the program, collecting every error instead of stopping at the first one.
+    ///
+    /// # Details
+    /// A stray `]` is skipped, and a `[` that is never closed becomes a loop over the
+    /// rest of its enclosing sequence. The returned program is a best-effort result meant
+    /// for tooling such as formatters and linters, and should not be executed when any
+    /// errors were reported. Errors are sorted by their position in the source.
+    pub fn parse_recovering(&mut self) -> (Vec<BfOp>, Vec<ParseError>) {
+        self.recovering = true;
+        self.errors.clear();
+
+        let ops = self
+            .parse_program()
+            .expect("Parsing cannot fail in recovering mode");
+
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.sort_by_key(|error| error.span().start);
+        (ops, errors)
+    }
 
-        while !self.tokens.is_empty() {
-            let current_token = self.tokens.front().cloned();
-            match current_token {
-                Some(Token::LoopEnd) => {
-                    if inside_loop {
-                        break;
-                    } else {
-                        self.tokens.pop_front();
-                        return Err(ParseError::UnmatchedLoopEnd {
-                            position: Some(self.position),
-                        });
-                    }
+    /// Reports an error, which aborts the parse unless the parser is recovering.
+    fn report(&mut self, error: ParseError) -> Result<(), ParseError> {
+        if self.recovering {
+            self.errors.push(error);
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+
+    /// Parses the whole token stream into a sequence of operations.
+    ///
+    /// # Details
+    /// Loops are tracked on an explicit stack instead of through recursion,
+    /// so the nesting depth is limited only by the available heap memory.
+    fn parse_program(&mut self) -> Result<Vec<BfOp>, ParseError> {
+        let mut ops = Vec::new();
+        // The enclosing sequences of the open loops, with the span of their `[`
+        let mut open_loops: Vec<(Vec<BfOp>, Span)> = Vec::new();
+
+        while let Some(token) = self.tokens.pop_front() {
+            match token.kind {
+                TokenKind::IncrementPointer | TokenKind::DecrementPointer => {
+                    let (net, span) = self.count_net_pointer_ops(&token);
+                    ops.push(BfOp::new(BfOpKind::PointerIncrement(net), span));
                 }
-                Some(token) => {
-                    self.tokens.pop_front();
-                    self.position += 1;
-
-                    match token {
-                        Token::IncrementPointer | Token::DecrementPointer => {
-                            let net = self.count_net_pointer_ops(token);
-                            if net != 0 {
-                                ops.push(BfOp::PointerIncrement(net));
-                            }
-                        }
-                        Token::IncrementByte | Token::DecrementByte => {
-                            let net = self.count_net_byte_ops(token);
-                            if net != 0 {
-                                ops.push(BfOp::Increment(Wrapping(net)));
-                            }
-                        }
-                        Token::OutputByte => ops.push(BfOp::OutputByte),
-                        Token::InputByte => ops.push(BfOp::InputByte),
-                        Token::LoopStart => {
-                            let loop_start_position = self.position - 1;
-                            let loop_body = self.parse_sequence(true, Some(loop_start_position))?;
-
-                            if self.tokens.pop_front() != Some(Token::LoopEnd) {
-                                return Err(ParseError::UnmatchedLoopStart {
-                                    position: Some(loop_start_position),
-                                });
-                            }
-                            self.position += 1;
-                            ops.push(BfOp::Loop(loop_body));
-                        }
-                        Token::LoopEnd => {
-                            return Err(ParseError::UnmatchedLoopEnd {
-                                position: Some(self.position - 1),
-                            })
-                        }
+                TokenKind::IncrementByte | TokenKind::DecrementByte => {
+                    let (net, span) = self.count_net_byte_ops(&token);
+                    if net.0 != 0 {
+                        ops.push(BfOp::new(BfOpKind::Increment(net), span));
                     }
                 }
-                None => {
-                    if inside_loop {
-                        return Err(ParseError::UnmatchedLoopStart {
-                            position: loop_start_pos,
-                        });
-                    } else {
-                        break;
-                    }
+                TokenKind::OutputByte => ops.push(BfOp::new(BfOpKind::OutputByte, token.span)),
+                TokenKind::InputByte => ops.push(BfOp::new(BfOpKind::InputByte, token.span)),
+                TokenKind::LoopStart => {
+                    open_loops.push((std::mem::take(&mut ops), token.span));
                 }
+                TokenKind::LoopEnd => match open_loops.pop() {
+                    Some((enclosing, start)) => {
+                        let body = std::mem::replace(&mut ops, enclosing);
+                        ops.push(BfOp::new(BfOpKind::Loop(body), start.to(token.span)));
+                    }
+                    None => self.report(ParseError::UnmatchedLoopEnd { span: token.span })?,
+                },
             }
         }
 
+        // Any loop still open at the end of input is unmatched, innermost first
+        while let Some((enclosing, start)) = open_loops.pop() {
+            self.report(ParseError::UnmatchedLoopStart { span: start })?;
+            let body = std::mem::replace(&mut ops, enclosing);
+            ops.push(BfOp::new(BfOpKind::Loop(body), start));
+        }
+
         Ok(ops)
     }
 
-    /// Counts the net pointer operations (increment/decrement) from the front of the queue.
+    /// Counts the pointer operations (increment/decrement) in one direction from the front
+    /// of the queue. Returns the net value and the span covering every folded token.
     ///
     /// # Details
-    /// If an overflow/underflow occurs, the net value is clamped to `isize::MAX` or `isize::MIN`.
-    fn count_net_pointer_ops(&mut self, token: Token) -> isize {
-        let mut net: isize = match token {
-            Token::IncrementPointer => 1,
-            Token::DecrementPointer => -1,
+    /// The net value cannot overflow, since it is bounded by the number of tokens.
+    /// Only the net movement of a run is checked against the tape bounds when executing,
+    /// so a run stops where it changes direction: `<>` at the first cell must still
+    /// underflow. A run in one direction passes every cell its commands would.
+    fn count_net_pointer_ops(&mut self, token: &Token) -> (isize, Span) {
+        let step: isize = match token.kind {
+            TokenKind::IncrementPointer => 1,
+            TokenKind::DecrementPointer => -1,
             _ => unreachable!("Unexpected token type for count_net_pointer_ops"),
         };
+        let mut net = step;
+        let mut span = token.span;
 
-        let mut clamped = None;
         while let Some(next_token) = self.tokens.front() {
-            match next_token {
-                Token::IncrementPointer => {
-                    match net.checked_add(1) {
-                        Some(result) => net = result,
-                        None => clamped = Some(isize::MAX),
-                    }
-                    self.tokens.pop_front();
-                    self.position += 1;
-                }
-                Token::DecrementPointer => {
-                    match net.checked_sub(1) {
-                        Some(result) => net = result,
-                        None => clamped = Some(isize::MIN),
-                    }
-                    self.tokens.pop_front();
-                    self.position += 1;
-                }
-                _ => break,
+            if next_token.kind != token.kind {
+                break;
             }
+            net += step;
+            span = span.to(next_token.span);
+            self.tokens.pop_front();
         }
 
-        if let Some(limit) = clamped {
-            eprintln!(
-                "Warning: Pointer movement overflowed. Clamped to {}.",
-                limit
-            );
-            return limit;
-        }
-
-        net
+        (net, span)
     }
 
     /// Counts the net byte operations (increment/decrement) from the front of the queue.
+    /// Returns the net value and the span covering every folded token.
     ///
     /// # Details
-    /// If an overflow/underflow occurs, the net value is clamped to `i8::MAX` or `i8::MIN`.
-    fn count_net_byte_ops(&mut self, token: Token) -> i8 {
-        let mut net: i8 = match token {
-            Token::IncrementByte => 1,
-            Token::DecrementByte => -1,
+    /// The net value wraps around modulo 2^64. Every supported cell width divides that,
+    /// so folding a run never changes the resulting cell value.
+    fn count_net_byte_ops(&mut self, token: &Token) -> (Wrapping<i64>, Span) {
+        let mut net = match token.kind {
+            TokenKind::IncrementByte => Wrapping(1),
+            TokenKind::DecrementByte => Wrapping(-1),
             _ => unreachable!("Unexpected token type for count_net_byte_ops"),
         };
+        let mut span = token.span;
 
-        let mut clamped = None;
         while let Some(next_token) = self.tokens.front() {
-            match next_token {
-                Token::IncrementByte => {
-                    match net.checked_add(1) {
-                        Some(result) => net = result,
-                        None => clamped = Some(i8::MAX),
-                    }
-                    self.tokens.pop_front();
-                    self.position += 1;
-                }
-                Token::DecrementByte => {
-                    match net.checked_sub(1) {
-                        Some(result) => net = result,
-                        None => clamped = Some(i8::MIN),
-                    }
-                    self.tokens.pop_front();
-                    self.position += 1;
-                }
+            match next_token.kind {
+                TokenKind::IncrementByte => net += 1,
+                TokenKind::DecrementByte => net -= 1,
                 _ => break,
             }
+            span = span.to(next_token.span);
+            self.tokens.pop_front();
         }
 
-        if let Some(limit) = clamped {
-            eprintln!("Warning: Byte operation overflowed. Clamped to {}.", limit);
-            return limit;
-        }
-
-        net
+        (net, span)
     }
 }
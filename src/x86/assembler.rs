@@ -0,0 +1,551 @@
+//! A minimal x86-64 assembler, covering only the instructions the JIT and the assembly
+//! backend emit.
+
+/// A general-purpose 64-bit register, numbered as in the instruction encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub(crate) enum Reg {
+    Rax = 0,
+    Rcx = 1,
+    Rdx = 2,
+    Rbx = 3,
+    Rsp = 4,
+    Rbp = 5,
+    Rsi = 6,
+    Rdi = 7,
+    R8 = 8,
+    R9 = 9,
+    R10 = 10,
+    R11 = 11,
+    R12 = 12,
+    R13 = 13,
+    R14 = 14,
+    R15 = 15,
+}
+
+impl Reg {
+    /// The low three bits, which go into the ModRM or SIB byte.
+    fn low(self) -> u8 {
+        self as u8 & 0b111
+    }
+
+    /// The high bit, which goes into the REX prefix.
+    fn high(self) -> u8 {
+        (self as u8 >> 3) & 1
+    }
+}
+
+/// The register holding the address of the first cell.
+pub(crate) const CELLS: Reg = Reg::R12;
+/// The register holding the index of the current cell.
+pub(crate) const POINTER: Reg = Reg::R13;
+
+/// A condition for conditional jumps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Condition {
+    AboveOrEqual = 0x3,
+    Equal = 0x4,
+    NotEqual = 0x5,
+    BelowOrEqual = 0x6,
+    Above = 0x7,
+    Sign = 0x8,
+    NotSign = 0x9,
+    LessOrEqual = 0xE,
+}
+
+/// A position in the code, which jumps can target before it is bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Label(usize);
+
+/// Accumulates machine code, resolving jumps to labels once every label is bound.
+#[derive(Default)]
+pub(crate) struct Assembler {
+    code: Vec<u8>,
+    /// The position each label is bound to.
+    labels: Vec<Option<usize>>,
+    /// The position of each 32-bit relative jump offset, with the label it targets.
+    fixups: Vec<(usize, Label)>,
+}
+
+impl Assembler {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Binds `label` to the current position.
+    pub(crate) fn bind(&mut self, label: Label) {
+        debug_assert!(self.labels[label.0].is_none(), "Label bound twice");
+        self.labels[label.0] = Some(self.code.len());
+    }
+
+    /// Binds `label` to `position`, which may lie past the end of the code, such as an
+    /// address in a data segment following it.
+    pub(crate) fn bind_to(&mut self, label: Label, position: usize) {
+        debug_assert!(self.labels[label.0].is_none(), "Label bound twice");
+        self.labels[label.0] = Some(position);
+    }
+
+    /// The current position, which is the size of the code so far.
+    pub(crate) fn position(&self) -> usize {
+        self.code.len()
+    }
+
+    /// Emits raw bytes, such as constant data placed among the code.
+    pub(crate) fn data(&mut self, bytes: &[u8]) {
+        self.bytes(bytes);
+    }
+
+    /// Resolves every jump and returns the machine code.
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        for &(position, label) in &self.fixups {
+            let target = self.labels[label.0].expect("Jump to an unbound label");
+            let relative = target as i64 - (position as i64 + 4);
+            let relative = i32::try_from(relative).expect("Jump out of range");
+            self.code[position..position + 4].copy_from_slice(&relative.to_le_bytes());
+        }
+        self.code
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    /// Emits a REX prefix with the given W, R, X and B bits.
+    fn rex(&mut self, w: bool, r: u8, x: u8, b: u8) {
+        self.code.push(0x40 | (w as u8) << 3 | r << 2 | x << 1 | b);
+    }
+
+    /// Emits a 32-bit placeholder offset, resolved by `finish`.
+    fn rel32(&mut self, label: Label) {
+        self.fixups.push((self.code.len(), label));
+        self.bytes(&[0; 4]);
+    }
+
+    /// Emits an instruction between two 64-bit registers: `opcode reg, rm`.
+    fn op_rr(&mut self, opcode: u8, reg: Reg, rm: Reg) {
+        self.rex(true, reg.high(), 0, rm.high());
+        self.bytes(&[opcode, 0b11 << 6 | reg.low() << 3 | rm.low()]);
+    }
+
+    /// Emits an instruction with a `[base + disp]` operand, using the shortest displacement.
+    fn op_rm(&mut self, opcode: &[u8], reg: u8, base: Reg, disp: i32) {
+        self.rex(true, reg >> 3, 0, base.high());
+        self.bytes(opcode);
+        self.memory_operand(reg & 0b111, base.low(), None, disp);
+    }
+
+    /// Emits the ModRM byte, the SIB byte when there is an index, and the displacement.
+    ///
+    /// A base of `rbp` or `r13` always needs a displacement, and a base of `rsp` or `r12`
+    /// always needs a SIB byte, due to how those encodings are reused.
+    fn memory_operand(&mut self, reg: u8, base: u8, index: Option<(u8, u8)>, disp: i32) {
+        let mode = if disp == 0 && base != 0b101 {
+            0b00
+        } else if i8::try_from(disp).is_ok() {
+            0b01
+        } else {
+            0b10
+        };
+
+        match index {
+            Some((index, scale)) => {
+                self.bytes(&[mode << 6 | reg << 3 | 0b100, scale << 6 | index << 3 | base]);
+            }
+            None if base == 0b100 => self.bytes(&[mode << 6 | reg << 3 | 0b100, 0b00_100_100]),
+            None => self.bytes(&[mode << 6 | reg << 3 | base]),
+        }
+
+        match mode {
+            0b01 => self.bytes(&[disp as i8 as u8]),
+            0b10 => self.bytes(&disp.to_le_bytes()),
+            _ => {}
+        }
+    }
+
+    /// Emits an instruction with a 32-bit immediate: `opcode /ext reg, imm32`.
+    fn op_ri(&mut self, ext: u8, reg: Reg, imm: i32) {
+        self.rex(true, 0, 0, reg.high());
+        self.bytes(&[0x81, 0b11 << 6 | ext << 3 | reg.low()]);
+        self.bytes(&imm.to_le_bytes());
+    }
+
+    /// Emits an instruction on a single register: `opcode /ext reg`.
+    fn op_r(&mut self, opcode: u8, ext: u8, reg: Reg) {
+        self.rex(true, 0, 0, reg.high());
+        self.bytes(&[opcode, 0b11 << 6 | ext << 3 | reg.low()]);
+    }
+
+    /// Emits an instruction on the cell `offset` cells away from the current one,
+    /// addressed as `[CELLS + POINTER * width + offset * width]`.
+    ///
+    /// `width` is the size of a cell in bytes, which selects the operand size.
+    /// `byte_opcode` is used for 1-byte cells and `opcode` for every other size.
+    fn op_cell(&mut self, width: u8, byte_opcode: u8, opcode: u8, reg: u8, offset: i32) {
+        if width == 2 {
+            self.code.push(0x66);
+        }
+        self.rex(width == 8, reg >> 3, POINTER.high(), CELLS.high());
+        self.code
+            .push(if width == 1 { byte_opcode } else { opcode });
+        self.cell_operand(reg, width, offset);
+    }
+
+    /// Emits the operand addressing the cell `offset` cells away from the current one.
+    fn cell_operand(&mut self, reg: u8, width: u8, offset: i32) {
+        let scale = width.trailing_zeros() as u8;
+        let disp = offset
+            .checked_mul(width as i32)
+            .expect("Cell offset out of range");
+        self.memory_operand(reg & 0b111, CELLS.low(), Some((POINTER.low(), scale)), disp);
+    }
+
+    /// Emits an immediate of the cell's size, truncating `value`.
+    fn cell_immediate(&mut self, width: u8, value: i64) {
+        match width {
+            1 => self.bytes(&[value as u8]),
+            2 => self.bytes(&(value as u16).to_le_bytes()),
+            _ => self.bytes(&(value as u32).to_le_bytes()),
+        }
+    }
+
+    /// `push reg`
+    pub(crate) fn push(&mut self, reg: Reg) {
+        if reg.high() != 0 {
+            self.rex(false, 0, 0, 1);
+        }
+        self.code.push(0x50 + reg.low());
+    }
+
+    /// `pop reg`
+    pub(crate) fn pop(&mut self, reg: Reg) {
+        if reg.high() != 0 {
+            self.rex(false, 0, 0, 1);
+        }
+        self.code.push(0x58 + reg.low());
+    }
+
+    /// `ret`
+    pub(crate) fn ret(&mut self) {
+        self.code.push(0xC3);
+    }
+
+    /// `mov dst, src`
+    pub(crate) fn mov(&mut self, dst: Reg, src: Reg) {
+        self.op_rr(0x89, src, dst);
+    }
+
+    /// `mov dst, imm64`, using the shortest encoding for the immediate
+    pub(crate) fn mov_imm(&mut self, dst: Reg, imm: i64) {
+        if let Ok(imm) = u32::try_from(imm) {
+            // Writing the 32-bit register clears the upper half
+            if dst.high() != 0 {
+                self.rex(false, 0, 0, 1);
+            }
+            self.code.push(0xB8 + dst.low());
+            self.bytes(&imm.to_le_bytes());
+        } else if let Ok(imm) = i32::try_from(imm) {
+            // Sign-extended from 32 bits
+            self.op_r(0xC7, 0, dst);
+            self.bytes(&imm.to_le_bytes());
+        } else {
+            self.rex(true, 0, 0, dst.high());
+            self.code.push(0xB8 + dst.low());
+            self.bytes(&imm.to_le_bytes());
+        }
+    }
+
+    /// `mov dst, [base + disp]`
+    pub(crate) fn load(&mut self, dst: Reg, base: Reg, disp: i32) {
+        self.op_rm(&[0x8B], dst as u8, base, disp);
+    }
+
+    /// `mov [base + disp], src`
+    #[cfg_attr(not(feature = "jit"), allow(dead_code))]
+    pub(crate) fn store(&mut self, base: Reg, disp: i32, src: Reg) {
+        self.op_rm(&[0x89], src as u8, base, disp);
+    }
+
+    /// `lea dst, [base + disp]`
+    pub(crate) fn lea(&mut self, dst: Reg, base: Reg, disp: i32) {
+        self.op_rm(&[0x8D], dst as u8, base, disp);
+    }
+
+    /// `add dst, src`
+    pub(crate) fn add(&mut self, dst: Reg, src: Reg) {
+        self.op_rr(0x01, src, dst);
+    }
+
+    /// `add dst, imm32`, with the immediate sign-extended
+    pub(crate) fn add_imm(&mut self, dst: Reg, imm: i32) {
+        self.op_ri(0, dst, imm);
+    }
+
+    /// `sub dst, src`
+    pub(crate) fn sub(&mut self, dst: Reg, src: Reg) {
+        self.op_rr(0x29, src, dst);
+    }
+
+    /// `sub dst, imm32`, with the immediate sign-extended
+    pub(crate) fn sub_imm(&mut self, dst: Reg, imm: i32) {
+        self.op_ri(5, dst, imm);
+    }
+
+    /// `imul dst, src`
+    pub(crate) fn imul(&mut self, dst: Reg, src: Reg) {
+        self.rex(true, dst.high(), 0, src.high());
+        self.bytes(&[0x0F, 0xAF, 0b11 << 6 | dst.low() << 3 | src.low()]);
+    }
+
+    /// `imul reg, reg, imm32`, using the short form for 8-bit immediates
+    pub(crate) fn imul_imm(&mut self, reg: Reg, imm: i32) {
+        match i8::try_from(imm) {
+            Ok(imm) => {
+                self.op_rr(0x6B, reg, reg);
+                self.bytes(&[imm as u8]);
+            }
+            Err(_) => {
+                self.op_rr(0x69, reg, reg);
+                self.bytes(&imm.to_le_bytes());
+            }
+        }
+    }
+
+    /// `cmp left, imm32`, with the immediate sign-extended
+    pub(crate) fn cmp_imm(&mut self, left: Reg, imm: i32) {
+        self.op_ri(7, left, imm);
+    }
+
+    /// `neg reg`
+    pub(crate) fn neg(&mut self, reg: Reg) {
+        self.op_r(0xF7, 3, reg);
+    }
+
+    /// `div reg`, dividing `rdx:rax` as unsigned numbers
+    pub(crate) fn div(&mut self, reg: Reg) {
+        self.op_r(0xF7, 6, reg);
+    }
+
+    /// `idiv reg`, dividing `rdx:rax` as signed numbers
+    pub(crate) fn idiv(&mut self, reg: Reg) {
+        self.op_r(0xF7, 7, reg);
+    }
+
+    /// `cqo`, sign-extending `rax` into `rdx`
+    pub(crate) fn cqo(&mut self) {
+        self.bytes(&[0x48, 0x99]);
+    }
+
+    /// `shl reg, count`
+    pub(crate) fn shl(&mut self, reg: Reg, count: u8) {
+        self.op_r(0xC1, 4, reg);
+        self.code.push(count);
+    }
+
+    /// `shr reg, count`
+    pub(crate) fn shr(&mut self, reg: Reg, count: u8) {
+        self.op_r(0xC1, 5, reg);
+        self.code.push(count);
+    }
+
+    /// `movzx dst, byte [base]`
+    pub(crate) fn load_byte(&mut self, dst: Reg, base: Reg) {
+        self.rex(false, dst.high(), 0, base.high());
+        self.bytes(&[0x0F, 0xB6]);
+        self.memory_operand(dst.low(), base.low(), None, 0);
+    }
+
+    /// `mov byte [base], src`, storing the low byte of `src`
+    pub(crate) fn store_byte(&mut self, base: Reg, src: Reg) {
+        // A REX prefix selects `spl` to `dil` rather than `ah` to `bh`
+        self.rex(false, src.high(), 0, base.high());
+        self.code.push(0x88);
+        self.memory_operand(src.low(), base.low(), None, 0);
+    }
+
+    /// `mov byte [base], imm8`
+    pub(crate) fn store_byte_imm(&mut self, base: Reg, imm: u8) {
+        if base.high() != 0 {
+            self.rex(false, 0, 0, 1);
+        }
+        self.code.push(0xC6);
+        self.memory_operand(0, base.low(), None, 0);
+        self.code.push(imm);
+    }
+
+    /// `lea dst, [rip + label]`
+    pub(crate) fn lea_label(&mut self, dst: Reg, label: Label) {
+        self.rex(true, dst.high(), 0, 0);
+        self.bytes(&[0x8D, dst.low() << 3 | 0b101]);
+        self.rel32(label);
+    }
+
+    /// `cmp left, right`
+    pub(crate) fn cmp(&mut self, left: Reg, right: Reg) {
+        self.op_rr(0x39, right, left);
+    }
+
+    /// `test left, right`
+    pub(crate) fn test(&mut self, left: Reg, right: Reg) {
+        self.op_rr(0x85, right, left);
+    }
+
+    /// `xor dst, src`
+    pub(crate) fn xor(&mut self, dst: Reg, src: Reg) {
+        self.op_rr(0x31, src, dst);
+    }
+
+    /// `call [base + disp]`
+    #[cfg_attr(not(feature = "jit"), allow(dead_code))]
+    pub(crate) fn call_indirect(&mut self, base: Reg, disp: i32) {
+        if base.high() != 0 {
+            self.rex(false, 0, 0, 1);
+        }
+        self.code.push(0xFF);
+        self.memory_operand(2, base.low(), None, disp);
+    }
+
+    /// `call label`
+    pub(crate) fn call(&mut self, label: Label) {
+        self.code.push(0xE8);
+        self.rel32(label);
+    }
+
+    /// `syscall`
+    pub(crate) fn syscall(&mut self) {
+        self.bytes(&[0x0F, 0x05]);
+    }
+
+    /// `std`, making string instructions run backwards
+    pub(crate) fn std(&mut self) {
+        self.code.push(0xFD);
+    }
+
+    /// `cld`, making string instructions run forwards
+    pub(crate) fn cld(&mut self) {
+        self.code.push(0xFC);
+    }
+
+    /// `rep movsb`, copying `rcx` bytes from `[rsi]` to `[rdi]`
+    pub(crate) fn rep_movsb(&mut self) {
+        self.bytes(&[0xF3, 0xA4]);
+    }
+
+    /// `rep stosb`, filling `rcx` bytes at `[rdi]` with `al`
+    pub(crate) fn rep_stosb(&mut self) {
+        self.bytes(&[0xF3, 0xAA]);
+    }
+
+    /// `jmp label`
+    pub(crate) fn jmp(&mut self, label: Label) {
+        self.code.push(0xE9);
+        self.rel32(label);
+    }
+
+    /// `jcc label`
+    pub(crate) fn jcc(&mut self, condition: Condition, label: Label) {
+        self.bytes(&[0x0F, 0x80 | condition as u8]);
+        self.rel32(label);
+    }
+
+    /// `add cell, imm`, wrapping `delta` to the cell's size.
+    ///
+    /// 64-bit deltas that do not fit in 32 bits go through `rax`.
+    pub(crate) fn add_cell(&mut self, width: u8, offset: i32, delta: i64) {
+        if width == 8 && i32::try_from(delta).is_err() {
+            self.mov_imm(Reg::Rax, delta);
+            self.op_cell(width, 0x00, 0x01, Reg::Rax as u8, offset);
+        } else if width != 1 && i8::try_from(sign_extend(width, delta)).is_ok() {
+            // Short form with a sign-extended 8-bit immediate
+            self.op_cell(width, 0x80, 0x83, 0, offset);
+            self.bytes(&[delta as i8 as u8]);
+        } else {
+            self.op_cell(width, 0x80, 0x81, 0, offset);
+            self.cell_immediate(width, delta);
+        }
+    }
+
+    /// `mov cell, imm`, wrapping `value` to the cell's size.
+    ///
+    /// 64-bit values that do not fit in 32 bits go through `rax`.
+    pub(crate) fn set_cell(&mut self, width: u8, offset: i32, value: i64) {
+        if width == 8 && i32::try_from(value).is_err() {
+            self.mov_imm(Reg::Rax, value);
+            self.store_cell(width, offset, Reg::Rax);
+        } else {
+            self.op_cell(width, 0xC6, 0xC7, 0, offset);
+            self.cell_immediate(width, value);
+        }
+    }
+
+    /// `mov cell, src`, storing the low bytes of `src`.
+    pub(crate) fn store_cell(&mut self, width: u8, offset: i32, src: Reg) {
+        self.op_cell(width, 0x88, 0x89, src as u8, offset);
+    }
+
+    /// `add cell, src`, adding the low bytes of `src`.
+    pub(crate) fn add_cell_reg(&mut self, width: u8, offset: i32, src: Reg) {
+        self.op_cell(width, 0x00, 0x01, src as u8, offset);
+    }
+
+    /// `movzx dst, byte cell`, loading the low byte of the cell.
+    pub(crate) fn load_cell_byte(&mut self, dst: Reg, width: u8, offset: i32) {
+        self.rex(false, dst.high(), POINTER.high(), CELLS.high());
+        self.bytes(&[0x0F, 0xB6]);
+        self.cell_operand(dst as u8, width, offset);
+    }
+
+    /// `movzx dst, cell` or `mov dst, cell`, zero-extending the whole cell.
+    pub(crate) fn load_cell(&mut self, dst: Reg, width: u8, offset: i32) {
+        self.rex(width == 8, dst.high(), POINTER.high(), CELLS.high());
+        match width {
+            1 => self.bytes(&[0x0F, 0xB6]),
+            2 => self.bytes(&[0x0F, 0xB7]),
+            _ => self.code.push(0x8B),
+        }
+        self.cell_operand(dst as u8, width, offset);
+    }
+
+    /// Loads the cell times `factor` into `rax`, which is correct in the low bytes.
+    ///
+    /// 64-bit factors that do not fit in 32 bits go through `rcx`.
+    pub(crate) fn mul_cell(&mut self, width: u8, offset: i32, factor: i64) {
+        self.load_cell(Reg::Rax, width, offset);
+        match mul_cell_factor(width, factor) {
+            1 => {}
+            factor => match i32::try_from(factor) {
+                Ok(factor) => self.imul_imm(Reg::Rax, factor),
+                Err(_) => {
+                    self.mov_imm(Reg::Rcx, factor);
+                    self.imul(Reg::Rax, Reg::Rcx);
+                }
+            },
+        }
+    }
+
+    /// `cmp cell, 0`
+    pub(crate) fn cmp_cell_zero(&mut self, width: u8, offset: i32) {
+        self.op_cell(width, 0x80, 0x83, 7, offset);
+        self.bytes(&[0]);
+    }
+}
+
+/// The factor `mul_cell` multiplies by, which only needs to be right modulo the cell size,
+/// so it fits in 32 bits for cells of up to 4 bytes.
+pub(crate) fn mul_cell_factor(width: u8, factor: i64) -> i64 {
+    if width < 8 {
+        factor as i32 as i64
+    } else {
+        factor
+    }
+}
+
+/// Truncates `value` to `width` bytes, then sign-extends it back to 64 bits.
+fn sign_extend(width: u8, value: i64) -> i64 {
+    let unused = 64 - 8 * width as u32;
+    (value << unused) >> unused
+}
@@ -0,0 +1,6 @@
+//! x86-64 machine code, shared by the JIT compiler and the assembly backend.
+
+mod assembler;
+
+pub(crate) use assembler::mul_cell_factor;
+pub(crate) use assembler::{Assembler, Condition, Label, Reg, CELLS, POINTER};
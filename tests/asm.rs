@@ -0,0 +1,139 @@
+#![cfg(all(target_arch = "x86_64", target_os = "linux"))]
+
+mod common;
+
+use bf_rs::{
+    codegen::{emit_elf, emit_gas, CodegenOptions},
+    interpreter::{CellWidth, EofPolicy, TapeConfig},
+    optimizer::Optimizer,
+    parser::BfOp,
+};
+use common::{interpret, parse, run_binary};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+/// Writes `program` as an executable named `name` and runs it with `input`, returning
+/// the output and the error message it printed.
+fn execute(
+    name: &str,
+    program: &[BfOp],
+    options: &CodegenOptions,
+    input: &[u8],
+) -> (Vec<u8>, String) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("asm");
+    fs::create_dir_all(&dir).expect("Failed to create the test directory");
+
+    let executable = dir.join(name);
+    fs::write(&executable, emit_elf(program, options)).expect("Failed to write the program");
+    fs::set_permissions(&executable, fs::Permissions::from_mode(0o755))
+        .expect("Failed to make the program executable");
+
+    run_binary(&executable, input)
+}
+
+#[test]
+fn gas_is_well_formed() {
+    let program = parse(include_str!("../examples/hello_world.bf"));
+    let gas = emit_gas(&program, &CodegenOptions::default());
+
+    assert!(gas.contains(".intel_syntax noprefix"));
+    assert!(gas.contains(".globl _start"));
+    assert!(gas.contains("_start:"));
+    assert!(gas.contains("syscall"));
+    assert!(gas
+        .trim_end()
+        .ends_with(".section .note.GNU-stack,\"\",@progbits"));
+}
+
+#[test]
+fn elf_matches_interpreter() {
+    let cases = [
+        (
+            include_str!("../examples/hello_world.bf"),
+            CodegenOptions::default(),
+        ),
+        ("+.<", CodegenOptions::default()),
+        (
+            ">>>.<",
+            CodegenOptions {
+                tape: TapeConfig::Fixed { cells: 3 },
+                ..Default::default()
+            },
+        ),
+        (
+            "<<<+.>>>>>>>>+.<<<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Wrapping { cells: 7 },
+                ..Default::default()
+            },
+        ),
+        (
+            "+[>+.]",
+            CodegenOptions {
+                tape: TapeConfig::Growing {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            "+[<+.]",
+            CodegenOptions {
+                tape: TapeConfig::Bidirectional {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            ",.,.,.,.",
+            CodegenOptions {
+                eof_policy: EofPolicy::Error,
+                ..Default::default()
+            },
+        ),
+        (
+            ",+[-.,+]>,+.",
+            CodegenOptions {
+                eof_policy: EofPolicy::MinusOne,
+                ..Default::default()
+            },
+        ),
+        (
+            "-.>+++++++++++++++[-<+++++++++++++++++>]<.[-]+.",
+            CodegenOptions::default(),
+        ),
+    ];
+
+    for (index, (source, options)) in cases.into_iter().enumerate() {
+        let program = parse(source);
+        let program = Optimizer::new().optimize(program);
+        for cell_width in [
+            CellWidth::U8,
+            CellWidth::U16,
+            CellWidth::U32,
+            CellWidth::U64,
+        ] {
+            let options = CodegenOptions {
+                cell_width,
+                ..options
+            };
+            let expected = match cell_width {
+                CellWidth::U8 => interpret::<u8>(&program, &options, b"elf"),
+                CellWidth::U16 => interpret::<u16>(&program, &options, b"elf"),
+                CellWidth::U32 => interpret::<u32>(&program, &options, b"elf"),
+                CellWidth::U64 => interpret::<u64>(&program, &options, b"elf"),
+            };
+            let name = format!("case_{}_{}", index, cell_width);
+            let actual = execute(&name, &program, &options, b"elf");
+            assert_eq!(
+                expected, actual,
+                "`{}` behaves differently with {}-bit cells",
+                source, cell_width
+            );
+        }
+    }
+}
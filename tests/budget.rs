@@ -0,0 +1,136 @@
+mod common;
+
+use bf_rs::{
+    bytecode::Bytecode,
+    interpreter::{Interpreter, InterpreterError},
+    optimizer::{OptimizationLevel, Optimizer},
+    parser::BfOp,
+};
+use common::{execute, parse};
+use std::time::Duration;
+
+/// Runs `source` with `fuel`, returning the output and the error.
+fn run(source: &str, fuel: u64) -> (Vec<u8>, Option<InterpreterError>) {
+    run_program(&parse(source), fuel)
+}
+
+fn run_program(program: &[BfOp], fuel: u64) -> (Vec<u8>, Option<InterpreterError>) {
+    execute(|| Interpreter::new().with_fuel(Some(fuel)), program, b"")
+}
+
+/// The least fuel `source` can run to the end with.
+fn cost(source: &str) -> u64 {
+    (0..1000)
+        .find(|&fuel| run(source, fuel).1.is_none())
+        .unwrap_or_else(|| panic!("`{}` costs more than 1000", source))
+}
+
+#[test]
+fn folded_commands_cost_one_each() {
+    assert_eq!(cost(""), 0);
+    assert_eq!(cost("+++++"), 5);
+    assert_eq!(cost(">>><<-."), 7);
+    // One check to enter the loop, then one after each of the 2 iterations
+    assert_eq!(cost("++[>+<-]"), 2 + 3 + 2 * 4);
+}
+
+#[test]
+fn optimized_programs_are_charged_as_they_run() {
+    let program = parse("++++++++++[-]+[>+<-]");
+    let optimized = Optimizer::with_level(OptimizationLevel::O1).optimize(program.clone());
+
+    // The increments and the clear loop cost 31 units, but setting the cell instead costs 1
+    assert!(matches!(
+        run_program(&program, 20).1,
+        Some(InterpreterError::OutOfFuel { executed: 20, .. })
+    ));
+    assert!(run_program(&optimized, 7).1.is_none());
+    assert!(matches!(
+        run_program(&optimized, 6).1,
+        Some(InterpreterError::OutOfFuel { .. })
+    ));
+}
+
+#[test]
+fn running_out_stops_before_the_operation() {
+    let (output, error) = run("++.\n+.", 4);
+    assert_eq!(output, [2]);
+
+    let error = error.expect("Execution did not run out of fuel");
+    assert!(matches!(
+        error,
+        InterpreterError::OutOfFuel { executed: 4, span } if span.line == 2 && span.column == 2
+    ));
+    assert_eq!(
+        error.to_string(),
+        "Out of fuel at line 2, column 2 after executing 4 operations"
+    );
+
+    // Endless loops run out as well
+    assert!(matches!(
+        run("+[]", 1000).1,
+        Some(InterpreterError::OutOfFuel { executed: 1000, .. })
+    ));
+}
+
+#[test]
+fn each_execution_gets_the_full_fuel() {
+    let program = parse("+++.");
+    let bytecode = Bytecode::compile(&program);
+    let mut interpreter = Interpreter::new().with_fuel(Some(4));
+    for _ in 0..3 {
+        let mut output = Vec::new();
+        interpreter
+            .execute(&program, &mut output, &mut &b""[..])
+            .expect("Execution failed");
+        interpreter
+            .execute_bytecode(&bytecode, &mut output, &mut &b""[..])
+            .expect("Bytecode execution failed");
+        // The memory is kept between executions
+        assert_eq!(output.len(), 2);
+    }
+}
+
+#[test]
+fn endless_loops_time_out() {
+    let program = parse("+\n[]");
+    let interpreter = || Interpreter::new().with_timeout(Some(Duration::from_millis(20)));
+
+    let results = [
+        interpreter().execute(&program, &mut Vec::new(), &mut &b""[..]),
+        interpreter().execute_bytecode(
+            &Bytecode::compile(&program),
+            &mut Vec::new(),
+            &mut &b""[..],
+        ),
+    ];
+    for result in results {
+        // The number of operations executed depends on the speed of the machine
+        let error = result.expect_err("Execution did not time out");
+        assert!(matches!(
+            error,
+            InterpreterError::Timeout { executed, span } if executed > 0 && span.line == 2
+        ));
+        assert!(error
+            .to_string()
+            .starts_with("Timed out at line 2, column "));
+    }
+}
+
+#[test]
+fn programs_within_the_limits_are_unaffected() {
+    let source = include_str!("../examples/hello_world.bf");
+    let (unlimited, _) = execute(Interpreter::new, &parse(source), b"");
+    let (limited, error) = execute(
+        || {
+            Interpreter::new()
+                .with_fuel(Some(u64::MAX - 1))
+                .with_timeout(Some(Duration::from_secs(60)))
+        },
+        &parse(source),
+        b"",
+    );
+    assert!(error.is_none(), "Execution failed: {:?}", error);
+    assert_eq!(limited, unlimited);
+    assert_eq!(limited, b"Hello World!\n");
+}
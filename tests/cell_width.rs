@@ -0,0 +1,86 @@
+mod common;
+
+use bf_rs::{
+    interpreter::{Cell, CellWidth, Interpreter},
+    lexer::Span,
+    optimizer::Optimizer,
+    parser::{BfOp, BfOpKind},
+};
+use common::{execute, parse};
+use std::num::Wrapping;
+
+/// Runs `program` with `input` on cells of each width, from 8 to 64 bits, returning the
+/// output of each.
+fn outputs(program: &[BfOp], input: &[u8]) -> [Vec<u8>; 4] {
+    fn output<C: Cell>(program: &[BfOp], input: &[u8]) -> Vec<u8> {
+        let (output, error) = execute(Interpreter::<C>::default, program, input);
+        assert!(error.is_none(), "Execution failed: {:?}", error);
+        output
+    }
+
+    [
+        output::<u8>(program, input),
+        output::<u16>(program, input),
+        output::<u32>(program, input),
+        output::<u64>(program, input),
+    ]
+}
+
+/// Adds `delta` to the first cell, then prints 1 if it is not 0, and 0 otherwise.
+fn nonzero_after_adding(delta: i64) -> Vec<BfOp> {
+    let mut program = vec![BfOp::new(
+        BfOpKind::Increment(Wrapping(delta)),
+        Span::default(),
+    )];
+    program.extend(parse("[>+<[-]]>."));
+    // Clears the cell at once, whatever its value
+    Optimizer::new().optimize(program)
+}
+
+#[test]
+fn cells_wrap_at_their_width() {
+    assert_eq!(
+        outputs(&nonzero_after_adding(1 << 8), b""),
+        [[0], [1], [1], [1]]
+    );
+    assert_eq!(
+        outputs(&nonzero_after_adding(1 << 16), b""),
+        [[0], [0], [1], [1]]
+    );
+    assert_eq!(
+        outputs(&nonzero_after_adding(1 << 32), b""),
+        [[0], [0], [0], [1]]
+    );
+}
+
+#[test]
+fn io_maps_cells_to_bytes() {
+    // Output writes the lowest 8 bits, here of the maximum of each width
+    assert_eq!(outputs(&parse("-."), b""), [[255], [255], [255], [255]]);
+    assert_eq!(
+        outputs(&parse(&format!("{}.", "+".repeat(300))), b""),
+        [[44], [44], [44], [44]]
+    );
+
+    // Input is zero-extended, so it only wraps back to 0 in 8-bit cells
+    assert_eq!(outputs(&parse(",."), b"\xc8"), [[200], [200], [200], [200]]);
+    assert_eq!(
+        outputs(&parse(",+[>+<[-]]>."), b"\xff"),
+        [[0], [1], [1], [1]]
+    );
+}
+
+#[test]
+fn widths_are_parsed_from_their_bits() {
+    for (text, width) in [
+        ("8", CellWidth::U8),
+        ("16", CellWidth::U16),
+        ("32", CellWidth::U32),
+        ("64", CellWidth::U64),
+    ] {
+        assert_eq!(text.parse::<CellWidth>(), Ok(width));
+        assert_eq!(width.to_string(), text);
+        assert_eq!(width.bits().to_string(), text);
+    }
+    assert!("12".parse::<CellWidth>().is_err());
+}
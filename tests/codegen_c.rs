@@ -0,0 +1,198 @@
+mod common;
+
+use bf_rs::{
+    codegen::{emit_c, CodegenOptions},
+    interpreter::{CellWidth, EofPolicy, TapeConfig},
+    optimizer::Optimizer,
+    parser::BfOp,
+};
+use common::{examples, interpret, parse, run_binary};
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Whether `cc` can be run, as the tests are skipped without a C compiler.
+fn has_c_compiler() -> bool {
+    let found = Command::new("cc")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !found {
+        eprintln!("No C compiler found, skipping the test");
+    }
+    found
+}
+
+/// Compiles `program` to C, builds it into an executable named `name` and runs it with
+/// `input`, returning the output and the error message it printed.
+fn compile_and_run(
+    name: &str,
+    program: &[BfOp],
+    options: &CodegenOptions,
+    input: &[u8],
+) -> (Vec<u8>, String) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("codegen_c");
+    fs::create_dir_all(&dir).expect("Failed to create the test directory");
+
+    let source_path = dir.join(format!("{}.c", name));
+    fs::write(&source_path, emit_c(program, options)).expect("Failed to write the program");
+    let executable = dir.join(name);
+    let status = Command::new("cc")
+        .args(["-std=c99", "-O1", "-Wall", "-Wextra", "-Werror", "-o"])
+        .arg(&executable)
+        .arg(&source_path)
+        .status()
+        .expect("Failed to run cc");
+    assert!(
+        status.success(),
+        "The generated code for `{}` does not compile",
+        name
+    );
+
+    run_binary(&executable, input)
+}
+
+#[test]
+fn examples_match_interpreter() {
+    if !has_c_compiler() {
+        return;
+    }
+
+    for (path, source) in examples() {
+        let program = Optimizer::new().optimize(parse(&source));
+        let options = CodegenOptions::default();
+
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let expected = interpret::<u8>(&program, &options, b"");
+        let actual = compile_and_run(&name, &program, &options, b"");
+        assert_eq!(expected, actual, "{} behaves differently", path.display());
+    }
+}
+
+#[test]
+fn edge_cases_match_interpreter() {
+    if !has_c_compiler() {
+        return;
+    }
+
+    let cases = [
+        ("+.<", CodegenOptions::default()),
+        (
+            ">>>.<",
+            CodegenOptions {
+                tape: TapeConfig::Fixed { cells: 3 },
+                ..Default::default()
+            },
+        ),
+        (
+            "<<<+.>>>>>>>>+.<<<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Wrapping { cells: 7 },
+                ..Default::default()
+            },
+        ),
+        (
+            "+[>+.]",
+            CodegenOptions {
+                tape: TapeConfig::Growing {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            "+[<+.]",
+            CodegenOptions {
+                tape: TapeConfig::Bidirectional {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            ",.,.,.,.",
+            CodegenOptions {
+                eof_policy: EofPolicy::Error,
+                ..Default::default()
+            },
+        ),
+        (
+            ",+[-.,+]>,+.",
+            CodegenOptions {
+                eof_policy: EofPolicy::MinusOne,
+                ..Default::default()
+            },
+        ),
+        (
+            "-.>+++++++++++++++[-<+++++++++++++++++>]<.[-]+.",
+            CodegenOptions::default(),
+        ),
+        // Cells at an offset outside the tape
+        (
+            ">>>+<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Fixed { cells: 3 },
+                ..Default::default()
+            },
+        ),
+        (
+            "+>>>>>>>>++<<<<<<<<.>.",
+            CodegenOptions {
+                tape: TapeConfig::Wrapping { cells: 7 },
+                ..Default::default()
+            },
+        ),
+        (
+            "++[->>>>>>+<<<<<<]>>>>>>.",
+            CodegenOptions {
+                tape: TapeConfig::Growing {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            "++[-<<<<+>>>>]<<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Bidirectional {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+    ];
+
+    for (index, (source, options)) in cases.into_iter().enumerate() {
+        let program = Optimizer::new().optimize(parse(source));
+        for cell_width in [
+            CellWidth::U8,
+            CellWidth::U16,
+            CellWidth::U32,
+            CellWidth::U64,
+        ] {
+            let options = CodegenOptions {
+                cell_width,
+                ..options
+            };
+            let expected = match cell_width {
+                CellWidth::U8 => interpret::<u8>(&program, &options, b"c"),
+                CellWidth::U16 => interpret::<u16>(&program, &options, b"c"),
+                CellWidth::U32 => interpret::<u32>(&program, &options, b"c"),
+                CellWidth::U64 => interpret::<u64>(&program, &options, b"c"),
+            };
+            let name = format!("case_{}_{}", index, cell_width);
+            let actual = compile_and_run(&name, &program, &options, b"c");
+            assert_eq!(
+                expected, actual,
+                "`{}` behaves differently with {}-bit cells",
+                source, cell_width
+            );
+        }
+    }
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use bf_rs::{
+    codegen::{rust_from_source, CodegenOptions},
+    interpreter::{EofPolicy, TapeConfig},
+    optimizer::Optimizer,
+};
+use common::{interpret, parse, run_binary};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Compiles `source` to Rust, builds it into an executable calling `run` on the standard
+/// streams, and returns the output and error message of running it with `input`.
+fn compile_and_run(
+    name: &str,
+    source: &str,
+    options: &CodegenOptions,
+    input: &[u8],
+) -> (Vec<u8>, String) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("codegen_rust");
+    fs::create_dir_all(&dir).expect("Failed to create the test directory");
+
+    let program = rust_from_source(source, options).expect("Parsing failed");
+    fs::write(dir.join(format!("{}.rs", name)), program).expect("Failed to write the program");
+    let main = format!(
+        "mod program {{
+    include!(\"{name}.rs\");
+}}
+
+fn main() {{
+    let result = program::run(&mut std::io::stdin().lock(), &mut std::io::stdout().lock());
+    if let Err(e) = result {{
+        eprintln!(\"Runtime error: {{}}\", e);
+        std::process::exit(1);
+    }}
+}}
+"
+    );
+    let main_path = dir.join(format!("{}_main.rs", name));
+    fs::write(&main_path, main).expect("Failed to write the wrapper");
+
+    let executable = dir.join(name);
+    let status = Command::new("rustc")
+        .args(["--edition=2021", "-D", "warnings", "-o"])
+        .arg(&executable)
+        .arg(&main_path)
+        .status()
+        .expect("Failed to run rustc");
+    assert!(
+        status.success(),
+        "The generated code for `{}` does not compile",
+        name
+    );
+
+    run_binary(&executable, input)
+}
+
+#[test]
+fn generated_rust_matches_interpreter() {
+    let cases = [
+        (
+            "hello_world",
+            include_str!("../examples/hello_world.bf"),
+            CodegenOptions::default(),
+        ),
+        ("underflow", "+.<", CodegenOptions::default()),
+        (
+            "wrapping",
+            "<<<+.>>>>>>>>+.<<<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Wrapping { cells: 7 },
+                ..Default::default()
+            },
+        ),
+        (
+            "bidirectional",
+            "+[<+.]",
+            CodegenOptions {
+                tape: TapeConfig::Bidirectional {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            "eof_error",
+            ",.,.,.,.",
+            CodegenOptions {
+                eof_policy: EofPolicy::Error,
+                ..Default::default()
+            },
+        ),
+        (
+            "clear",
+            "-.>+++++++++++++++[-<+++++++++++++++++>]<.[-]+.",
+            CodegenOptions::default(),
+        ),
+        // Cells at an offset outside the tape
+        (
+            "add_at_overflow",
+            ">>>+<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Fixed { cells: 3 },
+                ..Default::default()
+            },
+        ),
+        (
+            "add_at_wrapping",
+            "+>>>>>>>>++<<<<<<<<.>.",
+            CodegenOptions {
+                tape: TapeConfig::Wrapping { cells: 7 },
+                ..Default::default()
+            },
+        ),
+        (
+            "mul_add_growing",
+            "++[->>>>>>+<<<<<<]>>>>>>.",
+            CodegenOptions {
+                tape: TapeConfig::Growing {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            "mul_add_bidirectional",
+            "++[-<<<<+>>>>]<<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Bidirectional {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+    ];
+
+    for (name, source, options) in cases {
+        // Optimized like `rust_from_source` does
+        let program = Optimizer::new().optimize(parse(source));
+        let expected = interpret::<u8>(&program, &options, b"rs");
+        let actual = compile_and_run(name, source, &options, b"rs");
+        assert_eq!(expected, actual, "`{}` behaves differently", name);
+    }
+}
@@ -0,0 +1,154 @@
+//! Helpers shared by the integration tests.
+
+// Each test file is its own crate and only uses some of these
+#![allow(dead_code)]
+
+use bf_rs::{
+    bytecode::Bytecode,
+    codegen::CodegenOptions,
+    interpreter::{Cell, Interpreter, InterpreterError, TapeConfig},
+    lexer::Lexer,
+    parser::{BfOp, BfOpKind, Parser},
+};
+use std::fs;
+use std::io::Write;
+use std::mem::{discriminant, Discriminant};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A small xorshift generator, so the tests are reproducible without extra dependencies.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+pub fn parse(source: &str) -> Vec<BfOp> {
+    let tokens = Lexer::new(source).tokenize();
+    Parser::new(tokens).parse().expect("Parsing failed")
+}
+
+pub fn kinds(program: &[BfOp]) -> Vec<BfOpKind> {
+    program.iter().map(|op| op.kind.clone()).collect()
+}
+
+/// Runs `program` with `input` on interpreters configured by `interpreter`, returning
+/// the output and the error.
+///
+/// The bytecode VM must agree with the tree-walking interpreter.
+pub fn execute<C: Cell>(
+    interpreter: impl Fn() -> Interpreter<C>,
+    program: &[BfOp],
+    input: &[u8],
+) -> (Vec<u8>, Option<InterpreterError>) {
+    let mut output = Vec::new();
+    let result = interpreter().execute(program, &mut output, &mut &input[..]);
+
+    let mut vm_output = Vec::new();
+    let vm_result = interpreter().execute_bytecode(
+        &Bytecode::compile(program),
+        &mut vm_output,
+        &mut &input[..],
+    );
+    assert_eq!(output, vm_output);
+    assert_eq!(
+        result.as_ref().err().map(ToString::to_string),
+        vm_result.err().map(|e| e.to_string())
+    );
+
+    (output, result.err())
+}
+
+/// Runs `program` on `tape` without input, returning the output and the error.
+pub fn run<C: Cell>(program: &[BfOp], tape: TapeConfig) -> (Vec<u8>, Option<InterpreterError>) {
+    execute(|| Interpreter::<C>::default().with_tape(tape), program, b"")
+}
+
+/// Like [`run`], but only returning the kind of error. Optimized code reports errors for
+/// a whole block or loop at once, so their positions are not compared.
+pub fn run_kind<C: Cell>(
+    program: &[BfOp],
+    tape: TapeConfig,
+) -> (Vec<u8>, Option<Discriminant<InterpreterError>>) {
+    let (output, error) = run::<C>(program, tape);
+    (output, error.as_ref().map(discriminant))
+}
+
+/// Runs `program` on the tree-walking interpreter, configured like the code generated
+/// with `options`, returning the output and the error message.
+pub fn interpret<C: Cell>(
+    program: &[BfOp],
+    options: &CodegenOptions,
+    input: &[u8],
+) -> (Vec<u8>, String) {
+    let mut interpreter = Interpreter::<C>::default()
+        .with_tape(options.tape)
+        .with_eof_policy(options.eof_policy);
+    let mut output = Vec::new();
+    let result = interpreter.execute(program, &mut output, &mut &input[..]);
+    (
+        output,
+        result.err().map(|e| e.to_string()).unwrap_or_default(),
+    )
+}
+
+/// Runs the compiled program at `executable` with `input`, returning the output and the
+/// error message it printed, which must come with a failing exit status.
+pub fn run_binary(executable: &Path, input: &[u8]) -> (Vec<u8>, String) {
+    run_command(&mut Command::new(executable), input)
+}
+
+/// Like [`run_binary`], but running the program through `command`.
+pub fn run_command(command: &mut Command, input: &[u8]) -> (Vec<u8>, String) {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run the program");
+    // Programs that never read may exit before the input is written
+    let _ = child.stdin.as_mut().unwrap().write_all(input);
+    let output = child.wait_with_output().expect("Failed to run the program");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let error = stderr
+        .trim_end()
+        .strip_prefix("Runtime error: ")
+        .unwrap_or(&stderr);
+    assert_eq!(
+        output.status.success(),
+        error.is_empty(),
+        "`{:?}` exited with {}",
+        command,
+        output.status
+    );
+    (output.stdout, error.to_string())
+}
+
+/// The example programs, sorted by path, with their source.
+pub fn examples() -> Vec<(PathBuf, String)> {
+    let mut paths: Vec<_> = fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/examples"))
+        .expect("Failed to read the examples")
+        .map(|entry| entry.expect("Failed to read the examples").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "bf"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "No examples found");
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let source = fs::read_to_string(&path).expect("Failed to read an example");
+            (path, source)
+        })
+        .collect()
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use bf_rs::{
+    optimizer::Optimizer,
+    parser::{BfOpKind, OptimizedOp},
+};
+use common::{kinds, parse};
+use std::num::Wrapping;
+
+fn is_loop(kind: &BfOpKind) -> bool {
+    matches!(kind, BfOpKind::Loop(_))
+}
+
+#[test]
+fn skipped_loops_are_removed() {
+    let optimizer = Optimizer::new();
+    let optimize = |source| kinds(&optimizer.optimize(parse(source)));
+
+    // A comment loop at the start of the program, with output before it
+    assert_eq!(
+        optimize(".[comment, with commands.]+"),
+        [BfOpKind::OutputByte, BfOpKind::Increment(Wrapping(1))]
+    );
+
+    // Loops right after another loop or a clear
+    let optimized = optimize(",[.,].[+.][,]");
+    assert_eq!(optimized.len(), 3);
+    assert!(is_loop(&optimized[1]));
+    assert_eq!(optimized[2], BfOpKind::OutputByte);
+    assert_eq!(
+        optimize(",[-].[.,]"),
+        [
+            BfOpKind::InputByte,
+            BfOpKind::Optimized(OptimizedOp::ClearCell),
+            BfOpKind::OutputByte
+        ]
+    );
+
+    // Moving or changing the cell may make it non-zero, as does entering a loop
+    for source in [",[.,]>[.,]", ",[-]+[.,]", ",[[.,]]"] {
+        let optimized = optimize(source);
+        let body = match optimized.last() {
+            Some(BfOpKind::Loop(body)) => kinds(body),
+            _ => panic!("`{}` lost its last loop", source),
+        };
+        if source.contains("[[") {
+            assert!(is_loop(&body[0]), "`{}` lost its inner loop", source);
+        }
+    }
+}
+
+#[test]
+fn stripped_ops_are_reported() {
+    let (optimized, report) = Optimizer::new().optimize_with_report(parse("[.,]+.,[-][>[.]<]"));
+    assert_eq!(optimized.len(), 4);
+
+    let pass = report
+        .passes
+        .iter()
+        .find(|pass| pass.name == "dead-loops")
+        .unwrap();
+    assert_eq!(pass.fires, 2);
+    // Both loops with their bodies, including the nested loop
+    assert_eq!(pass.ops_removed, 3 + 5);
+    assert!(report.to_string().contains("dead-loops: 2 (8 ops removed)"));
+}
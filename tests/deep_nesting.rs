@@ -0,0 +1,52 @@
+use bf_rs::{
+    bytecode::Bytecode, interpreter::Interpreter, lexer::Lexer, optimizer::Optimizer,
+    parser::Parser,
+};
+use std::io;
+
+/// Parsing, running and dropping a 1,000,000-deep loop nest must not overflow the stack.
+#[test]
+fn million_deep_nesting() {
+    const DEPTH: usize = 1_000_000;
+    let source = format!("+{}-{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+
+    let tokens = Lexer::new(&source).tokenize();
+    let program = Parser::new(tokens).parse().expect("Parsing failed");
+
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .execute(&program, &mut io::sink(), &mut io::empty())
+        .expect("Execution failed");
+
+    // The same goes for compiling and running it as bytecode
+    let bytecode = Bytecode::compile(&program);
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .execute_bytecode(&bytecode, &mut io::sink(), &mut io::empty())
+        .expect("Execution failed");
+
+    // And for optimizing it, which only turns the innermost loop into a clear
+    let (optimized, report) = Optimizer::new().optimize_with_report(program);
+    assert_eq!(report.ops_before, DEPTH + 2);
+    assert_eq!(report.ops_removed(), 1);
+    let bytecode = Bytecode::compile(&optimized);
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .execute_bytecode(&bytecode, &mut io::sink(), &mut io::empty())
+        .expect("Execution failed");
+}
+
+/// Removing the dead loop after a deep loop nest keeps a copy of the nest, which must not
+/// overflow the stack either.
+#[test]
+fn million_deep_nesting_before_a_dead_loop() {
+    const DEPTH: usize = 1_000_000;
+    let source = format!(",{}-{}[.]", "[".repeat(DEPTH), "]".repeat(DEPTH));
+
+    let tokens = Lexer::new(&source).tokenize();
+    let program = Parser::new(tokens).parse().expect("Parsing failed");
+
+    let (optimized, report) = Optimizer::new().optimize_with_report(program);
+    assert_eq!(optimized.len(), 2);
+    assert_eq!(report.ops_removed(), 3);
+}
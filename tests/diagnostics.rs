@@ -0,0 +1,196 @@
+use bf_rs::{
+    diagnostics::{Diagnostic, Style},
+    error::BfError,
+    interpreter::{Interpreter, InterpreterError, TapeConfig},
+    lexer::{Lexer, Span},
+    parser::Parser,
+};
+
+/// Parses and runs `source` on `tape`, returning the first error it hits.
+fn error(source: &str, tape: TapeConfig) -> BfError {
+    let tokens = Lexer::new(source).tokenize();
+    let program = match Parser::new(tokens).parse() {
+        Ok(program) => program,
+        Err(e) => return e.into(),
+    };
+    Interpreter::new()
+        .with_tape(tape)
+        .execute(&program, &mut Vec::new(), &mut &b""[..])
+        .expect_err("The program did not fail")
+        .into()
+}
+
+fn render(source: &str, tape: TapeConfig) -> String {
+    Diagnostic::from_error(source, &error(source, tape)).render(source, "test.bf", Style::Plain)
+}
+
+#[test]
+fn unmatched_brackets_point_at_a_likely_partner() {
+    assert_eq!(
+        render("+[>+<-", TapeConfig::default()),
+        "\
+error: unmatched loop start
+ --> test.bf:1:2
+  |
+1 | +[>+<-
+  |  ^ this `[` is never closed
+  |
+  = note: every `[` needs a matching `]` later in the program
+"
+    );
+
+    assert_eq!(
+        render("[[]", TapeConfig::default()),
+        "\
+error: unmatched loop start
+ --> test.bf:1:1
+  |
+1 | [[]
+  | ^ this `[` is never closed
+  |   - perhaps this `]` was meant to close it
+  |
+  = note: every `[` needs a matching `]` later in the program
+"
+    );
+
+    // Labels on distant lines are separated by an ellipsis
+    assert_eq!(
+        render("+\n\n\n[\n>\n]\n]", TapeConfig::default()),
+        "\
+error: unmatched loop end
+ --> test.bf:7:1
+  |
+4 | [
+  | - perhaps this `[` was meant to be closed here
+...
+7 | ]
+  | ^ this `]` has no matching `[`
+  |
+  = note: every `]` needs a matching `[` earlier in the program
+"
+    );
+}
+
+#[test]
+fn moves_off_the_tape_underline_the_whole_run() {
+    assert_eq!(
+        render(">><<<", TapeConfig::default()),
+        "\
+error: pointer underflow
+ --> test.bf:1:3
+  |
+1 | >><<<
+  |   ^^^ moves the pointer 3 cells left from cell 2
+  |
+  = note: the tape starts at cell 0 and cannot grow to the left; see the `--tape` option
+"
+    );
+
+    assert_eq!(
+        render("+>>>", TapeConfig::Fixed { cells: 3 }),
+        "\
+error: pointer overflow
+ --> test.bf:1:2
+  |
+1 | +>>>
+  |  ^^^ moves the pointer 3 cells right from cell 0
+  |
+  = note: the tape has a fixed size; see the `--tape` option
+"
+    );
+}
+
+#[test]
+fn counts_of_one_are_singular() {
+    let label = |source, tape| {
+        let diagnostic = Diagnostic::from_error(source, &error(source, tape));
+        diagnostic.labels[0].message.clone()
+    };
+    assert_eq!(
+        label("<", TapeConfig::default()),
+        "moves the pointer 1 cell left from cell 0"
+    );
+    assert_eq!(
+        label(">", TapeConfig::Fixed { cells: 1 }),
+        "moves the pointer 1 cell right from cell 0"
+    );
+    assert_eq!(
+        label(">", TapeConfig::Growing { initial: 1, max: 1 }),
+        "moves the pointer beyond 1 cell from cell 0"
+    );
+
+    let note = |executed| {
+        let error = BfError::Runtime(InterpreterError::OutOfFuel {
+            executed,
+            span: Span::new(0, 1, 1, 1),
+        });
+        Diagnostic::from_error("+", &error).notes[0].clone()
+    };
+    assert_eq!(
+        note(1),
+        "1 operation was executed before the fuel ran out; see the `--fuel` option"
+    );
+    assert_eq!(
+        note(2),
+        "2 operations were executed before the fuel ran out; see the `--fuel` option"
+    );
+}
+
+#[test]
+fn carets_line_up_after_multi_byte_characters() {
+    assert_eq!(
+        render("héllo wörld: <", TapeConfig::default()),
+        "\
+error: pointer underflow
+ --> test.bf:1:14
+  |
+1 | héllo wörld: <
+  |              ^ moves the pointer 1 cell left from cell 0
+  |
+  = note: the tape starts at cell 0 and cannot grow to the left; see the `--tape` option
+"
+    );
+
+    // Tabs are kept in the padding
+    assert_eq!(
+        render("\tcafé ]", TapeConfig::default()),
+        "\
+error: unmatched loop end
+ --> test.bf:1:7
+  |
+1 | \tcafé ]
+  | \t     ^ this `]` has no matching `[`
+  |
+  = note: every `]` needs a matching `[` earlier in the program
+"
+    );
+}
+
+#[test]
+fn ansi_style_colors_each_part() {
+    let source = "+]";
+    let error = error(source, TapeConfig::default());
+    let diagnostic = Diagnostic::from_error(source, &error);
+
+    assert_eq!(
+        diagnostic.render(source, "test.bf", Style::Ansi),
+        "\x1b[1;31merror\x1b[0m\x1b[1m: unmatched loop end\x1b[0m
+ \x1b[1;34m-->\x1b[0m test.bf:1:2
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m1\x1b[0m \x1b[1;34m|\x1b[0m +]
+  \x1b[1;34m|\x1b[0m  \x1b[1;31m^ this `]` has no matching `[`\x1b[0m
+  \x1b[1;34m|\x1b[0m
+  \x1b[1;34m=\x1b[0m \x1b[1mnote: every `]` needs a matching `[` earlier in the program\x1b[0m
+"
+    );
+    // Without colors, only the escape sequences differ
+    let plain = diagnostic.render(source, "test.bf", Style::Plain);
+    assert!(!plain.contains('\x1b'));
+    let stripped = diagnostic
+        .render(source, "test.bf", Style::Ansi)
+        .replace("\x1b[1;31m", "")
+        .replace("\x1b[1;34m", "")
+        .replace("\x1b[1m", "")
+        .replace("\x1b[0m", "");
+    assert_eq!(stripped, plain);
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use bf_rs::interpreter::{Cell, EofPolicy, Interpreter, InterpreterError};
+use common::{execute, parse};
+
+/// Runs `source` with `input` under `eof_policy`, returning the output and the error.
+fn run<C: Cell>(
+    source: &str,
+    eof_policy: EofPolicy,
+    input: &[u8],
+) -> (Vec<u8>, Option<InterpreterError>) {
+    execute(
+        || Interpreter::<C>::default().with_eof_policy(eof_policy),
+        &parse(source),
+        input,
+    )
+}
+
+#[test]
+fn reads_past_the_end_follow_the_policy() {
+    let output = |eof_policy| {
+        let (output, error) = run::<u8>("+++++,.", eof_policy, b"");
+        assert!(error.is_none(), "Execution failed: {:?}", error);
+        output
+    };
+    assert_eq!(output(EofPolicy::Unchanged), [5]);
+    assert_eq!(output(EofPolicy::Zero), [0]);
+    assert_eq!(output(EofPolicy::MinusOne), [255]);
+
+    // -1 is the maximum of each width, so adding 1 wraps it to 0
+    assert_eq!(run::<u8>(",+.", EofPolicy::MinusOne, b"").0, [0]);
+    assert_eq!(run::<u16>(",+.", EofPolicy::MinusOne, b"").0, [0]);
+    assert_eq!(run::<u64>(",+.", EofPolicy::MinusOne, b"").0, [0]);
+    assert_eq!(run::<u64>(",+.", EofPolicy::Zero, b"").0, [1]);
+}
+
+#[test]
+fn reads_before_the_end_ignore_the_policy() {
+    for eof_policy in [
+        EofPolicy::Unchanged,
+        EofPolicy::Zero,
+        EofPolicy::MinusOne,
+        EofPolicy::Error,
+    ] {
+        let (output, error) = run::<u8>(",.,.", eof_policy, b"ok");
+        assert_eq!(output, b"ok");
+        assert!(error.is_none(), "Execution failed with {}", eof_policy);
+    }
+}
+
+#[test]
+fn error_policy_stops_at_the_read() {
+    let (output, error) = run::<u8>(",.\n,.", EofPolicy::Error, b"x");
+    assert_eq!(output, b"x");
+
+    let error = error.expect("Reading past the end did not fail");
+    assert!(
+        matches!(error, InterpreterError::UnexpectedEof { span } if span.line == 2 && span.column == 1)
+    );
+    assert_eq!(
+        error.to_string(),
+        "Unexpected end of input at line 2, column 1"
+    );
+}
+
+#[test]
+fn policies_are_parsed_from_their_names() {
+    for (text, eof_policy) in [
+        ("unchanged", EofPolicy::Unchanged),
+        ("zero", EofPolicy::Zero),
+        ("minus-one", EofPolicy::MinusOne),
+        ("error", EofPolicy::Error),
+    ] {
+        assert_eq!(text.parse::<EofPolicy>(), Ok(eof_policy));
+        assert_eq!(eof_policy.to_string(), text);
+    }
+    // The values stored are accepted as well
+    assert_eq!("0".parse::<EofPolicy>(), Ok(EofPolicy::Zero));
+    assert_eq!("-1".parse::<EofPolicy>(), Ok(EofPolicy::MinusOne));
+    assert!("255".parse::<EofPolicy>().is_err());
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use bf_rs::{
+    interpreter::{Interpreter, InterpreterError, TapeConfig},
+    lexer::Lexer,
+    parser::Parser,
+};
+use common::Rng;
+use std::io;
+
+/// Number of cells compared after running each program.
+const TAPE_CELLS: usize = 16;
+
+/// Generates runs of `+`, `-`, `<` and `>`, which may well move the pointer off the tape.
+fn random_program(rng: &mut Rng) -> String {
+    let mut source = String::new();
+
+    for _ in 0..rng.below(40) {
+        let command = match rng.below(4) {
+            0 => "+",
+            1 => "-",
+            2 => "<",
+            _ => ">",
+        };
+        let run = match command {
+            "<" | ">" => rng.below(4) as usize + 1,
+            _ => rng.below(600) as usize + 1,
+        };
+        source.push_str(&command.repeat(run));
+    }
+
+    source
+}
+
+/// How a program ends: with the cells and the pointer, or with the error of a move and
+/// the offset of the command making it.
+type Outcome = Result<(Vec<u8>, usize), (&'static str, usize)>;
+
+/// Executes one command at a time without any folding, on a tape of fixed size.
+fn reference_tape(source: &str) -> Outcome {
+    let mut tape = vec![0u8; TAPE_CELLS];
+    let mut pointer = 0;
+
+    for (offset, command) in source.char_indices() {
+        match command {
+            '+' => tape[pointer] = tape[pointer].wrapping_add(1),
+            '-' => tape[pointer] = tape[pointer].wrapping_sub(1),
+            '>' if pointer == TAPE_CELLS - 1 => return Err(("overflow", offset)),
+            '>' => pointer += 1,
+            '<' if pointer == 0 => return Err(("underflow", offset)),
+            '<' => pointer -= 1,
+            _ => unreachable!(),
+        }
+    }
+
+    Ok((tape, pointer))
+}
+
+/// Folding runs of commands in the parser must preserve the final tape state, and fail
+/// on the same command when the pointer leaves the tape.
+#[test]
+fn folding_preserves_tape_state() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let (mut finished, mut failed) = (0, 0);
+
+    for _ in 0..500 {
+        let program = random_program(&mut rng);
+        let expected = reference_tape(&program);
+
+        // Rewind to the first cell and print every compared cell
+        let pointer = expected.as_ref().map_or(0, |(_, pointer)| *pointer);
+        let source = format!(
+            "{}{}{}.",
+            program,
+            "<".repeat(pointer),
+            ".>".repeat(TAPE_CELLS - 1)
+        );
+        let tokens = Lexer::new(&source).tokenize();
+        let ops = Parser::new(tokens).parse().expect("Parsing failed");
+
+        let mut output = Vec::new();
+        let result = Interpreter::new()
+            .with_tape(TapeConfig::Fixed { cells: TAPE_CELLS })
+            .execute(&ops, &mut output, &mut io::empty());
+
+        match (expected, result) {
+            (Ok((tape, _)), Ok(())) => {
+                assert_eq!(output, tape, "tape mismatch for program {:?}", program);
+                finished += 1;
+            }
+            (Err((kind, offset)), Err(error)) => {
+                let (actual, span) = match error {
+                    InterpreterError::PointerUnderflow { span, .. } => ("underflow", span),
+                    InterpreterError::PointerOverflow { span, .. } => ("overflow", span),
+                    error => panic!("unexpected error {} for program {:?}", error, program),
+                };
+                assert_eq!(actual, kind, "wrong error for program {:?}", program);
+                assert!(
+                    (span.start..span.end).contains(&offset),
+                    "error at {:?} instead of {} for program {:?}",
+                    span,
+                    offset,
+                    program
+                );
+                failed += 1;
+            }
+            (expected, result) => panic!(
+                "expected {:?}, got {:?} for program {:?}",
+                expected.map(|_| ()),
+                result,
+                program
+            ),
+        }
+    }
+
+    // Both outcomes must be covered
+    assert!(
+        finished > 50 && failed > 50,
+        "{} finished, {} failed",
+        finished,
+        failed
+    );
+}
+
+/// Moving off the tape and right back must fail like the unfolded commands.
+#[test]
+fn direction_changes_are_not_folded() {
+    for (source, tape) in [
+        ("<>+.", TapeConfig::default()),
+        (">><<<>>+.", TapeConfig::default()),
+        (">><+.", TapeConfig::Fixed { cells: 2 }),
+    ] {
+        let tokens = Lexer::new(source).tokenize();
+        let ops = Parser::new(tokens).parse().expect("Parsing failed");
+        let result =
+            Interpreter::new()
+                .with_tape(tape)
+                .execute(&ops, &mut io::sink(), &mut io::empty());
+        assert!(
+            matches!(
+                result,
+                Err(InterpreterError::PointerUnderflow { .. }
+                    | InterpreterError::PointerOverflow { .. })
+            ),
+            "`{}` did not fail",
+            source
+        );
+    }
+}
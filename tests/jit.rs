@@ -0,0 +1,93 @@
+#![cfg(feature = "jit")]
+
+mod common;
+
+use bf_rs::{
+    interpreter::{Cell, EofPolicy, Interpreter, TapeConfig},
+    jit::JitProgram,
+    optimizer::Optimizer,
+    parser::BfOp,
+};
+use common::{examples, parse};
+
+/// Runs `program` on both backends with the same input, returning the output and the
+/// error message of each.
+fn run_both<C: Cell>(
+    program: &[BfOp],
+    tape: TapeConfig,
+    eof_policy: EofPolicy,
+    input: &[u8],
+) -> [(Vec<u8>, Option<String>); 2] {
+    let interpreter = || {
+        Interpreter::<C>::default()
+            .with_tape(tape)
+            .with_eof_policy(eof_policy)
+    };
+
+    let mut output = Vec::new();
+    let result = interpreter().execute(program, &mut output, &mut &input[..]);
+    let expected = (output, result.err().map(|e| e.to_string()));
+
+    let compiled = JitProgram::<C>::compile(program).expect("Compilation failed");
+    let mut output = Vec::new();
+    let result = interpreter().execute_jit(&compiled, &mut output, &mut &input[..]);
+    let actual = (output, result.err().map(|e| e.to_string()));
+
+    [expected, actual]
+}
+
+#[test]
+fn examples_match_interpreter() {
+    for (path, source) in examples() {
+        let program = Optimizer::new().optimize(parse(&source));
+
+        let [expected, actual] =
+            run_both::<u8>(&program, TapeConfig::default(), EofPolicy::default(), b"");
+        assert_eq!(expected, actual, "{} behaves differently", path.display());
+    }
+}
+
+#[test]
+fn edge_cases_match_interpreter() {
+    // Programs exercising the tape bounds, wide cells, and the I/O callbacks
+    let cases: &[(&str, TapeConfig)] = &[
+        ("<", TapeConfig::default()),
+        (
+            "+[>+]",
+            TapeConfig::Growing {
+                initial: 4,
+                max: 1000,
+            },
+        ),
+        ("+[>+]", TapeConfig::Fixed { cells: 100 }),
+        (
+            "+[<+.]",
+            TapeConfig::Bidirectional {
+                initial: 4,
+                max: 300,
+            },
+        ),
+        ("<<<+.>>>>>>>>+.<<<<<.", TapeConfig::Wrapping { cells: 7 }),
+        (",.,.,.,.", TapeConfig::default()),
+        (",,,,+.", TapeConfig::default()),
+        (
+            "-.>+++++++++++++++[-<+++++++++++++++++>]<.[-]+.",
+            TapeConfig::default(),
+        ),
+    ];
+
+    for &(source, tape) in cases {
+        let program = Optimizer::new().optimize(parse(source));
+        for eof_policy in [EofPolicy::Unchanged, EofPolicy::MinusOne, EofPolicy::Error] {
+            let input = b"jit";
+            let [expected, actual] = run_both::<u8>(&program, tape, eof_policy, input);
+            assert_eq!(expected, actual, "`{}` differs with 8-bit cells", source);
+            let [expected, actual] = run_both::<u16>(&program, tape, eof_policy, input);
+            assert_eq!(expected, actual, "`{}` differs with 16-bit cells", source);
+            let [expected, actual] = run_both::<u32>(&program, tape, eof_policy, input);
+            assert_eq!(expected, actual, "`{}` differs with 32-bit cells", source);
+            let [expected, actual] = run_both::<u64>(&program, tape, eof_policy, input);
+            assert_eq!(expected, actual, "`{}` differs with 64-bit cells", source);
+        }
+    }
+}
@@ -0,0 +1,283 @@
+mod common;
+
+use bf_rs::{
+    codegen::{emit_llvm_ir, CodegenOptions},
+    interpreter::{CellWidth, EofPolicy, TapeConfig},
+    optimizer::Optimizer,
+    parser::BfOp,
+};
+use common::{examples, interpret, parse, run_binary, run_command};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// How the emitted modules are run, with the flags they need.
+enum Runner {
+    /// Run the module directly with `lli`.
+    Lli(Vec<&'static str>),
+    /// Build the module into an executable with `clang`, then run that.
+    Clang(Vec<&'static str>),
+}
+
+/// The major version of the LLVM tool `name`, if it can be run.
+fn llvm_version(name: &str) -> Option<u32> {
+    let output = Command::new(name).arg("--version").output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let version = text.split("version ").nth(1)?;
+    version.split('.').next()?.trim().parse().ok()
+}
+
+/// Finds `lli` or `clang`, as the tests are skipped without LLVM 14 or later.
+fn find_runner() -> Option<Runner> {
+    // The modules use opaque pointers, which LLVM 14 only parses when asked to
+    let flags = |version: u32, opaque_pointers: &[&'static str]| match version {
+        14 => Some(opaque_pointers.to_vec()),
+        15.. => Some(Vec::new()),
+        _ => None,
+    };
+    let runner = llvm_version("lli")
+        .and_then(|version| flags(version, &["-opaque-pointers"]))
+        .map(Runner::Lli)
+        .or_else(|| {
+            llvm_version("clang")
+                .and_then(|version| flags(version, &["-mllvm", "-opaque-pointers"]))
+                .map(Runner::Clang)
+        });
+    if runner.is_none() {
+        eprintln!("No lli or clang from LLVM 14 or later found, skipping the test");
+    }
+    runner
+}
+
+/// Compiles `program` to a module named `name` and runs it with `input`, returning the
+/// output and the error message it printed.
+fn compile_and_run(
+    runner: &Runner,
+    name: &str,
+    program: &[BfOp],
+    options: &CodegenOptions,
+    input: &[u8],
+) -> (Vec<u8>, String) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("llvm");
+    fs::create_dir_all(&dir).expect("Failed to create the test directory");
+
+    let module = dir.join(format!("{}.ll", name));
+    fs::write(&module, emit_llvm_ir(program, options)).expect("Failed to write the module");
+    match runner {
+        Runner::Lli(flags) => run_command(Command::new("lli").args(flags).arg(&module), input),
+        Runner::Clang(flags) => {
+            let executable = dir.join(name);
+            let status = Command::new("clang")
+                .args(flags)
+                .args(["-O1", "-o"])
+                .arg(&executable)
+                .arg(&module)
+                .status()
+                .expect("Failed to run clang");
+            assert!(
+                status.success(),
+                "The module for `{}` does not compile",
+                name
+            );
+            run_binary(&executable, input)
+        }
+    }
+}
+
+#[test]
+fn llvm_ir_is_well_formed() {
+    let source = include_str!("../examples/hello_world.bf");
+    let module = emit_llvm_ir(&parse(source), &CodegenOptions::default());
+
+    assert!(module.contains("@tape = internal global [30000 x i8] zeroinitializer"));
+    assert!(module.contains("declare i32 @getchar()"));
+    assert!(module.contains("declare i32 @putchar(i32)"));
+    assert!(module.contains("define i32 @main() {"));
+
+    // Each loop has a header and a body block
+    let labels: Vec<&str> = module
+        .lines()
+        .filter(|line| line.ends_with(':') && !line.starts_with(' '))
+        .collect();
+    let loops = source.matches('[').count();
+    assert_eq!(
+        labels
+            .iter()
+            .filter(|label| label.starts_with("loop"))
+            .count(),
+        loops
+    );
+    assert_eq!(
+        labels
+            .iter()
+            .filter(|label| label.starts_with("body"))
+            .count(),
+        loops
+    );
+
+    // Every block ends with a terminator before the next one starts
+    let lines: Vec<&str> = module.lines().collect();
+    for pair in lines.windows(2) {
+        if pair[1].ends_with(':') && !pair[1].starts_with(' ') && !pair[0].ends_with('{') {
+            let last = pair[0].trim_start();
+            assert!(
+                ["br ", "ret ", "unreachable"]
+                    .iter()
+                    .any(|terminator| last.starts_with(terminator)),
+                "Block before `{}` does not end with a terminator",
+                pair[1]
+            );
+        }
+    }
+}
+
+#[test]
+fn growing_tapes_move_to_the_heap() {
+    let options = CodegenOptions {
+        tape: TapeConfig::Bidirectional {
+            initial: 16,
+            max: 1024,
+        },
+        ..Default::default()
+    };
+    let module = emit_llvm_ir(&parse("+[<+]"), &options);
+
+    assert!(module.contains("@tape = internal global [16 x i8] zeroinitializer"));
+    assert!(module.contains("@cells = internal global ptr @tape"));
+    assert!(module.contains("@origin = internal global i64 0"));
+    assert!(module.contains("define internal ptr @resize("));
+    assert!(module.contains("@limit, i32 %line, i32 %column, i64 1024, i64 %position)"));
+}
+
+#[test]
+fn examples_match_interpreter() {
+    let Some(runner) = find_runner() else {
+        return;
+    };
+
+    for (path, source) in examples() {
+        let program = Optimizer::new().optimize(parse(&source));
+        let options = CodegenOptions::default();
+
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let expected = interpret::<u8>(&program, &options, b"");
+        let actual = compile_and_run(&runner, &name, &program, &options, b"");
+        assert_eq!(expected, actual, "{} behaves differently", path.display());
+    }
+}
+
+#[test]
+fn edge_cases_match_interpreter() {
+    let Some(runner) = find_runner() else {
+        return;
+    };
+
+    let cases = [
+        ("+.<", CodegenOptions::default()),
+        (
+            ">>>.<",
+            CodegenOptions {
+                tape: TapeConfig::Fixed { cells: 3 },
+                ..Default::default()
+            },
+        ),
+        (
+            "<<<+.>>>>>>>>+.<<<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Wrapping { cells: 7 },
+                ..Default::default()
+            },
+        ),
+        (
+            "+[>+.]",
+            CodegenOptions {
+                tape: TapeConfig::Growing {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            "+[<+.]",
+            CodegenOptions {
+                tape: TapeConfig::Bidirectional {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            ",.,.,.,.",
+            CodegenOptions {
+                eof_policy: EofPolicy::Error,
+                ..Default::default()
+            },
+        ),
+        (
+            ",+[-.,+]>,+.",
+            CodegenOptions {
+                eof_policy: EofPolicy::MinusOne,
+                ..Default::default()
+            },
+        ),
+        (
+            ",[.,]>,.",
+            CodegenOptions {
+                eof_policy: EofPolicy::Zero,
+                ..Default::default()
+            },
+        ),
+        (
+            "-.>+++++++++++++++[-<+++++++++++++++++>]<.[-]+.",
+            CodegenOptions::default(),
+        ),
+        // Cells at an offset outside the tape
+        (
+            ">>>+<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Fixed { cells: 3 },
+                ..Default::default()
+            },
+        ),
+        (
+            "++[-<<<<+>>>>]<<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Bidirectional {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+    ];
+
+    for (index, (source, options)) in cases.into_iter().enumerate() {
+        let program = Optimizer::new().optimize(parse(source));
+        for cell_width in [
+            CellWidth::U8,
+            CellWidth::U16,
+            CellWidth::U32,
+            CellWidth::U64,
+        ] {
+            let options = CodegenOptions {
+                cell_width,
+                ..options
+            };
+            let expected = match cell_width {
+                CellWidth::U8 => interpret::<u8>(&program, &options, b"ll"),
+                CellWidth::U16 => interpret::<u16>(&program, &options, b"ll"),
+                CellWidth::U32 => interpret::<u32>(&program, &options, b"ll"),
+                CellWidth::U64 => interpret::<u64>(&program, &options, b"ll"),
+            };
+            let name = format!("case_{}_{}", index, cell_width);
+            let actual = compile_and_run(&runner, &name, &program, &options, b"ll");
+            assert_eq!(
+                expected, actual,
+                "`{}` behaves differently with {}-bit cells",
+                source, cell_width
+            );
+        }
+    }
+}
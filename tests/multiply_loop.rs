@@ -0,0 +1,179 @@
+#![cfg(feature = "optimizer")]
+
+mod common;
+
+use bf_rs::{
+    interpreter::TapeConfig,
+    optimizer::Optimizer,
+    parser::{BfOpKind, OptimizedOp},
+};
+use common::{kinds, parse, run_kind, Rng};
+
+#[test]
+fn multiply_loops_become_mul_adds() {
+    let optimized = Optimizer::new().optimize(parse("[->+>++<<]"));
+    assert_eq!(
+        kinds(&optimized),
+        [
+            BfOpKind::Optimized(OptimizedOp::MulAdd {
+                offset: 1,
+                factor: 1
+            }),
+            BfOpKind::Optimized(OptimizedOp::MulAdd {
+                offset: 2,
+                factor: 2
+            }),
+            BfOpKind::Optimized(OptimizedOp::ClearCell),
+        ]
+    );
+
+    // Counting up negates the factors
+    let optimized = Optimizer::new().optimize(parse("[<--->+]"));
+    assert_eq!(
+        optimized[0].kind,
+        BfOpKind::Optimized(OptimizedOp::MulAdd {
+            offset: -1,
+            factor: 3
+        })
+    );
+
+    // Loops stepping by more than one may never end, and keep their shape, like loops
+    // moving to cells they leave alone, which may be off the tape. Reading the cell first
+    // keeps them from being dead
+    for source in [
+        ",[-->+<]",
+        ",[->+<<]",
+        ",[->+<.]",
+        ",[->[-]<]",
+        ",[->>>>><+<<<<]",
+        ",[->>>><<<<]",
+        ",[->+<>>+-<<]",
+    ] {
+        let optimized = Optimizer::new().optimize(parse(source));
+        assert!(
+            matches!(optimized[1].kind, BfOpKind::Loop(_)),
+            "`{}` was rewritten",
+            source
+        );
+    }
+}
+
+/// The optimized program must print the same cells and fail the same way.
+#[test]
+fn mul_adds_match_unoptimized_loops() {
+    let cases = [
+        // 7 * 6, with and without carrying across the cell width
+        ("+++++++[->++++++<]>.", TapeConfig::default()),
+        (
+            "++++++++++++++++[->++++++++++++++++<]>.>.",
+            TapeConfig::default(),
+        ),
+        ("+++[->+>---<<]>.>.<<.", TapeConfig::default()),
+        ("-[+>+++<]>.", TapeConfig::default()),
+        ("++>+++++[-<+++>>++<]<.>.>.", TapeConfig::default()),
+        // Every cell moved to is changed, so moving off the tape still fails
+        ("+[->>>>+<+<<<]", TapeConfig::Fixed { cells: 5 }),
+        ("+[->>>>+<+<<<]", TapeConfig::Fixed { cells: 4 }),
+        // Zero cells skip the loop, and so the moves
+        ("[-<+>]+.", TapeConfig::Fixed { cells: 4 }),
+        ("+[-<+>]", TapeConfig::Fixed { cells: 4 }),
+        ("+[->>>>+<<<<]", TapeConfig::Fixed { cells: 4 }),
+        ("+++[->>>>+<<<<]>>>>.", TapeConfig::Wrapping { cells: 3 }),
+        (
+            "++[-<<+>>]<<.",
+            TapeConfig::Bidirectional {
+                initial: 1,
+                max: 16,
+            },
+        ),
+        (
+            "++[->>>>>>>>+<<<<<<<<]>>>>>>>>.",
+            TapeConfig::Growing { initial: 2, max: 6 },
+        ),
+    ];
+
+    for (source, tape) in cases {
+        let program = parse(source);
+        let optimized = Optimizer::new().optimize(program.clone());
+        assert_ne!(program, optimized, "`{}` was not optimized", source);
+
+        assert_eq!(
+            run_kind::<u8>(&program, tape),
+            run_kind::<u8>(&optimized, tape),
+            "`{}` behaves differently with 8-bit cells",
+            source
+        );
+        assert_eq!(
+            run_kind::<u16>(&program, tape),
+            run_kind::<u16>(&optimized, tape),
+            "`{}` behaves differently with 16-bit cells",
+            source
+        );
+        assert_eq!(
+            run_kind::<u64>(&program, tape),
+            run_kind::<u64>(&optimized, tape),
+            "`{}` behaves differently with 64-bit cells",
+            source
+        );
+    }
+}
+
+/// Moves the pointer by `delta`.
+fn moves(delta: i64) -> String {
+    let command = if delta < 0 { "<" } else { ">" };
+    command.repeat(delta.unsigned_abs() as usize)
+}
+
+/// Random multiplication loops, moving over cells they may not change, must fail or
+/// print the same as the loops they replace.
+#[test]
+fn mul_adds_match_random_loops() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+    for _ in 0..500 {
+        let mut body = String::new();
+        let mut offset = 0;
+        for _ in 0..rng.below(4) + 1 {
+            let target = rng.below(9) as i64 - 4;
+            body.push_str(&moves(target - offset));
+            offset = target;
+            // Only change the current cell once, so that the loop ends
+            if target != 0 && rng.below(4) != 0 {
+                let command = if rng.below(2) == 0 { "+" } else { "-" };
+                body.push_str(&command.repeat(rng.below(3) as usize + 1));
+            }
+        }
+        body.push_str(&moves(-offset));
+        let step = if rng.below(2) == 0 { "-" } else { "+" };
+        let body = if rng.below(2) == 0 {
+            format!("{}{}", step, body)
+        } else {
+            format!("{}{}", body, step)
+        };
+
+        let source = format!(
+            "{}{}[{}]<<<{}",
+            "+".repeat(rng.below(10) as usize + 1),
+            ">".repeat(rng.below(4) as usize),
+            body,
+            ".>".repeat(7)
+        );
+        let program = parse(&source);
+        let optimized = Optimizer::new().optimize(program.clone());
+        for tape in [
+            TapeConfig::Fixed { cells: 4 },
+            // Any smaller, and moving 4 cells away would come back to the counter
+            TapeConfig::Wrapping { cells: 5 },
+            TapeConfig::Growing { initial: 2, max: 6 },
+            TapeConfig::Bidirectional { initial: 2, max: 6 },
+        ] {
+            assert_eq!(
+                run_kind::<u8>(&program, tape),
+                run_kind::<u8>(&optimized, tape),
+                "`{}` behaves differently on {}",
+                source,
+                tape
+            );
+        }
+    }
+}
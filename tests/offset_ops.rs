@@ -0,0 +1,100 @@
+#![cfg(feature = "optimizer")]
+
+mod common;
+
+use bf_rs::{
+    interpreter::TapeConfig,
+    optimizer::Optimizer,
+    parser::{BfOpKind, OptimizedOp},
+};
+use common::{kinds, parse, run_kind, Rng};
+use std::num::Wrapping;
+
+#[test]
+fn straight_line_code_becomes_offset_ops() {
+    let optimized = Optimizer::new().optimize(parse(">+>++>+++<<<."));
+    assert_eq!(
+        kinds(&optimized),
+        [
+            BfOpKind::Optimized(OptimizedOp::AddAt {
+                offset: 1,
+                delta: 1
+            }),
+            BfOpKind::Optimized(OptimizedOp::AddAt {
+                offset: 2,
+                delta: 2
+            }),
+            BfOpKind::Optimized(OptimizedOp::AddAt {
+                offset: 3,
+                delta: 3
+            }),
+            BfOpKind::OutputByte,
+        ]
+    );
+
+    // The last move becomes the adjustment, and fails where it did
+    let optimized = Optimizer::new().optimize(parse("+>.>-<<<[-]"));
+    assert_eq!(
+        kinds(&optimized)[..4],
+        [
+            BfOpKind::Increment(Wrapping(1)),
+            BfOpKind::Optimized(OptimizedOp::OutputAt(1)),
+            BfOpKind::Optimized(OptimizedOp::AddAt {
+                offset: 2,
+                delta: -1
+            }),
+            BfOpKind::PointerIncrement(-1),
+        ]
+    );
+    assert_eq!(optimized[3].span.column, 6);
+
+    // Moves that do not save anything, or whose cell is not used, keep their shape
+    for source in [">+", ">><<+", "+>,>+"] {
+        let program = parse(source);
+        assert_eq!(
+            Optimizer::new().optimize(program.clone()),
+            program,
+            "`{}` was rewritten",
+            source
+        );
+    }
+}
+
+/// Random straight-line code must print the same cells, and fail the same way before
+/// printing anything else, on every kind of tape.
+#[test]
+fn offset_ops_match_unoptimized_code() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    for _ in 0..500 {
+        let mut source = "+".repeat(rng.below(40) as usize + 1);
+        for _ in 0..rng.below(30) + 1 {
+            let command = match rng.below(8) {
+                0 | 1 => ">".repeat(rng.below(3) as usize + 1),
+                2 | 3 => "<".repeat(rng.below(3) as usize + 1),
+                4 => "+".repeat(rng.below(5) as usize + 1),
+                5 => "-".repeat(rng.below(5) as usize + 1),
+                6 => ".".to_string(),
+                _ => "[-]".to_string(),
+            };
+            source.push_str(&command);
+        }
+
+        let program = parse(&source);
+        let optimized = Optimizer::new().optimize(program.clone());
+        for tape in [
+            TapeConfig::Fixed { cells: 4 },
+            TapeConfig::Wrapping { cells: 3 },
+            TapeConfig::Growing { initial: 2, max: 6 },
+            TapeConfig::Bidirectional { initial: 2, max: 6 },
+        ] {
+            assert_eq!(
+                run_kind::<u8>(&program, tape),
+                run_kind::<u8>(&optimized, tape),
+                "`{}` behaves differently on {}",
+                source,
+                tape
+            );
+        }
+    }
+}
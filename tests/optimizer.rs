@@ -0,0 +1,155 @@
+mod common;
+
+use bf_rs::{
+    optimizer::{
+        OptimizationLevel, OptimizationReport, OptimizationRule, Optimizer, MULTIPLY_LOOPS,
+        OFFSET_OPS, SCAN_LOOPS,
+    },
+    parser::{BfOp, BfOpKind, OptimizedOp},
+};
+use common::parse;
+
+/// How many times each pass fired, by name.
+fn fires(report: &OptimizationReport) -> Vec<(&'static str, usize)> {
+    report
+        .passes
+        .iter()
+        .map(|pass| (pass.name, pass.fires))
+        .collect()
+}
+
+/// Rewrites `+` into `-` and `-` into `>`, so that each of its matches enables another.
+struct ChainRule {}
+
+impl OptimizationRule for ChainRule {
+    fn name(&self) -> &'static str {
+        "chain"
+    }
+
+    fn description(&self) -> &'static str {
+        "Turn `+` into `-` and `-` into `>`"
+    }
+
+    fn apply(&self, ops: &[BfOp]) -> Option<(Vec<BfOp>, usize)> {
+        let kind = match ops.first()?.kind {
+            BfOpKind::Increment(delta) if delta.0 > 0 => BfOpKind::Increment(-delta),
+            BfOpKind::Increment(_) => BfOpKind::PointerIncrement(1),
+            _ => return None,
+        };
+        Some((vec![BfOp::new(kind, ops[0].span)], 1))
+    }
+}
+
+#[test]
+#[cfg(feature = "optimizer")]
+fn passes_run_until_nothing_changes() {
+    let (optimized, report) =
+        Optimizer::new().optimize_with_report(parse("+++[-]++>[->+<]<[>]>>>+>+<<<"));
+
+    // The clear only becomes a constant once the clear loop is gone
+    assert_eq!(
+        optimized[0].kind,
+        BfOpKind::Optimized(OptimizedOp::SetCell(2))
+    );
+    assert_eq!(report.iterations, 2);
+    assert!(report.converged);
+    assert_eq!(
+        fires(&report),
+        [
+            ("clear-loops", 1),
+            ("multiply-loops", 1),
+            ("scan-loops", 1),
+            ("offset-ops", 1),
+            ("set-cells", 1),
+            ("dead-loops", 0),
+        ]
+    );
+    assert_eq!(report.ops_before, 18);
+    assert_eq!(report.ops_after, optimized.len());
+    assert_eq!(report.ops_removed(), 9);
+}
+
+#[test]
+fn iterations_stop_at_the_limit() {
+    let mut optimizer = Optimizer::empty().with_max_iterations(2);
+    optimizer.register_rule(Box::new(ChainRule {}));
+
+    let (optimized, report) = optimizer.optimize_with_report(parse("+"));
+    assert_eq!(optimized[0].kind, BfOpKind::PointerIncrement(1));
+    assert_eq!(report.iterations, 2);
+    assert!(!report.converged);
+    assert_eq!(fires(&report), [("chain", 2)]);
+    assert_eq!(report.ops_removed(), 0);
+    assert!(report.to_string().contains("stopping at the limit"));
+
+    // Without a limit, the next iteration finds nothing to do
+    let mut optimizer = Optimizer::empty();
+    optimizer.register_rule(Box::new(ChainRule {}));
+    let (_, report) = optimizer.optimize_with_report(parse("+"));
+    assert_eq!(report.iterations, 3);
+    assert!(report.converged);
+}
+
+#[test]
+#[cfg(feature = "optimizer")]
+fn levels_and_toggles_select_the_passes() {
+    let names = |optimizer: &Optimizer| {
+        let (_, report) = optimizer.optimize_with_report(parse("[-]"));
+        fires(&report)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+    };
+
+    assert!(names(&Optimizer::with_level(OptimizationLevel::O0)).is_empty());
+    assert_eq!(
+        names(&Optimizer::with_level(OptimizationLevel::O1)),
+        ["clear-loops", "set-cells", "dead-loops"]
+    );
+    assert_eq!(
+        names(&Optimizer::with_level(OptimizationLevel::O3)),
+        names(&Optimizer::new())
+    );
+
+    // Toggles apply whatever the level, and keep the passes in order
+    let mut optimizer = Optimizer::with_level(OptimizationLevel::O1);
+    optimizer.set_rule_enabled("clear-loops", false).unwrap();
+    assert_eq!(optimizer.set_rule_enabled("offset-ops", true), Ok(true));
+    assert_eq!(names(&optimizer), ["offset-ops", "set-cells", "dead-loops"]);
+    let (optimized, _) = optimizer.optimize_with_report(parse(",[-]"));
+    assert!(matches!(optimized[1].kind, BfOpKind::Loop(_)));
+
+    // The names of the heavy passes, which are known without the feature as well
+    for name in [MULTIPLY_LOOPS, SCAN_LOOPS, OFFSET_OPS] {
+        assert_eq!(optimizer.set_rule_enabled(name, false), Ok(true));
+    }
+
+    let error = optimizer.set_rule_enabled("scan-loop", false).unwrap_err();
+    assert!(error.contains("`scan-loop`"), "{}", error);
+    assert!(error.contains("scan-loops"), "{}", error);
+}
+
+#[test]
+#[cfg(not(feature = "optimizer"))]
+fn heavy_passes_need_the_feature() {
+    let mut optimizer = Optimizer::with_level(OptimizationLevel::O3);
+    let (optimized, report) = optimizer.optimize_with_report(parse(",[-]+[>]"));
+    assert_eq!(
+        optimized[1].kind,
+        BfOpKind::Optimized(OptimizedOp::SetCell(1))
+    );
+    assert!(matches!(optimized[2].kind, BfOpKind::Loop(_)));
+    assert_eq!(
+        fires(&report),
+        [("clear-loops", 1), ("set-cells", 1), ("dead-loops", 0)]
+    );
+    // The heavy passes can still be named, but are not there to enable
+    for name in [MULTIPLY_LOOPS, SCAN_LOOPS, OFFSET_OPS] {
+        assert_eq!(optimizer.set_rule_enabled(name, true), Ok(false));
+    }
+    assert_eq!(
+        fires(&optimizer.optimize_with_report(parse(",[>]")).1).len(),
+        3
+    );
+    assert!(optimizer.set_rule_enabled("scan-loop", true).is_err());
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use bf_rs::{
+    bytecode::Bytecode,
+    interpreter::{Interpreter, InterpreterError, OutputMode},
+    lexer::Span,
+    optimizer::Optimizer,
+    parser::{BfOp, BfOpKind, OptimizedOp},
+};
+use common::parse;
+use std::io::{self, Write};
+
+/// A writer recording how much had been written at each flush.
+#[derive(Default)]
+struct Recorder {
+    written: Vec<u8>,
+    flushes: Vec<usize>,
+}
+
+impl Write for Recorder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes.push(self.written.len());
+        Ok(())
+    }
+}
+
+/// Runs `program` with `input` in `output_mode`, returning the output, the length of the
+/// output at each flush, and the error.
+///
+/// The bytecode VM must flush at the same points as the tree-walking interpreter, with
+/// and without optimizations.
+fn run(
+    program: Vec<BfOp>,
+    output_mode: OutputMode,
+    input: &[u8],
+) -> (Vec<u8>, Vec<usize>, Option<InterpreterError>) {
+    let interpreter = || Interpreter::new().with_output_mode(output_mode);
+
+    let mut results = Vec::new();
+    for program in [program.clone(), Optimizer::new().optimize(program)] {
+        let mut recorder = Recorder::default();
+        let result = interpreter().execute(&program, &mut recorder, &mut &input[..]);
+        results.push((recorder.written, recorder.flushes, result.err()));
+
+        let mut recorder = Recorder::default();
+        let result = interpreter().execute_bytecode(
+            &Bytecode::compile(&program),
+            &mut recorder,
+            &mut &input[..],
+        );
+        results.push((recorder.written, recorder.flushes, result.err()));
+    }
+
+    let (output, flushes, error) = results.remove(0);
+    for (other_output, other_flushes, other_error) in results {
+        assert_eq!(other_output, output);
+        assert_eq!(other_flushes, flushes, "Flushes differ in {}", output_mode);
+        assert_eq!(
+            other_error.map(|e| e.to_string()),
+            error.as_ref().map(ToString::to_string)
+        );
+    }
+    (output, flushes, error)
+}
+
+/// Lengths of the output at each flush of an echo of `ab\nc`.
+fn flushes(output_mode: OutputMode) -> Vec<usize> {
+    let (output, flushes, error) = run(parse(",.,.,.,."), output_mode, b"ab\nc");
+    assert!(error.is_none(), "Execution failed: {:?}", error);
+    assert_eq!(output, b"ab\nc");
+    flushes
+}
+
+#[test]
+fn each_mode_flushes_at_its_points() {
+    // Every mode flushes once more when execution ends
+    assert_eq!(flushes(OutputMode::Unbuffered), [1, 2, 3, 4, 4]);
+    assert_eq!(flushes(OutputMode::LineBuffered), [3, 4]);
+    assert_eq!(flushes(OutputMode::FullyBuffered), [4]);
+    assert_eq!(flushes(OutputMode::FlushBeforeInput), [0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn output_at_an_offset_is_flushed_too() {
+    // Prints a newline from the next cell, then the current cell
+    let mut program = parse(">++++++++++<");
+    program.push(BfOp::new(
+        BfOpKind::Optimized(OptimizedOp::OutputAt(1)),
+        Span::default(),
+    ));
+    program.extend(parse("."));
+
+    let (output, flushes, _) = run(program, OutputMode::LineBuffered, b"");
+    assert_eq!(output, [10, 0]);
+    assert_eq!(flushes, [1, 2]);
+}
+
+#[test]
+fn errors_still_flush_the_output() {
+    for output_mode in [
+        OutputMode::Unbuffered,
+        OutputMode::LineBuffered,
+        OutputMode::FullyBuffered,
+        OutputMode::FlushBeforeInput,
+    ] {
+        let (output, flushes, error) = run(parse("+.<"), output_mode, b"");
+        assert_eq!(output, [1]);
+        assert_eq!(flushes.last(), Some(&1));
+        assert!(matches!(
+            error,
+            Some(InterpreterError::PointerUnderflow { .. })
+        ));
+    }
+}
+
+#[test]
+fn modes_are_parsed_from_their_names() {
+    for (text, output_mode) in [
+        ("unbuffered", OutputMode::Unbuffered),
+        ("line", OutputMode::LineBuffered),
+        ("full", OutputMode::FullyBuffered),
+        ("before-input", OutputMode::FlushBeforeInput),
+    ] {
+        assert_eq!(text.parse::<OutputMode>(), Ok(output_mode));
+        assert_eq!(output_mode.to_string(), text);
+    }
+    assert!("buffered".parse::<OutputMode>().is_err());
+}
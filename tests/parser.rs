@@ -0,0 +1,98 @@
+mod common;
+
+use bf_rs::{
+    lexer::{Lexer, Span},
+    parser::{BfOp, BfOpKind, ParseError, Parser},
+};
+use common::kinds;
+use std::num::Wrapping;
+
+fn parse_recovering(source: &str) -> (Vec<BfOp>, Vec<ParseError>) {
+    let tokens = Lexer::new(source).tokenize();
+    Parser::new(tokens).parse_recovering()
+}
+
+/// The kind and byte offset of each error, in the order they were returned.
+fn offsets(errors: &[ParseError]) -> Vec<(&'static str, usize)> {
+    errors
+        .iter()
+        .map(|error| match error {
+            ParseError::UnmatchedLoopStart { span } => ("start", span.start),
+            ParseError::UnmatchedLoopEnd { span } => ("end", span.start),
+        })
+        .collect()
+}
+
+fn body(op: &BfOp) -> &[BfOp] {
+    match &op.kind {
+        BfOpKind::Loop(body) => body,
+        kind => panic!("Expected a loop, found {:?}", kind),
+    }
+}
+
+#[test]
+fn every_stray_loop_end_is_reported() {
+    let (program, errors) = parse_recovering("+]]>[.]]-");
+    assert_eq!(offsets(&errors), [("end", 1), ("end", 2), ("end", 7)]);
+
+    // The stray brackets are skipped, and the rest is parsed as usual
+    assert_eq!(program.len(), 4);
+    assert_eq!(program[0].kind, BfOpKind::Increment(Wrapping(1)));
+    assert_eq!(program[1].kind, BfOpKind::PointerIncrement(1));
+    assert_eq!(kinds(body(&program[2])), [BfOpKind::OutputByte]);
+    assert_eq!(program[2].span, Span::new(4, 7, 1, 5));
+    assert_eq!(program[3].kind, BfOpKind::Increment(Wrapping(-1)));
+}
+
+#[test]
+fn unclosed_loop_start_is_reported() {
+    let (program, errors) = parse_recovering("+[>[-]");
+    assert_eq!(offsets(&errors), [("start", 1)]);
+    assert_eq!(errors[0].span(), Span::new(1, 2, 1, 2));
+
+    // The unclosed loop runs over the rest of the program, and only spans its `[`
+    assert_eq!(program.len(), 2);
+    assert_eq!(program[0].kind, BfOpKind::Increment(Wrapping(1)));
+    assert_eq!(program[1].span, Span::new(1, 2, 1, 2));
+    let placeholder = body(&program[1]);
+    assert_eq!(placeholder.len(), 2);
+    assert_eq!(placeholder[0].kind, BfOpKind::PointerIncrement(1));
+    assert_eq!(
+        kinds(body(&placeholder[1])),
+        [BfOpKind::Increment(Wrapping(-1))]
+    );
+}
+
+#[test]
+fn errors_are_sorted_by_position() {
+    // Unclosed loops are only found at the end, innermost first
+    let (program, errors) = parse_recovering("[]]\n[[");
+    assert_eq!(offsets(&errors), [("end", 2), ("start", 4), ("start", 5)]);
+    assert_eq!(
+        errors
+            .iter()
+            .map(|error| error.span().line)
+            .collect::<Vec<_>>(),
+        [1, 2, 2]
+    );
+
+    // Both placeholder loops are kept, nested like their brackets
+    assert_eq!(program.len(), 2);
+    assert!(body(&program[0]).is_empty());
+    let outer = body(&program[1]);
+    assert_eq!(program[1].span.start, 4);
+    assert_eq!(outer.len(), 1);
+    assert!(body(&outer[0]).is_empty());
+    assert_eq!(outer[0].span.start, 5);
+}
+
+#[test]
+fn well_formed_programs_have_no_errors() {
+    let source = include_str!("../examples/hello_world.bf");
+    let (program, errors) = parse_recovering(source);
+    assert!(errors.is_empty());
+
+    let tokens = Lexer::new(source).tokenize();
+    let parsed = Parser::new(tokens).parse().expect("Parsing failed");
+    assert_eq!(program, parsed);
+}
@@ -0,0 +1,124 @@
+#![cfg(feature = "optimizer")]
+
+mod common;
+
+use bf_rs::{
+    interpreter::{Interpreter, InterpreterError, TapeConfig},
+    optimizer::Optimizer,
+    parser::{BfOp, BfOpKind, OptimizedOp},
+};
+use common::{parse, run, Rng};
+
+/// Runs `program` on `tape`, returning the output and error message.
+fn run_message(program: &[BfOp], tape: TapeConfig) -> (Vec<u8>, String) {
+    let (output, error) = run::<u8>(program, tape);
+    (output, error.map(|e| e.to_string()).unwrap_or_default())
+}
+
+#[test]
+fn scan_loops_become_scans() {
+    let optimized = Optimizer::new().optimize(parse("[>]+[<<<]"));
+    assert_eq!(
+        optimized[0].kind,
+        BfOpKind::Optimized(OptimizedOp::ScanRight(1))
+    );
+    assert_eq!(
+        optimized[2].kind,
+        BfOpKind::Optimized(OptimizedOp::ScanLeft(3))
+    );
+    // Scans report errors at the move inside the loop
+    assert_eq!(optimized[2].span.column, 6);
+
+    // Reading the cell first keeps the loops from being dead
+    for source in [",[>+]", ",[><]", ",[]"] {
+        let optimized = Optimizer::new().optimize(parse(source));
+        assert!(
+            !matches!(
+                optimized[1].kind,
+                BfOpKind::Optimized(OptimizedOp::ScanRight(_) | OptimizedOp::ScanLeft(_))
+            ),
+            "`{}` was rewritten",
+            source
+        );
+    }
+}
+
+#[test]
+fn left_scans_keep_pointer_underflow() {
+    let program = Optimizer::new().optimize(parse("+>+>+[<]"));
+    let (_, error) = run_message(&program, TapeConfig::default());
+    assert_eq!(
+        error,
+        "Pointer underflow at line 1, column 7: attempted to move left 1 steps when pointer was at position 0"
+    );
+}
+
+#[test]
+fn scans_without_zero_cells_run_out_of_fuel() {
+    let program = Optimizer::new().optimize(parse("+>+>+[>]"));
+    let result = Interpreter::new()
+        .with_tape(TapeConfig::Wrapping { cells: 3 })
+        .with_fuel(Some(1000))
+        .execute(&program, &mut Vec::new(), &mut &b""[..]);
+    assert!(matches!(result, Err(InterpreterError::OutOfFuel { .. })));
+}
+
+/// Scanning random tapes must stop on the same cell, or fail the same way, as the loop.
+#[test]
+fn scans_match_unoptimized_loops() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+    for _ in 0..300 {
+        // Long enough tapes to cover several chunks of the search
+        let len = rng.below(150) as usize + 1;
+        let values: Vec<usize> = (0..len)
+            .map(|i| if rng.below(5) == 0 { 0 } else { i % 200 + 1 })
+            .collect();
+        let start = rng.below(len as u64) as usize;
+        let stride = rng.below(4) as isize + 1;
+        let stride = if rng.below(2) == 0 { stride } else { -stride };
+
+        let mut source = values
+            .iter()
+            .map(|&value| "+".repeat(value))
+            .collect::<Vec<_>>()
+            .join(">");
+        source.push_str(&"<".repeat(len - 1 - start));
+        let step = if stride > 0 { ">" } else { "<" };
+        source.push_str(&format!("[{}]", step.repeat(stride.unsigned_abs())));
+
+        // Mark the cell the scan stops on, then print the whole tape
+        let mut pointer = start as isize;
+        while (0..len as isize).contains(&pointer) && values[pointer as usize] != 0 {
+            pointer += stride;
+        }
+        if (0..len as isize).contains(&pointer) {
+            source.push('+');
+            source.push_str(&"<".repeat(pointer as usize));
+            source.push_str(&".>".repeat(len - 1));
+            source.push('.');
+        }
+
+        let program = parse(&source);
+        let optimized = Optimizer::new().optimize(program.clone());
+        for tape in [
+            TapeConfig::Fixed { cells: len },
+            TapeConfig::Growing {
+                initial: len,
+                max: len + 3,
+            },
+            TapeConfig::Bidirectional {
+                initial: len,
+                max: len + 3,
+            },
+        ] {
+            assert_eq!(
+                run_message(&program, tape),
+                run_message(&optimized, tape),
+                "`{}` behaves differently on {}",
+                source,
+                tape
+            );
+        }
+    }
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use bf_rs::{
+    interpreter::TapeConfig,
+    optimizer::Optimizer,
+    parser::{BfOpKind, OptimizedOp},
+};
+use common::{kinds, parse, run_kind, Rng};
+use std::num::Wrapping;
+
+/// Wraps around rather than failing, whatever the moves.
+const TAPE: TapeConfig = TapeConfig::Wrapping { cells: 4 };
+
+#[test]
+fn clears_and_increments_become_set_cells() {
+    let set = |value| BfOpKind::Optimized(OptimizedOp::SetCell(value));
+    let clear = || BfOpKind::Optimized(OptimizedOp::ClearCell);
+
+    let optimizer = Optimizer::new();
+    assert_eq!(kinds(&optimizer.optimize(parse("[-]+++"))), [set(3)]);
+    assert_eq!(kinds(&optimizer.optimize(parse("[-]---[+]++"))), [set(2)]);
+    assert_eq!(
+        kinds(&optimizer.optimize(parse("[-]+++[-]----"))),
+        [set(-4)]
+    );
+    assert_eq!(kinds(&optimizer.optimize(parse("[-]++--"))), [clear()]);
+    // Increments right before a clear are dead
+    assert_eq!(kinds(&optimizer.optimize(parse("+++[-]"))), [clear()]);
+    assert_eq!(kinds(&optimizer.optimize(parse("+++[-]+"))), [set(1)]);
+
+    // Increments whose value is read first stay
+    let optimized = optimizer.optimize(parse("+++.[-]"));
+    assert_eq!(
+        kinds(&optimized),
+        [
+            BfOpKind::Increment(Wrapping(3)),
+            BfOpKind::OutputByte,
+            clear()
+        ]
+    );
+    let optimized = optimizer.optimize(parse("+++[->+<][-]"));
+    assert!(matches!(optimized[0].kind, BfOpKind::Increment(_)));
+}
+
+/// Programs setting cells in every way must print the same cells once optimized.
+/// Wider cells would take too long to clear without the optimizer.
+#[test]
+fn set_cells_match_unoptimized_code() {
+    let mut rng = Rng(0x853c_49e6_748f_ea9b);
+
+    for _ in 0..300 {
+        let mut source = String::new();
+        for _ in 0..rng.below(20) + 1 {
+            let command = match rng.below(7) {
+                0 | 1 => "[-]".to_string(),
+                2 => "[+]".to_string(),
+                3 => "+".repeat(rng.below(300) as usize + 1),
+                4 => "-".repeat(rng.below(300) as usize + 1),
+                5 => ".".to_string(),
+                _ => if rng.below(2) == 0 { ">" } else { "<" }.to_string(),
+            };
+            source.push_str(&command);
+        }
+        // Print every cell at the end
+        source.push_str(".>.>.>.");
+
+        let program = parse(&source);
+        let optimized = Optimizer::new().optimize(program.clone());
+        assert_eq!(
+            run_kind::<u8>(&program, TAPE),
+            run_kind::<u8>(&optimized, TAPE),
+            "`{}`",
+            source
+        );
+        assert_eq!(
+            run_kind::<u16>(&program, TAPE),
+            run_kind::<u16>(&optimized, TAPE),
+            "`{}`",
+            source
+        );
+    }
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use bf_rs::interpreter::{InterpreterError, TapeConfig};
+use common::{parse, run};
+
+/// Runs `source` on `tape`, expecting it to finish, and returns its output.
+fn output(source: &str, tape: TapeConfig) -> Vec<u8> {
+    let (output, error) = run::<u8>(&parse(source), tape);
+    assert!(
+        error.is_none(),
+        "`{}` failed on {}: {:?}",
+        source,
+        tape,
+        error
+    );
+    output
+}
+
+/// Runs `source` on `tape`, expecting it to fail, and returns the error.
+fn error(source: &str, tape: TapeConfig) -> InterpreterError {
+    run::<u8>(&parse(source), tape)
+        .1
+        .unwrap_or_else(|| panic!("`{}` did not fail on {}", source, tape))
+}
+
+#[test]
+fn fixed_tapes_fail_past_either_end() {
+    let tape = TapeConfig::Fixed { cells: 3 };
+    assert_eq!(output("+>++>+++.<.<.", tape), [3, 2, 1]);
+
+    assert!(matches!(
+        error("+>>>", tape),
+        InterpreterError::PointerOverflow {
+            position: 0,
+            attempted_move: 3,
+            ..
+        }
+    ));
+    assert_eq!(
+        error(">\n<<", tape).to_string(),
+        "Pointer underflow at line 2, column 1: attempted to move left 2 steps when pointer was at position 1"
+    );
+}
+
+#[test]
+fn growing_tapes_stop_at_their_limit() {
+    // New cells start at 0
+    let tape = TapeConfig::Growing {
+        initial: 2,
+        max: 16,
+    };
+    assert_eq!(output(">>>>>>>>>>.+.", tape), [0, 1]);
+    assert!(matches!(
+        error("<", tape),
+        InterpreterError::PointerUnderflow { .. }
+    ));
+
+    assert!(matches!(
+        error("+[>+]", tape),
+        InterpreterError::TapeLimitExceeded {
+            position: 15,
+            limit: 16,
+            ..
+        }
+    ));
+
+    // The default tape grows without any limit
+    let source = format!("{}+.", ">".repeat(40_000));
+    assert_eq!(output(&source, TapeConfig::default()), [1]);
+}
+
+#[test]
+fn wrapping_tapes_come_back_around() {
+    let tape = TapeConfig::Wrapping { cells: 3 };
+    assert_eq!(output("<+>.>.>.", tape), [0, 0, 1]);
+    assert_eq!(output("+>>>>>>>.", tape), [0]);
+    assert_eq!(output("+>>>>>>.", tape), [1]);
+}
+
+#[test]
+fn bidirectional_tapes_grow_to_the_left() {
+    let tape = TapeConfig::Bidirectional { initial: 2, max: 8 };
+    // Growing to the left keeps the cells in place
+    assert_eq!(output("+>++<<<<+++.>>>.>.", tape), [3, 1, 2]);
+
+    // The 8 cells run from -6 to the 2 cells the tape started with
+    let error = error("+[<+]", tape);
+    assert!(matches!(
+        error,
+        InterpreterError::TapeLimitExceeded {
+            position: -6,
+            limit: 8,
+            ..
+        }
+    ));
+    assert_eq!(
+        error.to_string(),
+        "Tape limit exceeded at line 1, column 3: the tape cannot grow beyond 8 cells, pointer was at position -6"
+    );
+}
+
+#[test]
+fn layouts_are_parsed_like_the_option() {
+    for (text, tape) in [
+        ("fixed:10", TapeConfig::Fixed { cells: 10 }),
+        ("wrap:7", TapeConfig::Wrapping { cells: 7 }),
+        (
+            "growing:100",
+            TapeConfig::Growing {
+                initial: 100,
+                max: 100,
+            },
+        ),
+        (
+            "bidirectional:50000",
+            TapeConfig::Bidirectional {
+                initial: 30_000,
+                max: 50_000,
+            },
+        ),
+        ("growing", TapeConfig::default()),
+    ] {
+        assert_eq!(text.parse::<TapeConfig>(), Ok(tape));
+        assert_eq!(tape.to_string(), text);
+    }
+
+    for text in ["fixed", "wrap:0", "growing:lots", "circular:10"] {
+        assert!(
+            text.parse::<TapeConfig>().is_err(),
+            "`{}` was accepted",
+            text
+        );
+    }
+}
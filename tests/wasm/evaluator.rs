@@ -0,0 +1,554 @@
+//! A tiny WebAssembly evaluator, decoding and running just enough of the binary format
+//! to execute the modules emitted by the WebAssembly backend.
+//!
+//! Values are kept as `u64`s on the operand stack, with `i32` values zero-extended.
+
+const PAGE: usize = 65536;
+/// The most pages the memory may grow to, to keep runaway tests in check.
+const MAX_PAGES: usize = 1024;
+
+/// A decoded instruction. Block instructions know where their `else` and `end` are.
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Unreachable,
+    Block {
+        end: usize,
+    },
+    Loop,
+    If {
+        else_: Option<usize>,
+        end: usize,
+    },
+    Else {
+        end: usize,
+    },
+    End,
+    Br(u32),
+    BrIf(u32),
+    Return,
+    Call(u32),
+    Drop,
+    Select,
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    /// A load of `bytes` bytes, zero-extended.
+    Load {
+        bytes: usize,
+        offset: u32,
+    },
+    /// A store of the low `bytes` bytes of a value.
+    Store {
+        bytes: usize,
+        offset: u32,
+    },
+    MemorySize,
+    MemoryGrow,
+    MemoryCopy,
+    MemoryFill,
+    Const(u64),
+    /// A numeric instruction without immediates, identified by its opcode.
+    Numeric(u8),
+}
+
+struct FuncType {
+    params: usize,
+    results: usize,
+}
+
+struct Function {
+    ty: usize,
+    locals: usize,
+    body: Vec<Op>,
+}
+
+/// A decoded module, with its memory and globals ready to run.
+pub struct Instance {
+    types: Vec<FuncType>,
+    /// The name and type of each imported function.
+    imports: Vec<(String, usize)>,
+    functions: Vec<Function>,
+    exports: Vec<(String, u32)>,
+    globals: Vec<u64>,
+    pub memory: Vec<u8>,
+}
+
+/// Handles calls to imported functions, by name. Returning an error traps.
+pub type Host<'a> = dyn FnMut(&str, &[u64], &mut Vec<u8>) -> Result<Option<u64>, String> + 'a;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .bytes
+            .get(self.position)
+            .ok_or("Unexpected end of module")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + count)
+            .ok_or("Unexpected end of module")?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut result = 0u64;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            result |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return u32::try_from(result).map_err(|_| "Integer too large".to_string());
+            }
+        }
+        Err("Integer too long".to_string())
+    }
+
+    fn i64(&mut self) -> Result<i64, String> {
+        let mut result = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            result |= ((byte & 0x7F) as i64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+                return Ok(result);
+            }
+            if shift >= 70 {
+                return Err("Integer too long".to_string());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|e| e.to_string())
+    }
+
+    fn value_types(&mut self) -> Result<usize, String> {
+        let count = self.u32()? as usize;
+        for _ in 0..count {
+            match self.byte()? {
+                0x7F | 0x7E => {}
+                other => return Err(format!("Unsupported value type {:#x}", other)),
+            }
+        }
+        Ok(count)
+    }
+
+    /// Decodes an expression, up to and including its final `end`.
+    fn expression(&mut self) -> Result<Vec<Op>, String> {
+        let mut ops = Vec::new();
+        // The index of each open block instruction
+        let mut open = Vec::new();
+        loop {
+            let opcode = self.byte()?;
+            let op = match opcode {
+                0x00 => Op::Unreachable,
+                0x02..=0x04 => {
+                    if self.byte()? != 0x40 {
+                        return Err("Only empty block types are supported".to_string());
+                    }
+                    open.push(ops.len());
+                    match opcode {
+                        0x02 => Op::Block { end: 0 },
+                        0x03 => Op::Loop,
+                        _ => Op::If {
+                            else_: None,
+                            end: 0,
+                        },
+                    }
+                }
+                0x05 => {
+                    let start = *open.last().ok_or("`else` outside of `if`")?;
+                    let position = ops.len();
+                    match &mut ops[start] {
+                        Op::If { else_, .. } => *else_ = Some(position),
+                        _ => return Err("`else` outside of `if`".to_string()),
+                    }
+                    Op::Else { end: 0 }
+                }
+                0x0B => {
+                    let Some(start) = open.pop() else {
+                        ops.push(Op::End);
+                        return Ok(ops);
+                    };
+                    let position = ops.len();
+                    match &mut ops[start] {
+                        Op::Block { end } => *end = position,
+                        Op::If { else_, end } => {
+                            *end = position;
+                            if let Some(else_) = *else_ {
+                                ops[else_] = Op::Else { end: position };
+                            }
+                        }
+                        _ => {}
+                    }
+                    Op::End
+                }
+                0x0C => Op::Br(self.u32()?),
+                0x0D => Op::BrIf(self.u32()?),
+                0x0F => Op::Return,
+                0x10 => Op::Call(self.u32()?),
+                0x1A => Op::Drop,
+                0x1B => Op::Select,
+                0x20 => Op::LocalGet(self.u32()?),
+                0x21 => Op::LocalSet(self.u32()?),
+                0x22 => Op::LocalTee(self.u32()?),
+                0x23 => Op::GlobalGet(self.u32()?),
+                0x24 => Op::GlobalSet(self.u32()?),
+                0x28 | 0x29 | 0x2D | 0x2F | 0x36 | 0x37 | 0x3A | 0x3B => {
+                    let _align = self.u32()?;
+                    let offset = self.u32()?;
+                    let bytes = match opcode {
+                        0x28 | 0x36 => 4,
+                        0x29 | 0x37 => 8,
+                        0x2D | 0x3A => 1,
+                        _ => 2,
+                    };
+                    if opcode < 0x36 {
+                        Op::Load { bytes, offset }
+                    } else {
+                        Op::Store { bytes, offset }
+                    }
+                }
+                0x3F | 0x40 => {
+                    self.byte()?;
+                    if opcode == 0x3F {
+                        Op::MemorySize
+                    } else {
+                        Op::MemoryGrow
+                    }
+                }
+                0x41 => Op::Const(self.i64()? as u32 as u64),
+                0x42 => Op::Const(self.i64()? as u64),
+                0x45..=0x50 | 0x6A..=0x70 | 0x7C | 0x7E | 0xAD => Op::Numeric(opcode),
+                0xFC => match self.u32()? {
+                    10 => {
+                        self.take(2)?;
+                        Op::MemoryCopy
+                    }
+                    11 => {
+                        self.byte()?;
+                        Op::MemoryFill
+                    }
+                    other => return Err(format!("Unsupported instruction 0xFC {}", other)),
+                },
+                other => return Err(format!("Unsupported instruction {:#x}", other)),
+            };
+            ops.push(op);
+        }
+    }
+}
+
+impl Instance {
+    /// Decodes a module in the binary format.
+    pub fn new(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.get(..8) != Some(b"\0asm\x01\0\0\0") {
+            return Err("Not a version 1 module".to_string());
+        }
+        let mut reader = Reader { bytes, position: 8 };
+        let mut instance = Instance {
+            types: Vec::new(),
+            imports: Vec::new(),
+            functions: Vec::new(),
+            exports: Vec::new(),
+            globals: Vec::new(),
+            memory: Vec::new(),
+        };
+
+        let mut last_id = 0;
+        while reader.position < bytes.len() {
+            let id = reader.byte()?;
+            if id <= last_id {
+                return Err(format!("Section {} out of order", id));
+            }
+            last_id = id;
+            let size = reader.u32()? as usize;
+            let end = reader.position + size;
+            let count = reader.u32()? as usize;
+            for index in 0..count {
+                match id {
+                    1 => {
+                        if reader.byte()? != 0x60 {
+                            return Err("Malformed function type".to_string());
+                        }
+                        let params = reader.value_types()?;
+                        let results = reader.value_types()?;
+                        instance.types.push(FuncType { params, results });
+                    }
+                    2 => {
+                        let _module = reader.name()?;
+                        let name = reader.name()?;
+                        if reader.byte()? != 0x00 {
+                            return Err("Only function imports are supported".to_string());
+                        }
+                        instance.imports.push((name, reader.u32()? as usize));
+                    }
+                    3 => instance.functions.push(Function {
+                        ty: reader.u32()? as usize,
+                        locals: 0,
+                        body: Vec::new(),
+                    }),
+                    5 => {
+                        if reader.byte()? != 0x00 {
+                            return Err("Memory maximums are not supported".to_string());
+                        }
+                        instance.memory = vec![0; reader.u32()? as usize * PAGE];
+                    }
+                    6 => {
+                        reader.take(2)?;
+                        match reader.expression()?.as_slice() {
+                            [Op::Const(value), Op::End] => instance.globals.push(*value),
+                            _ => return Err("Unsupported global initializer".to_string()),
+                        }
+                    }
+                    7 => {
+                        let name = reader.name()?;
+                        let kind = reader.byte()?;
+                        let index = reader.u32()?;
+                        if kind == 0x00 {
+                            instance.exports.push((name, index));
+                        }
+                    }
+                    10 => {
+                        let size = reader.u32()? as usize;
+                        let body_end = reader.position + size;
+                        let mut locals = 0;
+                        for _ in 0..reader.u32()? {
+                            locals += reader.u32()? as usize;
+                            reader.byte()?;
+                        }
+                        let function = instance
+                            .functions
+                            .get_mut(index)
+                            .ok_or("More bodies than functions")?;
+                        function.locals = locals;
+                        function.body = reader.expression()?;
+                        if reader.position != body_end {
+                            return Err("Function body size mismatch".to_string());
+                        }
+                    }
+                    _ => return Err(format!("Unsupported section {}", id)),
+                }
+            }
+            if reader.position != end {
+                return Err(format!("Section {} size mismatch", id));
+            }
+        }
+        Ok(instance)
+    }
+
+    /// Calls the exported function `name` without arguments.
+    pub fn invoke(&mut self, name: &str, host: &mut Host) -> Result<Vec<u64>, String> {
+        let index = self
+            .exports
+            .iter()
+            .find(|(export, _)| export == name)
+            .map(|&(_, index)| index)
+            .ok_or_else(|| format!("No export named `{}`", name))?;
+        let mut stack = Vec::new();
+        self.call(index, &mut stack, host)?;
+        Ok(stack)
+    }
+
+    fn call(&mut self, index: u32, stack: &mut Vec<u64>, host: &mut Host) -> Result<(), String> {
+        let index = index as usize;
+        if let Some((name, ty)) = self.imports.get(index) {
+            let params = self.types[*ty].params;
+            let args = stack.split_off(stack.len() - params);
+            let name = name.clone();
+            stack.extend(host(&name, &args, &mut self.memory)?);
+            return Ok(());
+        }
+
+        let function = index - self.imports.len();
+        let ty = &self.types[self.functions[function].ty];
+        let results = ty.results;
+        let mut locals = stack.split_off(stack.len() - ty.params);
+        locals.resize(locals.len() + self.functions[function].locals, 0);
+        let base = stack.len();
+
+        // Each label holds where a branch continues, the stack height to restore, and
+        // whether it belongs to a loop, which a branch restarts instead of leaving
+        let mut labels: Vec<(usize, usize, bool)> = Vec::new();
+        let mut pc = 0;
+        loop {
+            let op = self.functions[function].body[pc];
+            pc += 1;
+            let mut branch = None;
+            match op {
+                Op::Unreachable => return Err("unreachable".to_string()),
+                Op::Block { end } => labels.push((end + 1, stack.len(), false)),
+                Op::Loop => labels.push((pc, stack.len(), true)),
+                Op::If { else_, end } => {
+                    if pop(stack) as u32 != 0 {
+                        labels.push((end + 1, stack.len(), false));
+                    } else if let Some(else_) = else_ {
+                        labels.push((end + 1, stack.len(), false));
+                        pc = else_ + 1;
+                    } else {
+                        pc = end + 1;
+                    }
+                }
+                Op::Else { end } => pc = end,
+                Op::End => {
+                    if labels.pop().is_none() {
+                        break;
+                    }
+                }
+                Op::Br(depth) => branch = Some(depth),
+                Op::BrIf(depth) => {
+                    if pop(stack) as u32 != 0 {
+                        branch = Some(depth);
+                    }
+                }
+                Op::Return => break,
+                Op::Call(index) => self.call(index, stack, host)?,
+                Op::Drop => {
+                    pop(stack);
+                }
+                Op::Select => {
+                    let condition = pop(stack) as u32;
+                    let second = pop(stack);
+                    let first = pop(stack);
+                    stack.push(if condition != 0 { first } else { second });
+                }
+                Op::LocalGet(index) => stack.push(locals[index as usize]),
+                Op::LocalSet(index) => locals[index as usize] = pop(stack),
+                Op::LocalTee(index) => locals[index as usize] = *stack.last().unwrap(),
+                Op::GlobalGet(index) => stack.push(self.globals[index as usize]),
+                Op::GlobalSet(index) => self.globals[index as usize] = pop(stack),
+                Op::Load { bytes, offset } => {
+                    let address = pop(stack) as u32 as usize + offset as usize;
+                    let mut value = [0; 8];
+                    value[..bytes].copy_from_slice(self.access(address, bytes)?);
+                    stack.push(u64::from_le_bytes(value));
+                }
+                Op::Store { bytes, offset } => {
+                    let value = pop(stack).to_le_bytes();
+                    let address = pop(stack) as u32 as usize + offset as usize;
+                    self.access(address, bytes)?
+                        .copy_from_slice(&value[..bytes]);
+                }
+                Op::MemorySize => stack.push((self.memory.len() / PAGE) as u64),
+                Op::MemoryGrow => {
+                    let pages = self.memory.len() / PAGE;
+                    let extra = pop(stack) as u32 as usize;
+                    if pages + extra > MAX_PAGES {
+                        stack.push(u32::MAX as u64);
+                    } else {
+                        self.memory.resize((pages + extra) * PAGE, 0);
+                        stack.push(pages as u64);
+                    }
+                }
+                Op::MemoryCopy => {
+                    let len = pop(stack) as u32 as usize;
+                    let source = pop(stack) as u32 as usize;
+                    let destination = pop(stack) as u32 as usize;
+                    self.access(source, len)?;
+                    self.access(destination, len)?;
+                    self.memory.copy_within(source..source + len, destination);
+                }
+                Op::MemoryFill => {
+                    let len = pop(stack) as u32 as usize;
+                    let value = pop(stack) as u8;
+                    let destination = pop(stack) as u32 as usize;
+                    self.access(destination, len)?.fill(value);
+                }
+                Op::Const(value) => stack.push(value),
+                Op::Numeric(opcode) => numeric(opcode, stack)?,
+            }
+
+            if let Some(depth) = branch {
+                let Some(target) = labels.len().checked_sub(depth as usize + 1) else {
+                    break;
+                };
+                let (continuation, height, is_loop) = labels[target];
+                stack.truncate(height);
+                labels.truncate(if is_loop { target + 1 } else { target });
+                pc = continuation;
+            }
+        }
+
+        let returned = stack.split_off(stack.len() - results);
+        stack.truncate(base);
+        stack.extend(returned);
+        Ok(())
+    }
+
+    fn access(&mut self, address: usize, len: usize) -> Result<&mut [u8], String> {
+        self.memory
+            .get_mut(address..address + len)
+            .ok_or_else(|| "out of bounds memory access".to_string())
+    }
+}
+
+fn pop(stack: &mut Vec<u64>) -> u64 {
+    stack.pop().expect("Operand stack underflow")
+}
+
+/// Runs a numeric instruction on the operand stack.
+fn numeric(opcode: u8, stack: &mut Vec<u64>) -> Result<(), String> {
+    let result = match opcode {
+        0x45 => (pop(stack) as u32 == 0) as u64,
+        0x50 => (pop(stack) == 0) as u64,
+        0xAD => pop(stack) as u32 as u64,
+        0x7C => {
+            let right = pop(stack);
+            pop(stack).wrapping_add(right)
+        }
+        0x7E => {
+            let right = pop(stack);
+            pop(stack).wrapping_mul(right)
+        }
+        _ => {
+            let right = pop(stack) as u32;
+            let left = pop(stack) as u32;
+            let (signed_left, signed_right) = (left as i32, right as i32);
+            let divisor = |value: u32| {
+                if value == 0 {
+                    Err("integer divide by zero".to_string())
+                } else {
+                    Ok(value)
+                }
+            };
+            let value = match opcode {
+                0x46 => (left == right) as u32,
+                0x47 => (left != right) as u32,
+                0x48 => (signed_left < signed_right) as u32,
+                0x49 => (left < right) as u32,
+                0x4A => (signed_left > signed_right) as u32,
+                0x4B => (left > right) as u32,
+                0x4C => (signed_left <= signed_right) as u32,
+                0x4D => (left <= right) as u32,
+                0x4E => (signed_left >= signed_right) as u32,
+                0x4F => (left >= right) as u32,
+                0x6A => left.wrapping_add(right),
+                0x6B => left.wrapping_sub(right),
+                0x6C => left.wrapping_mul(right),
+                0x6D => signed_left.wrapping_div(divisor(right)? as i32) as u32,
+                0x6E => left / divisor(right)?,
+                0x6F => signed_left.wrapping_rem(divisor(right)? as i32) as u32,
+                0x70 => left % divisor(right)?,
+                other => return Err(format!("Unsupported instruction {:#x}", other)),
+            };
+            value as u64
+        }
+    };
+    stack.push(result);
+    Ok(())
+}
@@ -0,0 +1,204 @@
+#[path = "../common/mod.rs"]
+mod common;
+mod evaluator;
+
+use bf_rs::{
+    codegen::{emit_wasm, emit_wat, CodegenOptions},
+    interpreter::{CellWidth, EofPolicy, TapeConfig},
+    optimizer::Optimizer,
+    parser::BfOp,
+};
+use common::{interpret, parse};
+use evaluator::Instance;
+
+/// Compiles `program` to a binary module and runs it on the evaluator, turning the
+/// errors reported through the `error` import into the interpreter's messages.
+fn evaluate(program: &[BfOp], options: &CodegenOptions, input: &[u8]) -> (Vec<u8>, String) {
+    let mut instance = Instance::new(&emit_wasm(program, options)).expect("Invalid module");
+    let mut input = input.iter();
+    let mut output = Vec::new();
+    let mut error = String::new();
+
+    let result = instance.invoke("run", &mut |name, args, _memory| match name {
+        "output" => {
+            output.push(args[0] as u8);
+            Ok(None)
+        }
+        "input" => Ok(Some(input.next().map_or(-1i32 as u32, |&byte| byte as u32) as u64)),
+        "error" => {
+            let [kind, line, column, a, b] = [0, 1, 2, 3, 4].map(|i| args[i] as u32);
+            error = match kind {
+                1 => format!("Pointer underflow at line {}, column {}: attempted to move left {} steps when pointer was at position {}", line, column, a, b),
+                2 => format!("Pointer overflow at line {}, column {}: attempted to move right {} steps when pointer was at position {}", line, column, a, b),
+                3 => format!("Tape limit exceeded at line {}, column {}: the tape cannot grow beyond {} cells, pointer was at position {}", line, column, a, b as i32),
+                _ => format!("Unexpected end of input at line {}, column {}", line, column),
+            };
+            Ok(None)
+        }
+        _ => Err(format!("Unknown import `{}`", name)),
+    });
+    if let Err(trap) = result {
+        assert!(!error.is_empty(), "The module trapped: {}", trap);
+    }
+    (output, error)
+}
+
+#[test]
+fn wat_is_well_formed() {
+    let source = include_str!("../../examples/hello_world.bf");
+    let wat = emit_wat(&parse(source), &CodegenOptions::default());
+
+    assert!(wat.starts_with("(module\n"));
+    assert!(wat.contains("(import \"env\" \"output\" (func $output (param i32)))"));
+    assert!(wat.contains("(import \"env\" \"input\" (func $input (result i32)))"));
+    assert!(wat.contains("(memory (export \"memory\") 1)"));
+    assert!(wat.contains("(func $run (export \"run\")"));
+
+    let mut depth = 0;
+    for c in wat.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        assert!(depth >= 0, "Unbalanced parentheses");
+    }
+    assert_eq!(depth, 0, "Unbalanced parentheses");
+
+    // Every block is closed, and each loop of the program becomes a `loop`
+    let instructions: Vec<&str> = wat.lines().map(str::trim).collect();
+    let opened = instructions
+        .iter()
+        .filter(|line| matches!(**line, "block" | "loop" | "if"))
+        .count();
+    let closed = instructions.iter().filter(|line| **line == "end").count();
+    assert_eq!(opened, closed);
+    let loops = instructions.iter().filter(|line| **line == "loop").count();
+    assert_eq!(loops, source.matches('[').count());
+}
+
+#[test]
+fn wasm_matches_interpreter() {
+    let cases = [
+        (
+            include_str!("../../examples/hello_world.bf"),
+            CodegenOptions::default(),
+        ),
+        ("+.<", CodegenOptions::default()),
+        (
+            ">>>.<",
+            CodegenOptions {
+                tape: TapeConfig::Fixed { cells: 3 },
+                ..Default::default()
+            },
+        ),
+        (
+            "<<<+.>>>>>>>>+.<<<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Wrapping { cells: 7 },
+                ..Default::default()
+            },
+        ),
+        (
+            "+[>+.]",
+            CodegenOptions {
+                tape: TapeConfig::Growing {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            "+[<+.]",
+            CodegenOptions {
+                tape: TapeConfig::Bidirectional {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            ",.,.,.,.",
+            CodegenOptions {
+                eof_policy: EofPolicy::Error,
+                ..Default::default()
+            },
+        ),
+        (
+            ",+[-.,+]>,+.",
+            CodegenOptions {
+                eof_policy: EofPolicy::MinusOne,
+                ..Default::default()
+            },
+        ),
+        (
+            "-.>+++++++++++++++[-<+++++++++++++++++>]<.[-]+.",
+            CodegenOptions::default(),
+        ),
+        // Cells at an offset outside the tape
+        (
+            ">>>+<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Fixed { cells: 3 },
+                ..Default::default()
+            },
+        ),
+        (
+            "+>>>>>>>>++<<<<<<<<.>.",
+            CodegenOptions {
+                tape: TapeConfig::Wrapping { cells: 7 },
+                ..Default::default()
+            },
+        ),
+        (
+            "++[->>>>>>+<<<<<<]>>>>>>.",
+            CodegenOptions {
+                tape: TapeConfig::Growing {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+        (
+            "++[-<<<<+>>>>]<<<<.",
+            CodegenOptions {
+                tape: TapeConfig::Bidirectional {
+                    initial: 4,
+                    max: 300,
+                },
+                ..Default::default()
+            },
+        ),
+    ];
+
+    for (source, options) in cases {
+        let program = parse(source);
+        let program = Optimizer::new().optimize(program);
+        for cell_width in [
+            CellWidth::U8,
+            CellWidth::U16,
+            CellWidth::U32,
+            CellWidth::U64,
+        ] {
+            let options = CodegenOptions {
+                cell_width,
+                ..options
+            };
+            let expected = match cell_width {
+                CellWidth::U8 => interpret::<u8>(&program, &options, b"wasm"),
+                CellWidth::U16 => interpret::<u16>(&program, &options, b"wasm"),
+                CellWidth::U32 => interpret::<u32>(&program, &options, b"wasm"),
+                CellWidth::U64 => interpret::<u64>(&program, &options, b"wasm"),
+            };
+            let actual = evaluate(&program, &options, b"wasm");
+            assert_eq!(
+                expected, actual,
+                "`{}` behaves differently with {}-bit cells",
+                source, cell_width
+            );
+        }
+    }
+}